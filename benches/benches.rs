@@ -172,6 +172,34 @@ pub fn hash_block_txs(c: &mut Criterion) {
                 black_box((&block, tx_hashes));
             })
         });
+
+    #[cfg(feature = "rayon")]
+    c.benchmark_group("hash_block_txs")
+        .throughput(criterion::Throughput::Bytes(mainnet_702861().len() as u64))
+        .bench_function("slices_par", |b| {
+            b.iter(|| {
+                #[derive(Clone)]
+                struct VisitTx(Vec<sha256d::Hash>);
+                let mut v = VisitTx(vec![]);
+                impl Visitor for VisitTx {
+                    fn visit_transaction(&mut self, tx: &Transaction) -> ControlFlow<()> {
+                        self.0.push(tx.txid());
+                        ControlFlow::Continue(())
+                    }
+                }
+                impl bitcoin_slices::combinators::Merge for VisitTx {
+                    fn merge(&mut self, other: Self) {
+                        self.0.extend(other.0);
+                    }
+                }
+
+                let block = Block::par_visit(mainnet_702861(), &mut v).unwrap();
+
+                assert_eq!(v.0.len(), 2500);
+
+                black_box((&block, v));
+            })
+        });
 }
 
 const TXID: &str = "416a5f96cb63e7649f6f272e7f82a43a97bcf6cfc46184c733344de96ff1e433";
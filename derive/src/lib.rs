@@ -0,0 +1,448 @@
+//! Macros implementing the `bitcoin_slices::Visitor` trait, use them through the `derive` feature
+//! of `bitcoin_slices`.
+//!
+//! * `#[visitor]` on an `impl` block implements `Visitor` by calling the methods annotated with
+//!   `#[visit(callback)]`, for example `#[visit(tx_out)] fn on_out(&mut self, vout: usize, out: &bsl::TxOut)`.
+//! * `#[derive(Visitor)]` on a struct forwards every callback to the fields, which must implement
+//!   `Visitor`, except fields annotated with `#[visit(callback)]` which are closures called for the
+//!   given callback and fields annotated with `#[visit(skip)]`.
+//!
+//! Both accept `#[visitor(break_type = T)]` to implement `Visitor<T>` instead of `Visitor<()>`.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    Attribute, Data, DeriveInput, Fields, Ident, ImplItem, ItemImpl, Member, Token, Type,
+};
+
+/// What a `Visitor` callback returns
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Returns {
+    /// `()`
+    Unit,
+    /// `ControlFlow<B>`
+    Flow,
+    /// `ControlFlow<B, Descend>`
+    Descend,
+}
+
+/// The subtree of the transaction that must be visited for the callback to be called
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Needs {
+    Nothing,
+    TxIns,
+    TxOuts,
+    Witnesses,
+}
+
+struct Callback {
+    name: &'static str,
+    args: &'static [(&'static str, &'static str)],
+    returns: Returns,
+    needs: Needs,
+}
+
+const CALLBACKS: &[Callback] = &[
+    Callback {
+        name: "block_header",
+        args: &[("header", "&::bitcoin_slices::bsl::BlockHeader")],
+        returns: Returns::Flow,
+        needs: Needs::Nothing,
+    },
+    Callback {
+        name: "block_begin",
+        args: &[("total_transactions", "usize")],
+        returns: Returns::Unit,
+        needs: Needs::Nothing,
+    },
+    Callback {
+        name: "subtree",
+        args: &[("subtree", "::bitcoin_slices::Subtree")],
+        returns: Returns::Descend,
+        needs: Needs::Nothing,
+    },
+    Callback {
+        name: "transaction",
+        args: &[("tx", "&::bitcoin_slices::bsl::Transaction")],
+        returns: Returns::Flow,
+        needs: Needs::Nothing,
+    },
+    Callback {
+        name: "tx_ins",
+        args: &[("total_inputs", "usize")],
+        returns: Returns::Unit,
+        needs: Needs::TxIns,
+    },
+    Callback {
+        name: "tx_in",
+        args: &[("vin", "usize"), ("tx_in", "&::bitcoin_slices::bsl::TxIn")],
+        returns: Returns::Flow,
+        needs: Needs::TxIns,
+    },
+    Callback {
+        name: "tx_outs",
+        args: &[("total_outputs", "usize")],
+        returns: Returns::Unit,
+        needs: Needs::TxOuts,
+    },
+    Callback {
+        name: "tx_out",
+        args: &[
+            ("vout", "usize"),
+            ("tx_out", "&::bitcoin_slices::bsl::TxOut"),
+        ],
+        returns: Returns::Flow,
+        needs: Needs::TxOuts,
+    },
+    Callback {
+        name: "witness",
+        args: &[("vin", "usize")],
+        returns: Returns::Flow,
+        needs: Needs::Witnesses,
+    },
+    Callback {
+        name: "witness_total_element",
+        args: &[("witness_total", "usize")],
+        returns: Returns::Unit,
+        needs: Needs::Witnesses,
+    },
+    Callback {
+        name: "witness_element",
+        args: &[("witness_i", "usize"), ("witness_element", "&[u8]")],
+        returns: Returns::Unit,
+        needs: Needs::Witnesses,
+    },
+    Callback {
+        name: "witness_end",
+        args: &[],
+        returns: Returns::Unit,
+        needs: Needs::Witnesses,
+    },
+];
+
+fn find_callback(ident: &Ident) -> syn::Result<&'static Callback> {
+    CALLBACKS.iter().find(|c| ident == c.name).ok_or_else(|| {
+        let names: Vec<_> = CALLBACKS.iter().map(|c| c.name).collect();
+        syn::Error::new(
+            ident.span(),
+            format!(
+                "unknown visitor callback, expected one of: {}",
+                names.join(", ")
+            ),
+        )
+    })
+}
+
+/// The arguments of `#[visitor(...)]`
+#[derive(Default)]
+struct VisitorArgs {
+    break_type: Option<Type>,
+}
+
+impl Parse for VisitorArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = VisitorArgs::default();
+        if input.is_empty() {
+            return Ok(args);
+        }
+        let key: Ident = input.parse()?;
+        if key != "break_type" {
+            return Err(syn::Error::new(key.span(), "expected `break_type = T`"));
+        }
+        input.parse::<Token![=]>()?;
+        args.break_type = Some(input.parse()?);
+        Ok(args)
+    }
+}
+
+/// The content of a `#[visit(...)]` attribute
+enum Visit {
+    Skip,
+    Callbacks(Vec<&'static Callback>),
+}
+
+fn parse_visit_attr(attrs: &[Attribute]) -> syn::Result<Option<Visit>> {
+    let mut result = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("visit")) {
+        if result.is_some() {
+            return Err(syn::Error::new(attr.span(), "duplicated `visit` attribute"));
+        }
+        let idents =
+            attr.parse_args_with(Punctuated::<Ident, Token![,]>::parse_separated_nonempty)?;
+        if idents.len() == 1 && idents[0] == "skip" {
+            result = Some(Visit::Skip);
+        } else {
+            let callbacks = idents
+                .iter()
+                .map(find_callback)
+                .collect::<syn::Result<Vec<_>>>()?;
+            result = Some(Visit::Callbacks(callbacks));
+        }
+    }
+    Ok(result)
+}
+
+/// How a callback is dispatched to a member of the visitor
+enum Target {
+    /// A field implementing `Visitor`
+    Visitor(Member, Box<Type>),
+    /// A method of the visitor
+    Method(Ident),
+    /// A field containing a closure
+    Closure(Member),
+}
+
+/// Generate the `Visitor` implementation calling `targets` for every callback
+fn expand(
+    callbacks: &[(&'static Callback, Target)],
+    visitors: &[Target],
+    break_type: &TokenStream2,
+) -> TokenStream2 {
+    let krate = quote!(::bitcoin_slices);
+    let private = quote!(#krate::__private);
+    let flow = quote!(::core::ops::ControlFlow);
+    let mut methods = vec![];
+
+    for callback in CALLBACKS {
+        let targets: Vec<_> = callbacks
+            .iter()
+            .filter(|(c, _)| c.name == callback.name)
+            .map(|(_, t)| t)
+            .collect();
+        let user_defined = !targets.is_empty();
+        if !user_defined && visitors.is_empty() && callback.returns != Returns::Descend {
+            continue;
+        }
+
+        let method = format_ident!("visit_{}", callback.name);
+        let arg_names: Vec<_> = callback
+            .args
+            .iter()
+            .map(|(name, _)| Ident::new(name, Span::call_site()))
+            .collect();
+        let arg_types: Vec<TokenStream2> = callback
+            .args
+            .iter()
+            .map(|(_, ty)| ty.parse().expect("statically defined"))
+            .collect();
+
+        let calls: Vec<_> = visitors
+            .iter()
+            .chain(targets.iter().copied())
+            .map(|target| match target {
+                Target::Visitor(member, ty) => quote! {
+                    <#ty as #krate::Visitor<#break_type>>::#method(&mut self.#member, #(#arg_names),*)
+                },
+                Target::Method(ident) => quote! { self.#ident(#(#arg_names),*) },
+                Target::Closure(member) => quote! { (self.#member)(#(#arg_names),*) },
+            })
+            .collect();
+
+        let body = match callback.returns {
+            Returns::Unit => quote! { #(#calls;)* },
+            Returns::Flow => quote! {
+                let mut result = #flow::Continue(());
+                #(
+                    result = #private::merge(
+                        result,
+                        #private::IntoControlFlow::<#break_type, ()>::into_control_flow(#calls),
+                    );
+                )*
+                result
+            },
+            Returns::Descend if user_defined => quote! {
+                let mut result = #flow::Continue(#krate::Descend::Skip);
+                #(
+                    result = #private::merge_descend(
+                        result,
+                        #private::IntoControlFlow::<#break_type, #krate::Descend>::into_control_flow(#calls),
+                    );
+                )*
+                result
+            },
+            Returns::Descend => {
+                // descend only in the subtrees needed by the callbacks, or wanted by the visitors
+                let needed = |needs: Needs| callbacks.iter().any(|(c, _)| c.needs == needs);
+                let descend = |needs: Needs| {
+                    if needed(needs) {
+                        quote!(#krate::Descend::Visit)
+                    } else {
+                        quote!(#krate::Descend::Skip)
+                    }
+                };
+                let (tx_ins, tx_outs, witnesses) = (
+                    descend(Needs::TxIns),
+                    descend(Needs::TxOuts),
+                    descend(Needs::Witnesses),
+                );
+                quote! {
+                    let mut result = #flow::Continue(match subtree {
+                        #krate::Subtree::TxIns => #tx_ins,
+                        #krate::Subtree::TxOuts => #tx_outs,
+                        #krate::Subtree::Witnesses => #witnesses,
+                    });
+                    #(
+                        result = #private::merge_descend(result, #calls);
+                    )*
+                    result
+                }
+            }
+        };
+        let returns = match callback.returns {
+            Returns::Unit => quote!(),
+            Returns::Flow => quote!(-> #flow<#break_type>),
+            Returns::Descend => quote!(-> #flow<#break_type, #krate::Descend>),
+        };
+        methods.push(quote! {
+            #[allow(unused_variables)]
+            fn #method(&mut self, #(#arg_names: #arg_types),*) #returns {
+                #body
+            }
+        });
+    }
+    quote! { #(#methods)* }
+}
+
+fn break_type(args: &VisitorArgs) -> TokenStream2 {
+    match &args.break_type {
+        Some(ty) => quote!(#ty),
+        None => quote!(()),
+    }
+}
+
+/// Implement `Visitor` for the type of the annotated `impl` block, by calling the methods
+/// annotated with `#[visit(callback)]` where `callback` is the name of the `Visitor` method
+/// without the `visit_` prefix.
+///
+/// Methods for callbacks returning a `ControlFlow` may return `()` to always continue.
+/// Subtrees of the transaction not needed by any method are skipped, unless a method handles the
+/// `subtree` callback.
+#[proc_macro_attribute]
+pub fn visitor(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as VisitorArgs);
+    let mut item = parse_macro_input!(item as ItemImpl);
+    match visitor_impl(&args, &mut item) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn visitor_impl(args: &VisitorArgs, item: &mut ItemImpl) -> syn::Result<TokenStream2> {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(syn::Error::new(
+            path.span(),
+            "`visitor` must be used on inherent impl blocks",
+        ));
+    }
+    let mut callbacks = vec![];
+    for impl_item in item.items.iter_mut() {
+        if let ImplItem::Fn(method) = impl_item {
+            match parse_visit_attr(&method.attrs)? {
+                Some(Visit::Callbacks(cs)) => {
+                    for c in cs {
+                        callbacks.push((c, Target::Method(method.sig.ident.clone())));
+                    }
+                }
+                Some(Visit::Skip) => {
+                    return Err(syn::Error::new(
+                        method.sig.span(),
+                        "`skip` is allowed only on struct fields",
+                    ))
+                }
+                None => (),
+            }
+            method.attrs.retain(|a| !a.path().is_ident("visit"));
+        }
+    }
+
+    let break_type = break_type(args);
+    let methods = expand(&callbacks, &[], &break_type);
+    let self_ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    Ok(quote! {
+        #item
+
+        impl #impl_generics ::bitcoin_slices::Visitor<#break_type> for #self_ty #where_clause {
+            #methods
+        }
+    })
+}
+
+/// Implement `Visitor` for a struct by forwarding every callback to its fields.
+///
+/// Fields must implement `Visitor`, unless annotated with `#[visit(skip)]` or with
+/// `#[visit(callback)]`, in which case the field is a closure called for the given callback with
+/// the same arguments of the `Visitor` method.
+/// Use `#[visitor(break_type = T)]` on the struct to implement `Visitor<T>`.
+#[proc_macro_derive(Visitor, attributes(visit, visitor))]
+pub fn derive_visitor(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match derive_visitor_impl(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn derive_visitor_impl(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "`Visitor` can be derived only for structs",
+            ))
+        }
+    };
+    let mut args = VisitorArgs::default();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("visitor")) {
+        args = attr.parse_args()?;
+    }
+    let break_type = break_type(&args);
+
+    let mut callbacks = vec![];
+    let mut visitors = vec![];
+    let members: Vec<Member> = match fields {
+        Fields::Named(named) => named
+            .named
+            .iter()
+            .map(|f| Member::Named(f.ident.clone().expect("named")))
+            .collect(),
+        Fields::Unnamed(unnamed) => (0..unnamed.unnamed.len()).map(Member::from).collect(),
+        Fields::Unit => vec![],
+    };
+    for (field, member) in fields.iter().zip(members) {
+        match parse_visit_attr(&field.attrs)? {
+            Some(Visit::Skip) => (),
+            Some(Visit::Callbacks(cs)) => {
+                for c in cs {
+                    callbacks.push((c, Target::Closure(member.clone())));
+                }
+            }
+            None => visitors.push(Target::Visitor(member, Box::new(field.ty.clone()))),
+        }
+    }
+
+    let methods = expand(&callbacks, &visitors, &break_type);
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut predicates: Vec<TokenStream2> = where_clause
+        .map(|w| w.predicates.iter().map(|p| quote!(#p)).collect())
+        .unwrap_or_default();
+    for visitor in visitors.iter() {
+        if let Target::Visitor(_, ty) = visitor {
+            predicates.push(quote!(#ty: ::bitcoin_slices::Visitor<#break_type>));
+        }
+    }
+    Ok(quote! {
+        impl #impl_generics ::bitcoin_slices::Visitor<#break_type> for #ident #ty_generics
+        where #(#predicates),*
+        {
+            #methods
+        }
+    })
+}
@@ -1,6 +1,7 @@
 use super::len::{parse_len, Len};
 use crate::bsl::{BlockHeader, Transaction};
-use crate::{ParseResult, SResult, Visit, Visitor};
+use crate::{AsSlice, Error, FromSlice, Parse};
+use crate::{BResult, LocatedError, ParseResult, Visit, Visitor};
 
 /// A Bitcoin block.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -11,8 +12,11 @@ pub struct Block<'a> {
 }
 
 impl<'a> Visit<'a> for Block<'a> {
-    fn visit<'b, V: Visitor>(slice: &'a [u8], visit: &'b mut V) -> SResult<'a, Self> {
-        let header = BlockHeader::visit(slice, visit)?;
+    fn visit_break<'b, B, V: Visitor<B>>(
+        slice: &'a [u8],
+        visit: &'b mut V,
+    ) -> BResult<'a, Self, B> {
+        let header = BlockHeader::visit_break(slice, visit)?;
         let Len { mut consumed, n } = parse_len(header.remaining())?;
         consumed += 80;
         let total_txs = n as usize;
@@ -20,7 +24,7 @@ impl<'a> Visit<'a> for Block<'a> {
 
         visit.visit_block_begin(total_txs);
         for _ in 0..total_txs {
-            let tx = Transaction::visit(remaining, visit)?;
+            let tx = Transaction::visit_break(remaining, visit)?;
             remaining = tx.remaining();
             consumed += tx.consumed();
         }
@@ -52,15 +56,126 @@ impl<'a> Block<'a> {
         self.header.block_hash_sha2()
     }
 
+    /// Like [`Parse::parse`](crate::Parse::parse) but on failure returns a [`LocatedError`]
+    /// with the offset and path of the element that failed, like `tx 1234 / input 5 / script_sig`.
+    ///
+    /// On success this has the same cost of parsing, the location is computed only on failure.
+    pub fn parse_located(slice: &'a [u8]) -> Result<ParseResult<'a, Block<'a>>, LocatedError> {
+        Self::parse(slice).map_err(|e| crate::located::locate_block(slice, e))
+    }
+
+    /// Context-free checks of the block validity, mirroring the Bitcoin Core checks which don't
+    /// need the UTXO set.
+    ///
+    /// Checks the merkle root, rejecting trees mutated with duplicate transactions, that there
+    /// is exactly one coinbase and it is the first transaction, the witness commitment and the
+    /// weight limit. If `height` is given, checks the coinbase starts with it as required by
+    /// BIP 34.
+    ///
+    /// Transactions are not checked, use [`Transaction::check_sanity`] for that.
+    #[cfg(feature = "bitcoin_hashes")]
+    pub fn check(&self, height: Option<u32>) -> Result<(), crate::consensus::BlockError> {
+        crate::consensus::check_block(self, height)
+    }
+
+    /// Creates the block from a slice validated by a previous parsing and its cached fields
+    #[cfg(feature = "alloc")]
+    pub(crate) fn from_parts(slice: &'a [u8], total_txs: usize) -> Self {
+        use crate::{bsl::BlockHeader, Parse};
+        let header = BlockHeader::parse(slice).expect("slice validated during parsing");
+        Block {
+            slice,
+            header: header.parsed_owned(),
+            total_txs,
+        }
+    }
+
     /// Returns the total transactions in this block
     pub fn total_transactions(&self) -> usize {
         self.total_txs
     }
 
     /// Returns the header in this block
-    pub fn header(&self) -> &BlockHeader {
+    pub fn header(&self) -> &BlockHeader<'_> {
         &self.header
     }
+
+    /// Like [`Visit::visit`] but visiting the transactions of the block on many threads.
+    ///
+    /// After a parsing-only scan to find the transaction boundaries, the transactions are split
+    /// in ranges of similar size in bytes. Every range is visited on the rayon thread pool by a
+    /// clone of `visit` taken before any callback, then the clones are merged back into `visit`
+    /// in block order with [`Merge::merge`](crate::combinators::Merge::merge).
+    ///
+    /// `visit` itself only receives the block level callbacks, as such the result is the same
+    /// as [`Visit::visit`] only if the visitor state doesn't depend on the order of the
+    /// transactions in a way that [`Merge`](crate::combinators::Merge) can't express.
+    ///
+    /// Visiting happens only after the whole block is parsed, so a block with a parsing error
+    /// produces no transaction callbacks. If any clone breaks, [`crate::Error::VisitBreak`]
+    /// is returned and the other clones states are lost.
+    #[cfg(feature = "rayon")]
+    pub fn par_visit<V>(slice: &'a [u8], visit: &mut V) -> crate::SResult<'a, Block<'a>>
+    where
+        V: Visitor + crate::combinators::Merge + Clone + Send,
+    {
+        use rayon::prelude::*;
+        use std::vec::Vec;
+
+        let template = visit.clone();
+        let header = BlockHeader::visit(slice, visit)?;
+        let Len { mut consumed, n } = parse_len(header.remaining())?;
+        consumed += 80;
+        let total_txs = n as usize;
+        let txs_start = consumed;
+
+        let mut ends = Vec::with_capacity(total_txs);
+        let mut remaining = &slice[consumed..];
+        for _ in 0..total_txs {
+            let tx = Transaction::parse(remaining)?;
+            remaining = tx.remaining();
+            consumed += tx.consumed();
+            ends.push(consumed);
+        }
+        visit.visit_block_begin(total_txs);
+
+        let chunks = rayon::current_num_threads().max(1);
+        // TODO use div_ceil once MSRV allows
+        #[allow(clippy::manual_div_ceil)]
+        let chunk_bytes = ((consumed - txs_start + chunks - 1) / chunks).max(1);
+        let mut ranges = Vec::with_capacity(chunks);
+        let mut start = txs_start;
+        for (i, end) in ends.iter().enumerate() {
+            if *end - start >= chunk_bytes || i + 1 == ends.len() {
+                ranges.push((start, *end));
+                start = *end;
+            }
+        }
+        let visitors: Vec<V> = ranges.iter().map(|_| template.clone()).collect();
+
+        let visited = ranges
+            .into_par_iter()
+            .zip(visitors)
+            .map(|((start, end), mut v)| {
+                let mut remaining = &slice[start..end];
+                while !remaining.is_empty() {
+                    remaining = Transaction::visit(remaining, &mut v)?.remaining();
+                }
+                Ok(v)
+            })
+            .collect::<Result<Vec<V>, crate::Error>>()?;
+        for v in visited {
+            visit.merge(v);
+        }
+
+        let (slice, remaining) = slice.split_at(consumed);
+        let parsed = Block {
+            slice,
+            header: header.parsed_owned(),
+            total_txs,
+        };
+        Ok(ParseResult::new(remaining, parsed))
+    }
 }
 
 impl<'a> AsRef<[u8]> for Block<'a> {
@@ -109,6 +224,68 @@ pub mod visitor {
     }
 }
 
+impl AsSlice for Block<'_> {
+    fn as_slice(&self) -> &[u8] {
+        self.slice
+    }
+}
+
+impl<'a> FromSlice<'a> for Block<'a> {
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        crate::codec::exact(Block::parse(slice))
+    }
+
+    /// Parses only the header and the transactions count
+    fn from_trusted_slice(slice: &'a [u8]) -> Self {
+        let header = BlockHeader::parse(slice).expect("inserted data is not a Block");
+        let total_txs = parse_len(header.remaining())
+            .expect("inserted data is not a Block")
+            .n() as usize;
+        Block {
+            slice,
+            header: header.parsed_owned(),
+            total_txs,
+        }
+    }
+}
+
+#[cfg(feature = "redb")]
+impl<'o> redb::RedbValue for Block<'o> {
+    // TODO fix where position once MSRV allows
+    type SelfType<'a>
+    where
+        Self: 'a,
+    = Block<'a>;
+
+    type AsBytes<'a>
+    where
+        Self: 'a,
+    = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+        Block::from_trusted_slice(data)
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value.as_ref()
+    }
+
+    fn type_name() -> redb::TypeName {
+        redb::TypeName::new("bsl::Block")
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
@@ -139,6 +316,30 @@ mod test {
         // assert!(iter.next().is_none())
     }
 
+    #[cfg(feature = "redb")]
+    #[test]
+    fn test_block_redb() {
+        use redb::ReadableTable;
+
+        const TABLE: redb::TableDefinition<u32, Block> = redb::TableDefinition::new("blocks");
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let db = redb::Database::create(path).unwrap();
+        let block = Block::parse(&GENESIS_BLOCK).unwrap().parsed_owned();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            table.insert(0, &block).unwrap();
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(TABLE).unwrap();
+        let read = table.get(0).unwrap().unwrap();
+        assert_eq!(read.value(), block);
+        assert_eq!(read.value().total_transactions(), 1);
+    }
+
     #[cfg(all(feature = "bitcoin", feature = "sha2"))]
     #[test]
     fn find_tx() {
@@ -156,6 +357,140 @@ mod test {
         assert_eq!(tx.compute_txid(), txid);
     }
 
+    #[test]
+    fn visit_break() {
+        use crate::{bsl::Transaction, bsl::TxOut, Stop, Visit, Visitor};
+        use bitcoin_test_data::blocks::mainnet_702861;
+        use core::ops::ControlFlow;
+
+        struct FirstVersion1 {
+            total: usize,
+            current: usize,
+        }
+        impl Visitor<(usize, usize)> for FirstVersion1 {
+            fn visit_block_begin(&mut self, total_transactions: usize) {
+                self.total = total_transactions;
+            }
+            fn visit_transaction(&mut self, tx: &Transaction) -> ControlFlow<(usize, usize)> {
+                self.current += 1;
+                if tx.version() == 1 {
+                    ControlFlow::Break((self.current - 1, tx.as_ref().len()))
+                } else {
+                    ControlFlow::Continue(())
+                }
+            }
+        }
+        let block_bytes = mainnet_702861();
+        let mut visitor = FirstVersion1 {
+            total: 0,
+            current: 0,
+        };
+        let (value, mut remaining) = match Block::visit_break(block_bytes, &mut visitor) {
+            Err(Stop::Break { value, remaining }) => (value, remaining),
+            _ => panic!("must break"),
+        };
+        let (index, len) = value;
+        assert!(index > 0);
+        assert!(len > 0);
+
+        // resume parsing from where the visitor stopped
+        for _ in index + 1..visitor.total {
+            remaining = Transaction::parse(remaining).unwrap().remaining();
+        }
+        assert!(remaining.is_empty());
+
+        struct OutputValue;
+        impl Visitor<u64> for OutputValue {
+            fn visit_tx_out(&mut self, _vout: usize, tx_out: &TxOut) -> ControlFlow<u64> {
+                ControlFlow::Break(tx_out.value())
+            }
+        }
+        let result = Block::visit_break(&GENESIS_BLOCK[..], &mut OutputValue);
+        match result {
+            Err(Stop::Break { value, remaining }) => {
+                assert_eq!(value, 50 * 100_000_000);
+                assert_eq!(remaining, &[0u8; 4][..]); // the genesis locktime
+            }
+            _ => panic!("must break"),
+        }
+
+        let result = Block::visit_break(&GENESIS_BLOCK[..200], &mut OutputValue);
+        assert_eq!(result.unwrap_err().into_break(), None);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_visit() {
+        use crate::{bsl::Transaction, bsl::TxOut, combinators::Merge, Visit, Visitor};
+        use bitcoin_test_data::blocks::mainnet_702861;
+        use core::ops::ControlFlow;
+
+        #[derive(Clone, Default, Debug, PartialEq, Eq)]
+        struct Stats {
+            begin: usize,
+            txs: usize,
+            outputs: usize,
+            value: u64,
+            versions: Vec<i32>,
+        }
+        impl Visitor for Stats {
+            fn visit_block_begin(&mut self, total_transactions: usize) {
+                self.begin = total_transactions;
+            }
+            fn visit_transaction(&mut self, tx: &Transaction) -> ControlFlow<()> {
+                self.txs += 1;
+                self.versions.push(tx.version());
+                ControlFlow::Continue(())
+            }
+            fn visit_tx_out(&mut self, _vout: usize, tx_out: &TxOut) -> ControlFlow<()> {
+                self.outputs += 1;
+                self.value += tx_out.value();
+                ControlFlow::Continue(())
+            }
+        }
+        impl Merge for Stats {
+            fn merge(&mut self, other: Self) {
+                self.txs += other.txs;
+                self.outputs += other.outputs;
+                self.value += other.value;
+                self.versions.extend(other.versions);
+            }
+        }
+
+        for bytes in [&GENESIS_BLOCK[..], mainnet_702861()] {
+            let mut expected = Stats::default();
+            let block = Block::visit(bytes, &mut expected).unwrap();
+            let mut stats = Stats::default();
+            let par_block = Block::par_visit(bytes, &mut stats).unwrap();
+            assert_eq!(block, par_block);
+            assert_eq!(expected, stats);
+            assert_eq!(stats.txs, block.parsed().total_transactions());
+        }
+
+        let mut stats = Stats::default();
+        assert!(Block::par_visit(&GENESIS_BLOCK[..200], &mut stats).is_err());
+        assert_eq!(stats.txs, 0);
+
+        #[derive(Clone)]
+        struct BreakOnVersion1;
+        impl Visitor for BreakOnVersion1 {
+            fn visit_transaction(&mut self, tx: &Transaction) -> ControlFlow<()> {
+                if tx.version() == 1 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            }
+        }
+        impl Merge for BreakOnVersion1 {
+            fn merge(&mut self, _other: Self) {}
+        }
+        assert_eq!(
+            Block::par_visit(mainnet_702861(), &mut BreakOnVersion1),
+            Err(crate::Error::VisitBreak)
+        );
+    }
+
     #[cfg(target_pointer_width = "64")]
     #[test]
     fn size_of() {
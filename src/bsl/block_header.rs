@@ -1,10 +1,10 @@
-use core::ops::ControlFlow;
-
 use crate::{
     number::{I32, U32},
     slice::read_slice,
-    Parse, ParseResult, SResult, Visit, Visitor,
+    visit::check,
+    BResult, Parse, Visit, Visitor,
 };
+use crate::{AsSlice, Error, FromSlice};
 
 /// The block header.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -17,7 +17,10 @@ pub struct BlockHeader<'a> {
 }
 
 impl<'a> Visit<'a> for BlockHeader<'a> {
-    fn visit<'b, V: Visitor>(slice: &'a [u8], visit: &'b mut V) -> SResult<'a, Self> {
+    fn visit_break<'b, B, V: Visitor<B>>(
+        slice: &'a [u8],
+        visit: &'b mut V,
+    ) -> BResult<'a, Self, B> {
         let version = I32::parse(slice)?;
         let hashes = read_slice(version.remaining(), 64)?;
         let time = U32::parse(hashes.remaining())?;
@@ -30,10 +33,7 @@ impl<'a> Visit<'a> for BlockHeader<'a> {
             bits: bits.parsed().into(),
             nonce: nonce.parsed().into(),
         };
-        if let ControlFlow::Break(_) = visit.visit_block_header(&header) {
-            return Err(crate::Error::VisitBreak);
-        }
-        Ok(ParseResult::new(nonce.remaining(), header))
+        check(visit.visit_block_header(&header), nonce.remaining(), header)
     }
 }
 
@@ -97,6 +97,55 @@ impl<'a> AsRef<[u8]> for BlockHeader<'a> {
     }
 }
 
+impl AsSlice for BlockHeader<'_> {
+    fn as_slice(&self) -> &[u8] {
+        self.as_ref()
+    }
+}
+
+impl<'a> FromSlice<'a> for BlockHeader<'a> {
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        crate::codec::exact(BlockHeader::parse(slice))
+    }
+}
+
+#[cfg(feature = "redb")]
+impl<'o> redb::RedbValue for BlockHeader<'o> {
+    // TODO fix where position once MSRV allows
+    type SelfType<'a>
+    where
+        Self: 'a,
+    = BlockHeader<'a>;
+
+    type AsBytes<'a>
+    where
+        Self: 'a,
+    = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        Some(80)
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+        BlockHeader::from_trusted_slice(data)
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value.as_ref()
+    }
+
+    fn type_name() -> redb::TypeName {
+        redb::TypeName::new("bsl::BlockHeader")
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{bsl::BlockHeader, test_common::GENESIS_BLOCK_HEADER, Parse};
@@ -131,7 +180,7 @@ mod test {
         );
 
         check_hash(
-            &block_header.parsed(),
+            block_header.parsed(),
             hex!("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"),
         );
     }
@@ -0,0 +1,186 @@
+/// Defines a 32 bytes hash type, usable as fixed width key and value in redb
+macro_rules! hash_type {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+        pub struct $name(pub [u8; 32]);
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl From<[u8; 32]> for $name {
+            fn from(bytes: [u8; 32]) -> Self {
+                $name(bytes)
+            }
+        }
+
+        impl crate::codec::AsSlice for $name {
+            fn as_slice(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl crate::codec::FromSlice<'_> for $name {
+            fn from_slice(slice: &[u8]) -> Result<Self, crate::Error> {
+                match slice.len() {
+                    32 => Ok($name(slice.try_into().expect("32 bytes"))),
+                    len if len < 32 => Err(crate::Error::Needed((32 - len) as u32)),
+                    len => Err(crate::Error::TrailingBytes((len - 32) as u32)),
+                }
+            }
+        }
+
+        #[cfg(feature = "redb")]
+        impl redb::RedbValue for $name {
+            // TODO fix where position once MSRV allows
+            type SelfType<'a>
+            where
+                Self: 'a,
+            = $name;
+
+            type AsBytes<'a>
+            where
+                Self: 'a,
+            = &'a [u8; 32];
+
+            fn fixed_width() -> Option<usize> {
+                Some(32)
+            }
+
+            fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+            where
+                Self: 'a,
+            {
+                crate::codec::FromSlice::from_trusted_slice(data)
+            }
+
+            fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+            where
+                Self: 'a,
+                Self: 'b,
+            {
+                &value.0
+            }
+
+            fn type_name() -> redb::TypeName {
+                redb::TypeName::new(concat!("bsl::", stringify!($name)))
+            }
+        }
+
+        #[cfg(feature = "redb")]
+        impl redb::RedbKey for $name {
+            fn compare(data1: &[u8], data2: &[u8]) -> core::cmp::Ordering {
+                data1.cmp(data2)
+            }
+        }
+    };
+}
+
+hash_type!(
+    /// A transaction identifier, as returned by [`crate::bsl::Transaction::txid`], in internal
+    /// byte order
+    Txid
+);
+
+hash_type!(
+    /// A block hash, as returned by [`crate::bsl::BlockHeader::block_hash`], in internal byte
+    /// order
+    BlockHash
+);
+
+hash_type!(
+    /// The single sha256 of a script pubkey, the script identifier used by electrum servers
+    ScriptHash
+);
+
+#[cfg(feature = "bitcoin_hashes")]
+impl From<crate::bitcoin_hashes::sha256d::Hash> for Txid {
+    fn from(hash: crate::bitcoin_hashes::sha256d::Hash) -> Self {
+        use crate::bitcoin_hashes::Hash;
+        Txid(hash.to_byte_array())
+    }
+}
+
+#[cfg(feature = "bitcoin_hashes")]
+impl From<crate::bitcoin_hashes::sha256d::Hash> for BlockHash {
+    fn from(hash: crate::bitcoin_hashes::sha256d::Hash) -> Self {
+        use crate::bitcoin_hashes::Hash;
+        BlockHash(hash.to_byte_array())
+    }
+}
+
+#[cfg(feature = "bitcoin_hashes")]
+impl ScriptHash {
+    /// Returns the hash of the given script pubkey
+    pub fn from_script(script_pubkey: &[u8]) -> Self {
+        use crate::bitcoin_hashes::{sha256, Hash};
+        ScriptHash(sha256::Hash::hash(script_pubkey).to_byte_array())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(all(feature = "redb", feature = "bitcoin_hashes"))]
+    #[test]
+    fn redb_keys() {
+        use redb::ReadableTable;
+
+        use super::{BlockHash, ScriptHash, Txid};
+        use crate::{
+            bsl::{Block, BlockHeader, Script, Transaction, TxIn, TxIns},
+            test_common::GENESIS_BLOCK,
+            Parse,
+        };
+
+        const HEADERS: redb::TableDefinition<BlockHash, BlockHeader> =
+            redb::TableDefinition::new("headers");
+        const SCRIPTS: redb::TableDefinition<ScriptHash, Script> =
+            redb::TableDefinition::new("scripts");
+        const INPUTS: redb::TableDefinition<Txid, TxIns> = redb::TableDefinition::new("inputs");
+        const FIRST_INPUT: redb::TableDefinition<Txid, TxIn> =
+            redb::TableDefinition::new("first_input");
+
+        let block = Block::parse(&GENESIS_BLOCK[..]).unwrap().parsed_owned();
+        let header = block.header().clone();
+        let block_hash = BlockHash::from(block.block_hash());
+        let tx = Transaction::parse(&GENESIS_BLOCK[81..])
+            .unwrap()
+            .parsed_owned();
+        let txid = Txid::from(tx.txid());
+        let script =
+            Script::parse(&GENESIS_BLOCK[GENESIS_BLOCK.len() - 72..GENESIS_BLOCK.len() - 4])
+                .unwrap()
+                .parsed_owned();
+        let script_hash = ScriptHash::from_script(script.script());
+        let tx_ins = TxIns::parse(&GENESIS_BLOCK[85..]).unwrap().parsed_owned();
+        let tx_in = TxIn::parse(&GENESIS_BLOCK[86..]).unwrap().parsed_owned();
+
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let db = redb::Database::create(path).unwrap();
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(HEADERS).unwrap();
+            table.insert(&block_hash, &header).unwrap();
+            let mut table = write_txn.open_table(SCRIPTS).unwrap();
+            table.insert(&script_hash, &script).unwrap();
+            let mut table = write_txn.open_table(INPUTS).unwrap();
+            table.insert(&txid, &tx_ins).unwrap();
+            let mut table = write_txn.open_table(FIRST_INPUT).unwrap();
+            table.insert(&txid, &tx_in).unwrap();
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(HEADERS).unwrap();
+        assert_eq!(table.get(&block_hash).unwrap().unwrap().value(), header);
+        let table = read_txn.open_table(SCRIPTS).unwrap();
+        assert_eq!(table.get(&script_hash).unwrap().unwrap().value(), script);
+        let table = read_txn.open_table(INPUTS).unwrap();
+        assert_eq!(table.get(&txid).unwrap().unwrap().value(), tx_ins);
+        let table = read_txn.open_table(FIRST_INPUT).unwrap();
+        assert_eq!(table.get(&txid).unwrap().unwrap().value(), tx_in);
+    }
+}
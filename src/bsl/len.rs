@@ -28,6 +28,32 @@ pub fn parse_len(slice: &[u8]) -> Result<Len, Error> {
     })
 }
 
+/// Returns the number of bytes needed to encode `n` as a compact int
+pub fn len_size(n: u64) -> usize {
+    match n {
+        0..=0xFC => 1,
+        0xFD..=0xFFFF => 3,
+        0x10000..=0xFFFF_FFFF => 5,
+        _ => 9,
+    }
+}
+
+/// Encodes `n` as a compact int, returning the bytes buffer and how many of them are used
+pub fn encode_len(n: u64) -> ([u8; 9], usize) {
+    let mut bytes = [0u8; 9];
+    let len = len_size(n);
+    match len {
+        1 => bytes[0] = n as u8,
+        3 => bytes[0] = 0xFD,
+        5 => bytes[0] = 0xFE,
+        _ => bytes[0] = 0xFF,
+    }
+    if len > 1 {
+        bytes[1..len].copy_from_slice(&n.to_le_bytes()[..len - 1]);
+    }
+    (bytes, len)
+}
+
 impl Len {
     /// The value encoded in this compact int
     pub fn n(&self) -> u64 {
@@ -54,6 +80,9 @@ mod test {
 
     fn check(slice: &[u8], consumed: usize, n: u64) {
         assert_eq!(parse_len(slice), Ok(Len { consumed, n }));
+        assert_eq!(super::len_size(n), consumed);
+        let (bytes, len) = super::encode_len(n);
+        assert_eq!(&bytes[..len], &slice[..consumed]);
     }
 
     #[test]
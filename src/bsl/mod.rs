@@ -12,8 +12,11 @@
 
 mod block;
 mod block_header;
+mod hash_types;
 mod len;
 mod out_point;
+#[cfg(feature = "alloc")]
+mod owned;
 mod script;
 mod transaction;
 mod tx_in;
@@ -25,10 +28,16 @@ mod witnesses;
 
 pub use block::Block;
 pub use block_header::BlockHeader;
+pub use hash_types::{BlockHash, ScriptHash, Txid};
+pub use len::encode_len;
+pub use len::len_size;
 pub use len::parse_len;
 pub use len::Len;
 pub use out_point::OutPoint;
+#[cfg(feature = "alloc")]
+pub use owned::{BlockBuf, TransactionBuf};
 pub use script::Script;
+pub use transaction::PoolTags;
 pub use transaction::Transaction;
 pub use tx_in::TxIn;
 pub use tx_ins::TxIns;
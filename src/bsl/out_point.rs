@@ -1,4 +1,5 @@
 use crate::{slice::read_slice, Parse, ParseResult, SResult};
+use crate::{AsSlice, Error, FromSlice};
 
 /// The out point of a transaction input, identifying the previous output being spent
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -14,7 +15,7 @@ impl<'a> AsRef<[u8]> for OutPoint<'a> {
 
 impl<'a> Parse<'a> for OutPoint<'a> {
     /// Parse the out point from the given slice
-    fn parse(slice: &'a [u8]) -> SResult<Self> {
+    fn parse(slice: &'a [u8]) -> SResult<'a, Self> {
         let outpoint = read_slice(slice, 36usize)?;
         Ok(ParseResult::new(
             outpoint.remaining(),
@@ -37,6 +38,27 @@ impl<'a> OutPoint<'a> {
             .expect("slice length ensured by parsing");
         u32::from_le_bytes(arr)
     }
+    /// Returns true if this is the null outpoint (all zero txid and `u32::MAX` vout), which is
+    /// spent only by coinbase inputs
+    pub fn is_null(&self) -> bool {
+        self.slice[..32] == [0u8; 32] && self.vout() == u32::MAX
+    }
+}
+
+impl AsSlice for OutPoint<'_> {
+    fn as_slice(&self) -> &[u8] {
+        self.as_ref()
+    }
+}
+
+impl<'a> FromSlice<'a> for OutPoint<'a> {
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        crate::codec::exact(OutPoint::parse(slice))
+    }
+
+    fn from_trusted_slice(slice: &'a [u8]) -> Self {
+        OutPoint { slice }
+    }
 }
 
 #[cfg(feature = "redb")]
@@ -60,7 +82,7 @@ impl<'o> redb::RedbValue for OutPoint<'o> {
     where
         Self: 'a,
     {
-        OutPoint { slice: data }
+        OutPoint::from_trusted_slice(data)
     }
 
     fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
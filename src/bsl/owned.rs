@@ -0,0 +1,151 @@
+use crate::{
+    bsl::{transaction::InputsOutputsLen, Block, Transaction},
+    Parse, ParseResult, SResult,
+};
+use alloc::sync::Arc;
+
+/// An owned [`Transaction`], backed by a reference-counted buffer.
+///
+/// Cloning is cheap and the borrowed view returned by [`TransactionBuf::as_transaction`] is
+/// built without parsing again, since the fields cached by the parsing are kept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TransactionBuf {
+    bytes: Arc<[u8]>,
+    inputs_outputs_len: InputsOutputsLen,
+}
+
+impl TransactionBuf {
+    /// Parses a transaction from the start of `slice`, copying its bytes in a new buffer
+    pub fn parse(slice: &[u8]) -> SResult<'_, Self> {
+        let tx = Transaction::parse(slice)?;
+        let remaining = tx.remaining();
+        Ok(ParseResult::new(remaining, tx.parsed().into()))
+    }
+
+    /// Returns the borrowed view of this transaction
+    pub fn as_transaction(&self) -> Transaction<'_> {
+        Transaction::from_parts(&self.bytes, self.inputs_outputs_len)
+    }
+
+    /// Returns the buffer containing the serialized transaction
+    pub fn bytes(&self) -> &Arc<[u8]> {
+        &self.bytes
+    }
+}
+
+impl From<&Transaction<'_>> for TransactionBuf {
+    fn from(tx: &Transaction<'_>) -> Self {
+        TransactionBuf {
+            bytes: tx.as_ref().into(),
+            inputs_outputs_len: tx.inputs_outputs_len(),
+        }
+    }
+}
+
+impl From<Transaction<'_>> for TransactionBuf {
+    fn from(tx: Transaction<'_>) -> Self {
+        (&tx).into()
+    }
+}
+
+impl AsRef<[u8]> for TransactionBuf {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// An owned [`Block`], backed by a reference-counted buffer.
+///
+/// Cloning is cheap and the borrowed view returned by [`BlockBuf::as_block`] is built without
+/// parsing the transactions again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlockBuf {
+    bytes: Arc<[u8]>,
+    total_txs: usize,
+}
+
+impl BlockBuf {
+    /// Parses a block from the start of `slice`, copying its bytes in a new buffer
+    pub fn parse(slice: &[u8]) -> SResult<'_, Self> {
+        let block = Block::parse(slice)?;
+        let remaining = block.remaining();
+        Ok(ParseResult::new(remaining, block.parsed().into()))
+    }
+
+    /// Returns the borrowed view of this block
+    pub fn as_block(&self) -> Block<'_> {
+        Block::from_parts(&self.bytes, self.total_txs)
+    }
+
+    /// Returns the buffer containing the serialized block
+    pub fn bytes(&self) -> &Arc<[u8]> {
+        &self.bytes
+    }
+}
+
+impl From<&Block<'_>> for BlockBuf {
+    fn from(block: &Block<'_>) -> Self {
+        BlockBuf {
+            bytes: block.as_ref().into(),
+            total_txs: block.total_transactions(),
+        }
+    }
+}
+
+impl From<Block<'_>> for BlockBuf {
+    fn from(block: Block<'_>) -> Self {
+        (&block).into()
+    }
+}
+
+impl AsRef<[u8]> for BlockBuf {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::sync::Arc;
+
+    use crate::{
+        bsl::{Block, BlockBuf, Transaction, TransactionBuf},
+        test_common::GENESIS_BLOCK,
+        Parse,
+    };
+
+    #[test]
+    fn transaction_buf() {
+        let block = bitcoin_test_data::blocks::mainnet_702861();
+        let block = Block::parse(block).unwrap().parsed_owned();
+        let mut slice = &block.as_ref()[83..];
+        let mut txs = vec![];
+        while let Ok(tx) = TransactionBuf::parse(slice) {
+            slice = tx.remaining();
+            txs.push(tx.parsed_owned());
+        }
+        assert_eq!(txs.len(), block.total_transactions());
+
+        let coinbase = Transaction::parse(&block.as_ref()[83..]).unwrap();
+        assert_eq!(txs[0].as_transaction(), *coinbase.parsed());
+        assert!(txs[0].as_transaction().has_witness());
+        assert_eq!(TransactionBuf::from(coinbase.parsed()), txs[0]);
+
+        let clone = txs[0].clone();
+        assert!(Arc::ptr_eq(clone.bytes(), txs[0].bytes()));
+
+        let handle = std::thread::spawn(move || clone.as_transaction().version());
+        assert_eq!(handle.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn block_buf() {
+        let block = BlockBuf::parse(&GENESIS_BLOCK[..]).unwrap();
+        assert!(block.remaining().is_empty());
+        let block = block.parsed_owned();
+        let parsed = Block::parse(&GENESIS_BLOCK[..]).unwrap().parsed_owned();
+        assert_eq!(block.as_block(), parsed);
+        assert_eq!(block.as_ref(), &GENESIS_BLOCK[..]);
+        assert_eq!(BlockBuf::from(parsed), block);
+    }
+}
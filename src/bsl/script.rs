@@ -1,6 +1,7 @@
 use crate::{slice::read_slice, Parse, ParseResult, SResult};
 
 use super::len::{parse_len, Len};
+use crate::{AsSlice, Error, FromSlice};
 
 /// The Script, this type could be found in transaction outputs as `script_pubkey` or in transaction
 /// inputs as `script_sig`.
@@ -15,7 +16,7 @@ pub struct Script<'a> {
 
 impl<'a> Parse<'a> for Script<'a> {
     /// Parse a script from the slice.
-    fn parse(slice: &'a [u8]) -> SResult<Self> {
+    fn parse(slice: &'a [u8]) -> SResult<'a, Self> {
         let Len { consumed, n } = parse_len(slice)?;
         let n = n as usize;
         let remaining = &slice[consumed..];
@@ -32,7 +33,7 @@ impl<'a> Parse<'a> for Script<'a> {
 }
 impl<'a> Script<'a> {
     /// return the script bytes (exclude the compact int representing the length)
-    pub fn script(&self) -> &[u8] {
+    pub fn script(&self) -> &'a [u8] {
         &self.slice[self.from..]
     }
 }
@@ -43,6 +44,63 @@ impl<'a> AsRef<[u8]> for Script<'a> {
     }
 }
 
+impl AsSlice for Script<'_> {
+    fn as_slice(&self) -> &[u8] {
+        self.as_ref()
+    }
+}
+
+impl<'a> FromSlice<'a> for Script<'a> {
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        crate::codec::exact(Script::parse(slice))
+    }
+
+    fn from_trusted_slice(slice: &'a [u8]) -> Self {
+        let Len { consumed, .. } = parse_len(slice).expect("inserted data is not a Script");
+        Script {
+            slice,
+            from: consumed,
+        }
+    }
+}
+
+#[cfg(feature = "redb")]
+impl<'o> redb::RedbValue for Script<'o> {
+    // TODO fix where position once MSRV allows
+    type SelfType<'a>
+    where
+        Self: 'a,
+    = Script<'a>;
+
+    type AsBytes<'a>
+    where
+        Self: 'a,
+    = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+        Script::from_trusted_slice(data)
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value.as_ref()
+    }
+
+    fn type_name() -> redb::TypeName {
+        redb::TypeName::new("bsl::Script")
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{bsl::Script, Error, Parse};
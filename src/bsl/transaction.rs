@@ -1,35 +1,113 @@
-use core::{num::NonZeroU32, ops::ControlFlow};
+use core::{
+    num::NonZeroU32,
+    ops::ControlFlow,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 use crate::{
-    bsl::{TxIns, TxOuts, Witnesses},
+    bsl::{parse_len, Len, TxIn, TxIns, TxOut, TxOuts, Witnesses},
+    consensus::TxSanityError,
+    fee::{FeeError, PrevoutProvider},
     number::{I32, U32, U8},
-    Error, Parse, ParseResult, SResult, Visit, Visitor,
+    policy::PolicyError,
+    visit::check,
+    BResult, Descend, Error, LocatedError, Parse, ParseResult, Stop, Subtree, Visit, Visitor,
 };
+use crate::{AsSlice, FromSlice};
 
 /// A Bitcoin transaction
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug)]
 pub struct Transaction<'a> {
     slice: &'a [u8],
 
-    /// The length of the slice inlcuding all inputs and outputs of the transaction.
-    /// If some the tx is segwit
-    inputs_outputs_len: Option<NonZeroU32>,
+    /// The length of the slice inlcuding all inputs and outputs of the transaction, encoded with
+    /// [`InputsOutputsLen::to_u32`].
+    ///
+    /// Atomic so that it can be cached when computed on demand, keeping the transaction `Sync`.
+    inputs_outputs_len: AtomicU32,
+}
+
+impl<'a> Clone for Transaction<'a> {
+    fn clone(&self) -> Self {
+        Transaction {
+            slice: self.slice,
+            inputs_outputs_len: AtomicU32::new(self.inputs_outputs_len.load(Ordering::Relaxed)),
+        }
+    }
 }
 
+/// The length of the inputs and outputs of a transaction, cached when parsing because it's needed
+/// to compute the txid of segwit transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum InputsOutputsLen {
+    /// The transaction is segwit, inputs and outputs with their compact int counts are this long
+    Segwit(NonZeroU32),
+
+    /// The transaction is legacy, inputs and outputs are the slice without version and locktime
+    Legacy,
+
+    /// Not known since the transaction wasn't parsed, computed on demand
+    Unknown,
+}
+
+impl InputsOutputsLen {
+    fn to_u32(self) -> u32 {
+        match self {
+            InputsOutputsLen::Segwit(len) => len.get(),
+            InputsOutputsLen::Legacy => u32::MAX,
+            InputsOutputsLen::Unknown => 0,
+        }
+    }
+
+    fn from_u32(value: u32) -> Self {
+        match value {
+            u32::MAX => InputsOutputsLen::Legacy,
+            value => {
+                NonZeroU32::new(value).map_or(InputsOutputsLen::Unknown, InputsOutputsLen::Segwit)
+            }
+        }
+    }
+
+    fn into_atomic(self) -> AtomicU32 {
+        AtomicU32::new(self.to_u32())
+    }
+}
+
+/// The cached field is derived from the slice, so it's not compared
+impl<'a> PartialEq for Transaction<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.slice == other.slice
+    }
+}
+
+impl<'a> Eq for Transaction<'a> {}
+
 impl<'a> Visit<'a> for Transaction<'a> {
-    fn visit<'b, V: Visitor>(slice: &'a [u8], visit: &'b mut V) -> SResult<'a, Self> {
+    fn visit_break<'b, B, V: Visitor<B>>(
+        slice: &'a [u8],
+        visit: &'b mut V,
+    ) -> BResult<'a, Self, B> {
         let version = I32::parse(slice)?;
-        let inputs = TxIns::visit(version.remaining(), visit)?;
+        let descend_inputs = descend(visit, Subtree::TxIns, version.remaining())?;
+        let inputs = visit_subtree::<TxIns, B, V>(version.remaining(), descend_inputs, visit)?;
         if inputs.parsed().is_empty() {
             let segwit_flag = U8::parse(inputs.remaining())?;
             let segwit_flag_u8 = segwit_flag.parsed().into();
             if segwit_flag_u8 == 1 {
-                let inputs = TxIns::visit(segwit_flag.remaining(), visit)?;
-                let outputs = TxOuts::visit(inputs.remaining(), visit)?;
-                let witnesses = Witnesses::visit(outputs.remaining(), inputs.parsed().n(), visit)?;
+                let inputs =
+                    visit_subtree::<TxIns, B, V>(segwit_flag.remaining(), descend_inputs, visit)?;
+                let descend_outputs = descend(visit, Subtree::TxOuts, inputs.remaining())?;
+                let outputs =
+                    visit_subtree::<TxOuts, B, V>(inputs.remaining(), descend_outputs, visit)?;
+                let witnesses = match descend(visit, Subtree::Witnesses, outputs.remaining())? {
+                    Descend::Visit => {
+                        Witnesses::visit_break(outputs.remaining(), inputs.parsed().n(), visit)?
+                    }
+                    Descend::Skip => Witnesses::parse(outputs.remaining(), inputs.parsed().n())?,
+                };
 
                 if !inputs.parsed().is_empty() && witnesses.parsed().all_empty() {
-                    return Err(Error::SegwitFlagWithoutWitnesses);
+                    return Err(Error::SegwitFlagWithoutWitnesses.into());
                 }
 
                 let _locktime = U32::parse(witnesses.remaining())?;
@@ -39,32 +117,191 @@ impl<'a> Visit<'a> for Transaction<'a> {
 
                 let tx = Transaction {
                     slice: &slice[..consumed],
-                    inputs_outputs_len: NonZeroU32::new(inputs_outputs_len as u32), // inputs_outputs_len is at least 2 bytes if both empty, they contain the compact int len
+                    inputs_outputs_len: InputsOutputsLen::Segwit(
+                        NonZeroU32::new(inputs_outputs_len as u32)
+                            .expect("at least 2 bytes, the compact int counts"),
+                    )
+                    .into_atomic(),
                 };
-                match visit.visit_transaction(&tx) {
-                    ControlFlow::Continue(_) => Ok(ParseResult::new(&slice[consumed..], tx)),
-                    ControlFlow::Break(_) => Err(Error::VisitBreak),
-                }
+                check(visit.visit_transaction(&tx), &slice[consumed..], tx)
             } else {
-                Err(Error::UnknownSegwitFlag(segwit_flag_u8))
+                Err(Error::UnknownSegwitFlag(segwit_flag_u8).into())
             }
         } else {
-            let outputs = TxOuts::visit(inputs.remaining(), visit)?;
+            let descend_outputs = descend(visit, Subtree::TxOuts, inputs.remaining())?;
+            let outputs =
+                visit_subtree::<TxOuts, B, V>(inputs.remaining(), descend_outputs, visit)?;
             let _locktime = U32::parse(outputs.remaining())?;
             let consumed = inputs.consumed() + outputs.consumed() + 8;
 
             let tx = Transaction {
                 slice: &slice[..consumed],
-                inputs_outputs_len: None,
+                inputs_outputs_len: InputsOutputsLen::Legacy.into_atomic(),
             };
-            match visit.visit_transaction(&tx) {
-                ControlFlow::Continue(_) => Ok(ParseResult::new(&slice[consumed..], tx)),
-                ControlFlow::Break(_) => Err(Error::VisitBreak),
-            }
+            check(visit.visit_transaction(&tx), &slice[consumed..], tx)
         }
     }
 }
+
+/// Ask the visitor whether it wants to descend in the given `subtree`, starting at `remaining`
+fn descend<'a, B, V: Visitor<B>>(
+    visit: &mut V,
+    subtree: Subtree,
+    remaining: &'a [u8],
+) -> Result<Descend, Stop<'a, B>> {
+    match visit.visit_subtree(subtree) {
+        ControlFlow::Continue(descend) => Ok(descend),
+        ControlFlow::Break(value) => Err(Stop::Break { value, remaining }),
+    }
+}
+
+/// Visit `T` from the slice, or parse it without calling the visitor if it has to be skipped
+fn visit_subtree<'a, T: Visit<'a>, B, V: Visitor<B>>(
+    slice: &'a [u8],
+    descend: Descend,
+    visit: &mut V,
+) -> BResult<'a, T, B> {
+    match descend {
+        Descend::Visit => T::visit_break(slice, visit),
+        Descend::Skip => Ok(T::parse(slice)?),
+    }
+}
+
+/// Reads a compact int from an already validated slice, advancing it
+fn trusted_len(remaining: &mut &[u8]) -> usize {
+    let len = parse_len(remaining).expect("granted from parsing");
+    *remaining = &remaining[len.consumed()..];
+    len.n() as usize
+}
+
+/// Parses `T` from an already validated slice, advancing it
+fn trusted<'a, T: Parse<'a>>(remaining: &mut &'a [u8]) -> T {
+    let parsed = T::parse(remaining).expect("granted from parsing");
+    *remaining = parsed.remaining();
+    parsed.parsed_owned()
+}
+
 impl<'a> Transaction<'a> {
+    /// Like [`Parse::parse`] but on failure returns a [`LocatedError`] with the offset and path of
+    /// the element that failed, like `input 5 / script_sig`.
+    ///
+    /// On success this has the same cost of parsing, the location is computed only on failure.
+    pub fn parse_located(
+        slice: &'a [u8],
+    ) -> Result<ParseResult<'a, Transaction<'a>>, LocatedError> {
+        Self::parse(slice).map_err(|e| crate::located::locate_transaction(slice, e))
+    }
+
+    /// Creates a transaction from a slice containing exactly one transaction, without parsing it.
+    ///
+    /// This is O(1) and meant for data coming from a previous successful parsing, like the bytes of a
+    /// transaction stored in a database. The fields usually cached while parsing are computed and
+    /// cached on first use, for example the first txid of a segwit transaction requires to walk
+    /// inputs and outputs, which is O(n), following calls are O(1).
+    ///
+    /// Passing a slice which is not a valid transaction is not undefined behavior, but methods may
+    /// panic or return wrong results.
+    pub fn from_trusted_slice(slice: &'a [u8]) -> Self {
+        Transaction {
+            slice,
+            inputs_outputs_len: InputsOutputsLen::Unknown.into_atomic(),
+        }
+    }
+
+    /// Creates the transaction from a slice validated by a previous parsing and its cached fields
+    #[cfg(feature = "alloc")]
+    pub(crate) fn from_parts(slice: &'a [u8], inputs_outputs_len: InputsOutputsLen) -> Self {
+        Transaction {
+            slice,
+            inputs_outputs_len: inputs_outputs_len.into_atomic(),
+        }
+    }
+
+    /// Returns the cached length of inputs and outputs
+    fn cached_inputs_outputs_len(&self) -> InputsOutputsLen {
+        InputsOutputsLen::from_u32(self.inputs_outputs_len.load(Ordering::Relaxed))
+    }
+
+    /// Returns the length of inputs and outputs, computing and caching it if unknown
+    pub(crate) fn inputs_outputs_len(&self) -> InputsOutputsLen {
+        let len = match self.cached_inputs_outputs_len() {
+            InputsOutputsLen::Unknown if self.has_witness() => {
+                let tx_ins =
+                    TxIns::parse(&self.slice[6..]).expect("slice validated during parsing");
+                let tx_outs =
+                    TxOuts::parse(tx_ins.remaining()).expect("slice validated during parsing");
+                let len = tx_ins.consumed() + tx_outs.consumed();
+                InputsOutputsLen::Segwit(
+                    NonZeroU32::new(len as u32).expect("at least 2 bytes, the compact int counts"),
+                )
+            }
+            InputsOutputsLen::Unknown => InputsOutputsLen::Legacy,
+            known => return known,
+        };
+        // concurrent callers compute the same value
+        self.inputs_outputs_len
+            .store(len.to_u32(), Ordering::Relaxed);
+        len
+    }
+
+    /// Returns the length of inputs and outputs of segwit transactions
+    fn segwit_len(&self) -> Option<NonZeroU32> {
+        match self.inputs_outputs_len() {
+            InputsOutputsLen::Segwit(len) => Some(len),
+            _ => None,
+        }
+    }
+
+    /// Calls the input, output and witness callbacks of `visit` on this already parsed
+    /// transaction, honoring [`Visitor::visit_subtree`]: a skipped subtree gets no callbacks.
+    ///
+    /// Unlike parsing, nothing is validated again, [`Visitor::visit_transaction`] is not called
+    /// and, for segwit transactions, the `visit_tx_ins(0)` that parsing emits for the segwit
+    /// marker is not emitted.
+    pub(crate) fn visit_subtrees<B, V: Visitor<B>>(&self, visit: &mut V) -> ControlFlow<B> {
+        let segwit = self.has_witness();
+        let mut remaining = &self.slice[if segwit { 6 } else { 4 }..];
+
+        let descend_inputs = visit.visit_subtree(Subtree::TxIns)?;
+        let total_inputs = trusted_len(&mut remaining);
+        if descend_inputs == Descend::Visit {
+            visit.visit_tx_ins(total_inputs);
+        }
+        for vin in 0..total_inputs {
+            let tx_in: TxIn = trusted(&mut remaining);
+            if descend_inputs == Descend::Visit {
+                visit.visit_tx_in(vin, &tx_in)?;
+            }
+        }
+
+        let descend_outputs = visit.visit_subtree(Subtree::TxOuts)?;
+        let total_outputs = trusted_len(&mut remaining);
+        if descend_outputs == Descend::Visit {
+            visit.visit_tx_outs(total_outputs);
+        }
+        for vout in 0..total_outputs {
+            let tx_out: TxOut = trusted(&mut remaining);
+            if descend_outputs == Descend::Visit {
+                visit.visit_tx_out(vout, &tx_out)?;
+            }
+        }
+
+        if segwit && visit.visit_subtree(Subtree::Witnesses)? == Descend::Visit {
+            for vin in 0..total_inputs {
+                visit.visit_witness(vin)?;
+                let witness_total = trusted_len(&mut remaining);
+                visit.visit_witness_total_element(witness_total);
+                for i in 0..witness_total {
+                    let len = trusted_len(&mut remaining);
+                    visit.visit_witness_element(i, &remaining[..len]);
+                    remaining = &remaining[len..];
+                }
+                visit.visit_witness_end();
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
     /// Returns the transaction version.
     pub fn version(&self) -> i32 {
         I32::parse(&self.slice[..4])
@@ -73,6 +310,108 @@ impl<'a> Transaction<'a> {
             .into()
     }
 
+    /// Returns true if this is a coinbase transaction, with a single input spending the null
+    /// outpoint
+    pub fn is_coinbase(&self) -> bool {
+        self.coinbase_tx_in().is_some()
+    }
+
+    /// Returns the block height in the coinbase script sig, as required by BIP 34, or `None` if
+    /// this is not a coinbase or the script sig doesn't start with a number.
+    ///
+    /// Coinbases of blocks before BIP 34 activation (height 227931 on mainnet) may return any
+    /// value.
+    pub fn bip34_height(&self) -> Option<u32> {
+        self.coinbase_height_push().map(|(height, _)| height)
+    }
+
+    /// Returns the bytes of the coinbase script sig following the BIP 34 height, which miners
+    /// fill with the extranonce and their tags, or `None` if this is not a coinbase or the script
+    /// sig doesn't start with a number.
+    pub fn coinbase_extranonce(&self) -> Option<&'a [u8]> {
+        self.coinbase_height_push().map(|(_, rest)| rest)
+    }
+
+    /// Returns an iterator over the printable ASCII strings, at least 4 characters long, in the
+    /// coinbase script sig after the BIP 34 height, which usually contain the mining pool tag.
+    ///
+    /// This is a best effort: the bytes are scanned without parsing the script, since pools don't
+    /// always push their tags correctly, so random extranonce bytes may look like a tag. The iterator is empty if
+    /// this is not a coinbase.
+    pub fn pool_tags(&self) -> PoolTags<'a> {
+        let script_sig = match self.coinbase_height_push() {
+            Some((_, rest)) => rest,
+            None => self
+                .coinbase_tx_in()
+                .map_or(&[][..], |tx_in| tx_in.script_sig()),
+        };
+        PoolTags(script_sig)
+    }
+
+    /// Returns the witness reserved value of a coinbase, the single 32 bytes element of its
+    /// witness committed with the witness merkle root, as defined by BIP 141
+    pub fn coinbase_witness_reserved_value(&self) -> Option<&'a [u8; 32]> {
+        if !self.is_coinbase() {
+            return None;
+        }
+        let witnesses = self.witnesses_slice()?;
+        match witnesses {
+            [1, 32, value @ ..] => value.try_into().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the input of the transaction if it's a coinbase
+    pub(crate) fn coinbase_tx_in(&self) -> Option<TxIn<'a>> {
+        let from = if self.has_witness() { 6 } else { 4 };
+        let slice: &'a [u8] = &self.slice[from..];
+        let Len { consumed, n } = parse_len(slice).expect("slice validated during parsing");
+        if n != 1 {
+            return None;
+        }
+        let tx_in = TxIn::parse(&slice[consumed..]).expect("slice validated during parsing");
+        let tx_in = tx_in.parsed_owned();
+        if tx_in.prevout().is_null() {
+            Some(tx_in)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the serialized witnesses, `None` if the transaction has no witness
+    fn witnesses_slice(&self) -> Option<&'a [u8]> {
+        if !self.has_witness() {
+            return None;
+        }
+        let tx_ins = TxIns::parse(&self.slice[6..]).expect("slice validated during parsing");
+        let tx_outs = TxOuts::parse(tx_ins.remaining()).expect("slice validated during parsing");
+        let remaining = tx_outs.remaining();
+        Some(&remaining[..remaining.len() - 4])
+    }
+
+    /// Returns the BIP 34 height pushed at the start of the coinbase script sig, with the
+    /// following bytes
+    fn coinbase_height_push(&self) -> Option<(u32, &'a [u8])> {
+        let script_sig = self.coinbase_tx_in()?.script_sig();
+        let (&opcode, rest) = script_sig.split_first()?;
+        match opcode {
+            0x00 => Some((0, rest)),
+            0x51..=0x60 => Some(((opcode - 0x50) as u32, rest)),
+            0x01..=0x05 => {
+                let len = opcode as usize;
+                let (number, rest) = (rest.get(..len)?, &rest[len..]);
+                if number[len - 1] & 0x80 != 0 {
+                    return None; // negative
+                }
+                let mut bytes = [0u8; 8];
+                bytes[..len].copy_from_slice(number);
+                let height = u32::try_from(u64::from_le_bytes(bytes)).ok()?;
+                Some((height, rest))
+            }
+            _ => None,
+        }
+    }
+
     /// Returns the transaction locktime.
     pub fn locktime(&self) -> u32 {
         let from = self.slice.len() - 4; // slice length granted during parsing
@@ -87,7 +426,7 @@ impl<'a> Transaction<'a> {
     /// It is a tuple of 3 because for segwit transactions they are 3 non-contiguos bytes slices and
     /// we don't want to depend on standard and accept a `Write` parameter nor allocate.
     pub fn txid_preimage(&self) -> (&'a [u8], &'a [u8], &'a [u8]) {
-        if let Some(len) = self.inputs_outputs_len.as_ref() {
+        if let Some(len) = self.segwit_len() {
             (
                 &self.slice[..4],                       // version
                 &self.slice[6..len.get() as usize + 6], // input & outputs (but first skips segwit markers, why bip143 didn't want to hash those?)
@@ -112,6 +451,24 @@ impl<'a> Transaction<'a> {
         sha256d::Hash::from_engine(engine)
     }
 
+    /// Return the witness transaction identifier as defined by BIP 141, which commits also to the
+    /// witnesses. It's equal to the [`Transaction::txid`] for legacy transactions.
+    #[cfg(feature = "bitcoin_hashes")]
+    pub fn wtxid(&self) -> crate::bitcoin_hashes::sha256d::Hash {
+        use crate::bitcoin_hashes::{sha256d, Hash};
+        sha256d::Hash::hash(self.slice)
+    }
+
+    /// Returns true if the transaction is serialized with witnesses, as defined by BIP 144
+    pub fn has_witness(&self) -> bool {
+        match self.cached_inputs_outputs_len() {
+            InputsOutputsLen::Segwit(_) => true,
+            InputsOutputsLen::Legacy => false,
+            // a valid transaction has no inputs only if it's the segwit marker
+            InputsOutputsLen::Unknown => self.slice[4] == 0,
+        }
+    }
+
     /// Calculate the txid using the sha2 crate.
     /// NOTE: the result type is not displayed backwards when converted to string.
     #[cfg(feature = "sha2")]
@@ -129,10 +486,90 @@ impl<'a> Transaction<'a> {
         Sha256::digest(&hash[..])
     }
 
+    /// Context-free checks of the transaction validity, mirroring Bitcoin Core `CheckTransaction`.
+    ///
+    /// Rejects transactions without inputs or outputs, heavier than the block weight without
+    /// witnesses, with output values over [`MAX_MONEY`](crate::consensus::MAX_MONEY), with
+    /// duplicate inputs, with a coinbase script sig length outside 2..=100, or spending a null
+    /// outpoint if not coinbase.
+    ///
+    /// The outpoints and their index are copied and sorted in `scratch` to find duplicate inputs
+    /// without allocating. If `scratch` is shorter than the number of inputs, every input is
+    /// searched among chunks of `scratch.len()` sorted outpoints, so the cost grows quadratically
+    /// with the inputs not fitting in `scratch`.
+    pub fn check_sanity(&self, scratch: &mut [([u8; 36], u32)]) -> Result<(), TxSanityError> {
+        crate::consensus::check_sanity(self, scratch)
+    }
+
+    /// Returns the fee paid by the transaction, the difference between the value of the spent
+    /// outputs, asked to the `provider`, and the value of the outputs.
+    pub fn fee<P: PrevoutProvider>(&self, provider: &mut P) -> Result<u64, FeeError<P::Error>> {
+        crate::fee::fee(self, provider)
+    }
+
+    /// Returns the fee rate in satoshi per virtual byte, see [`Transaction::fee`] and
+    /// [`Transaction::vsize`]
+    pub fn feerate<P: PrevoutProvider>(&self, provider: &mut P) -> Result<f64, FeeError<P::Error>> {
+        Ok(self.fee(provider)? as f64 / self.vsize() as f64)
+    }
+
+    /// Checks the transaction is standard according to the Bitcoin Core default policy, see
+    /// [`crate::policy`].
+    ///
+    /// Checks the version, the weight and the TRUC size limit, the script sigs size and that
+    /// they are push only, the outputs script types, `OP_RETURN` outputs and dust outputs.
+    /// Inputs and witnesses standardness depends on the spent outputs, see
+    /// [`Transaction::check_standard_inputs`].
+    ///
+    /// Only the stateless part of the TRUC rules runs here, the rules depending on the
+    /// unconfirmed parents are checked by [`Transaction::check_truc`].
+    pub fn check_standard(&self) -> Result<(), PolicyError> {
+        crate::policy::check_standard(self)
+    }
+
+    /// Checks the TRUC (version 3) rules, BIP 431, given the unconfirmed transactions spent by
+    /// this one, in the order Bitcoin Core checks them.
+    ///
+    /// TRUC transactions can't spend unconfirmed non-TRUC transactions and vice versa. A TRUC
+    /// transaction is at most [`TRUC_MAX_VSIZE`](crate::policy::TRUC_MAX_VSIZE) virtual bytes,
+    /// has at most one unconfirmed ancestor, and if it has one, it's at most
+    /// [`TRUC_CHILD_MAX_VSIZE`](crate::policy::TRUC_CHILD_MAX_VSIZE) virtual bytes and the only
+    /// child of its parent.
+    pub fn check_truc(
+        &self,
+        unconfirmed_parents: &[crate::policy::UnconfirmedParent],
+    ) -> Result<(), PolicyError> {
+        crate::policy::check_truc(self, unconfirmed_parents)
+    }
+
+    /// Checks the spent outputs and witnesses are standard according to the Bitcoin Core
+    /// default policy, given the script pubkeys of the outputs spent by every input.
+    ///
+    /// Rejects spending non standard or unknown witness outputs, P2SH redeem scripts with too many
+    /// signature operations, and witnesses not standard for the spent output type. Coinbase
+    /// inputs are not checked.
+    pub fn check_standard_inputs(&self, prevout_scripts: &[&[u8]]) -> Result<(), PolicyError> {
+        crate::policy::check_standard_inputs(self, prevout_scripts)
+    }
+
+    /// Checks the ephemeral dust rule: a transaction with a dust output must pay zero `fee`, so
+    /// that the dust is spent by a child paying for both.
+    pub fn check_ephemeral(&self, fee: u64) -> Result<(), PolicyError> {
+        crate::policy::check_ephemeral(self, fee)
+    }
+
+    /// The size of the transaction serialized without witnesses
+    pub(crate) fn base_size(&self) -> usize {
+        match self.segwit_len() {
+            Some(n) => n.get() as usize + 4 + 4,
+            None => self.slice.len(),
+        }
+    }
+
     /// Transaction weight as defined by BIP 141
     pub fn weight(&self) -> u64 {
         let total_size = self.as_ref().len() as u64;
-        match self.inputs_outputs_len {
+        match self.segwit_len() {
             Some(n) => {
                 let base_size = n.get() as u64 + 4 + 4; // lenght of inputs, outputs + version + locktime
                 base_size * 3 + total_size
@@ -140,6 +577,43 @@ impl<'a> Transaction<'a> {
             None => total_size * 4,
         }
     }
+
+    /// Transaction virtual size as defined by BIP 141, the weight divided by 4 rounded up
+    // TODO use div_ceil once MSRV allows
+    #[allow(clippy::manual_div_ceil)]
+    pub fn vsize(&self) -> u64 {
+        (self.weight() + 3) / 4
+    }
+}
+
+/// Iterator over the printable ASCII strings in a coinbase script sig, see
+/// [`Transaction::pool_tags`]
+#[derive(Debug, Clone)]
+pub struct PoolTags<'a>(&'a [u8]);
+
+impl<'a> Iterator for PoolTags<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        const MIN_TAG_LEN: usize = 4;
+        let printable = |b: &u8| (0x20..0x7f).contains(b);
+        loop {
+            let start = self.0.iter().position(printable)?;
+            let len = self.0[start..]
+                .iter()
+                .position(|b| !printable(b))
+                .unwrap_or(self.0.len() - start);
+            let mut tag = &self.0[start..start + len];
+            self.0 = &self.0[start + len..];
+            if tag[0] as usize == tag.len() - 1 {
+                // the printable byte is the opcode pushing exactly the following string
+                tag = &tag[1..];
+            }
+            if tag.len() >= MIN_TAG_LEN {
+                return Some(core::str::from_utf8(tag).expect("ascii is valid utf8"));
+            }
+        }
+    }
 }
 
 impl<'a> AsRef<[u8]> for Transaction<'a> {
@@ -148,6 +622,23 @@ impl<'a> AsRef<[u8]> for Transaction<'a> {
     }
 }
 
+impl AsSlice for Transaction<'_> {
+    fn as_slice(&self) -> &[u8] {
+        self.slice
+    }
+}
+
+impl<'a> FromSlice<'a> for Transaction<'a> {
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        crate::codec::exact(Transaction::parse(slice))
+    }
+
+    /// Doesn't parse, see [`Transaction::from_trusted_slice`]
+    fn from_trusted_slice(slice: &'a [u8]) -> Self {
+        Transaction::from_trusted_slice(slice)
+    }
+}
+
 #[cfg(feature = "redb")]
 impl<'o> redb::RedbValue for Transaction<'o> {
     // TODO fix where position once MSRV allows
@@ -169,17 +660,7 @@ impl<'o> redb::RedbValue for Transaction<'o> {
     where
         Self: 'a,
     {
-        // TODO this is inefficient, should bsl objects contains only a slice so that this is easy?
-        // (but getting other stuff may be expensive?)
-        // a middle ground could be caching values, and computing them only if needed, for example
-        // for Transaction having inputs_outputs_len enum
-        // * KnownSegwit(usize)
-        // * KnownLegacy(usize)
-        // * Unknown
-        // This method would return `Transaction { slice: data, input_outputs_len: Unknown }`
-        Transaction::parse(data)
-            .expect("inserted data is not a Transaction")
-            .parsed_owned()
+        Transaction::from_trusted_slice(data)
     }
 
     fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
@@ -211,7 +692,7 @@ mod test {
         assert_eq!(tx.parsed().locktime(), 0);
 
         check_hash(
-            &tx.parsed(),
+            tx.parsed(),
             hex!("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"),
         );
     }
@@ -227,11 +708,70 @@ mod test {
         assert_eq!(tx.parsed().locktime(), 0);
 
         check_hash(
-            &tx.parsed(),
+            tx.parsed(),
             hex!("4be105f158ea44aec57bf12c5817d073a712ab131df6f37786872cfc70734188"), // testnet tx
         );
     }
 
+    #[test]
+    fn coinbase() {
+        let segwit_tx = hex!("010000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff3603da1b0e00045503bd5704c7dd8a0d0ced13bb5785010800000000000a636b706f6f6c122f4e696e6a61506f6f6c2f5345475749542fffffffff02b4e5a212000000001976a914876fbb82ec05caa6af7a3b5e5a983aae6c6cc6d688ac0000000000000000266a24aa21a9edf91c46b49eb8a29089980f02ee6b57e7d63d33b18b4fddac2bcd7db2a39837040120000000000000000000000000000000000000000000000000000000000000000000000000");
+        let tx = Transaction::parse(&segwit_tx[..]).unwrap().parsed_owned();
+        assert!(tx.is_coinbase());
+        assert_eq!(tx.bip34_height(), Some(924634));
+        assert_eq!(tx.coinbase_extranonce().unwrap(), &segwit_tx[48..98]);
+        let tags: Vec<_> = tx.pool_tags().collect();
+        assert_eq!(tags, vec!["ckpool", "/NinjaPool/SEGWIT/"]);
+        assert_eq!(tx.coinbase_witness_reserved_value(), Some(&[0u8; 32]));
+
+        // pre BIP34, the first push is the nBits of the block
+        let genesis = Transaction::parse(&GENESIS_TX[..]).unwrap().parsed_owned();
+        assert!(genesis.is_coinbase());
+        assert_eq!(genesis.bip34_height(), Some(0x1d00ffff));
+        assert_eq!(
+            genesis.pool_tags().collect::<Vec<_>>(),
+            vec!["The Times 03/Jan/2009 Chancellor on brink of second bailout for banks"]
+        );
+        assert_eq!(genesis.coinbase_witness_reserved_value(), None);
+
+        let block = bitcoin_test_data::blocks::mainnet_702861();
+        let coinbase = Transaction::parse(&block[83..]).unwrap();
+        assert_eq!(coinbase.parsed().bip34_height(), Some(702861));
+        let tx = Transaction::parse(coinbase.remaining())
+            .unwrap()
+            .parsed_owned();
+        assert!(!tx.is_coinbase());
+        assert_eq!(tx.bip34_height(), None);
+        assert_eq!(tx.coinbase_extranonce(), None);
+        assert_eq!(tx.pool_tags().count(), 0);
+        assert_eq!(tx.coinbase_witness_reserved_value(), None);
+    }
+
+    #[test]
+    fn from_trusted_slice() {
+        let block = bitcoin_test_data::blocks::mainnet_702861();
+        let mut slice = &block[83..];
+        while let Ok(tx) = Transaction::parse(slice) {
+            let trusted = Transaction::from_trusted_slice(&slice[..tx.consumed()]);
+            let tx = tx.parsed();
+            assert_eq!(&trusted, tx);
+            assert_eq!(trusted.has_witness(), tx.has_witness());
+            assert_eq!(trusted.txid_preimage(), tx.txid_preimage());
+            assert_eq!(trusted.weight(), tx.weight());
+            assert_eq!(trusted.inputs_outputs_len(), tx.cached_inputs_outputs_len());
+            assert_eq!(
+                trusted.cached_inputs_outputs_len(),
+                tx.cached_inputs_outputs_len()
+            );
+            slice = &slice[tx.as_ref().len()..];
+        }
+        assert!(slice.is_empty());
+
+        let genesis = Transaction::from_trusted_slice(&GENESIS_TX[..]);
+        assert!(!genesis.has_witness());
+        assert_eq!(genesis.txid_preimage().0, &GENESIS_TX[..]);
+    }
+
     #[test]
     fn parse_nonminimal_transaction() {
         let first_part =  hex!("020000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff310349ce0b04db6fd2632f466f756e6472792055534120506f6f6c202364726f70676f6c642f1e284d6da44c000000000000ffffffff02311b662500000000");
@@ -258,10 +798,98 @@ mod test {
         assert!(Transaction::parse(&tx_nonminimal[..]).is_err());
     }
 
+    #[test]
+    fn visit_skip_subtree() {
+        use crate::{bsl::TxIn, bsl::TxOut, Descend, Error, Subtree, Visit, Visitor};
+        use core::ops::ControlFlow;
+
+        #[derive(Default)]
+        struct Skipper {
+            skip: Option<Subtree>,
+            break_at: Option<Subtree>,
+            subtrees: usize,
+            inputs: usize,
+            outputs: usize,
+            witness_elements: usize,
+        }
+        impl Visitor for Skipper {
+            fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<(), Descend> {
+                self.subtrees += 1;
+                if self.break_at == Some(subtree) {
+                    ControlFlow::Break(())
+                } else if self.skip == Some(subtree) {
+                    ControlFlow::Continue(Descend::Skip)
+                } else {
+                    ControlFlow::Continue(Descend::Visit)
+                }
+            }
+            fn visit_tx_in(&mut self, _vin: usize, _tx_in: &TxIn) -> ControlFlow<()> {
+                self.inputs += 1;
+                ControlFlow::Continue(())
+            }
+            fn visit_tx_out(&mut self, _vout: usize, _tx_out: &TxOut) -> ControlFlow<()> {
+                self.outputs += 1;
+                ControlFlow::Continue(())
+            }
+            fn visit_witness_element(&mut self, _witness_i: usize, _witness_element: &[u8]) {
+                self.witness_elements += 1;
+            }
+        }
+        let segwit_tx = hex!("010000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff3603da1b0e00045503bd5704c7dd8a0d0ced13bb5785010800000000000a636b706f6f6c122f4e696e6a61506f6f6c2f5345475749542fffffffff02b4e5a212000000001976a914876fbb82ec05caa6af7a3b5e5a983aae6c6cc6d688ac0000000000000000266a24aa21a9edf91c46b49eb8a29089980f02ee6b57e7d63d33b18b4fddac2bcd7db2a39837040120000000000000000000000000000000000000000000000000000000000000000000000000");
+
+        let mut visitor = Skipper::default();
+        let tx = Transaction::visit(&segwit_tx[..], &mut visitor).unwrap();
+        assert_eq!(tx.consumed(), 222);
+        assert_eq!(
+            (visitor.subtrees, visitor.inputs, visitor.outputs),
+            (3, 1, 2)
+        );
+        assert_eq!(visitor.witness_elements, 1);
+
+        for (skip, expected) in [
+            (Subtree::TxIns, (0, 2, 1)),
+            (Subtree::TxOuts, (1, 0, 1)),
+            (Subtree::Witnesses, (1, 2, 0)),
+        ] {
+            let mut visitor = Skipper {
+                skip: Some(skip),
+                ..Default::default()
+            };
+            let tx = Transaction::visit(&segwit_tx[..], &mut visitor).unwrap();
+            assert_eq!(tx.parsed().as_ref(), &segwit_tx[..]);
+            assert_eq!(
+                (visitor.inputs, visitor.outputs, visitor.witness_elements),
+                expected
+            );
+        }
+
+        let mut visitor = Skipper {
+            skip: Some(Subtree::TxIns),
+            ..Default::default()
+        };
+        Transaction::visit(&GENESIS_TX[..], &mut visitor).unwrap();
+        assert_eq!(
+            (visitor.subtrees, visitor.inputs, visitor.outputs),
+            (2, 0, 1)
+        );
+
+        let mut visitor = Skipper {
+            break_at: Some(Subtree::TxOuts),
+            ..Default::default()
+        };
+        assert_eq!(
+            Transaction::visit(&segwit_tx[..], &mut visitor),
+            Err(Error::VisitBreak)
+        );
+        assert_eq!((visitor.inputs, visitor.outputs), (1, 0));
+    }
+
     #[cfg(target_pointer_width = "64")]
     #[test]
     fn size_of() {
         assert_eq!(std::mem::size_of::<Transaction>(), 24);
+        fn sync<T: Send + Sync>() {}
+        sync::<Transaction>();
     }
 
     #[cfg(all(not(feature = "sha2"), not(feature = "bitcoin_hashes")))]
@@ -3,6 +3,7 @@ use crate::{
     number::U32,
     Parse, ParseResult, SResult,
 };
+use crate::{AsSlice, Error, FromSlice};
 
 /// A transaction input
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -14,7 +15,7 @@ pub struct TxIn<'a> {
 }
 
 impl<'a> Parse<'a> for TxIn<'a> {
-    fn parse(slice: &'a [u8]) -> SResult<Self> {
+    fn parse(slice: &'a [u8]) -> SResult<'a, Self> {
         let out_point = OutPoint::parse(slice)?;
         let script = Script::parse(out_point.remaining())?;
         let sequence = U32::parse(script.remaining())?;
@@ -30,11 +31,11 @@ impl<'a> Parse<'a> for TxIn<'a> {
 }
 impl<'a> TxIn<'a> {
     /// Returns the previous output index spent by this transaction input
-    pub fn prevout(&self) -> &OutPoint {
+    pub fn prevout(&self) -> &OutPoint<'_> {
         &self.prevout
     }
     /// Return the script sig of this transaction input
-    pub fn script_sig(&self) -> &[u8] {
+    pub fn script_sig(&self) -> &'a [u8] {
         self.script_sig.script()
     }
     /// Returns the sequence of this transaction input
@@ -49,6 +50,55 @@ impl<'a> AsRef<[u8]> for TxIn<'a> {
     }
 }
 
+impl AsSlice for TxIn<'_> {
+    fn as_slice(&self) -> &[u8] {
+        self.as_ref()
+    }
+}
+
+impl<'a> FromSlice<'a> for TxIn<'a> {
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        crate::codec::exact(TxIn::parse(slice))
+    }
+}
+
+#[cfg(feature = "redb")]
+impl<'o> redb::RedbValue for TxIn<'o> {
+    // TODO fix where position once MSRV allows
+    type SelfType<'a>
+    where
+        Self: 'a,
+    = TxIn<'a>;
+
+    type AsBytes<'a>
+    where
+        Self: 'a,
+    = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+        TxIn::from_trusted_slice(data)
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value.as_ref()
+    }
+
+    fn type_name() -> redb::TypeName {
+        redb::TypeName::new("bsl::TxIn")
+    }
+}
+
 #[cfg(test)]
 mod test {
     use hex_lit::hex;
@@ -2,7 +2,8 @@ use core::ops::ControlFlow;
 
 use super::len::{parse_len, Len};
 use crate::bsl::TxIn;
-use crate::{Parse, ParseResult, SResult, Visit, Visitor};
+use crate::{AsSlice, Error, FromSlice};
+use crate::{BResult, Parse, ParseResult, Stop, Visit, Visitor};
 
 /// The transaction inputs of a transaction
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -12,7 +13,10 @@ pub struct TxIns<'a> {
 }
 
 impl<'a> Visit<'a> for TxIns<'a> {
-    fn visit<'b, V: Visitor>(slice: &'a [u8], visit: &'b mut V) -> SResult<'a, Self> {
+    fn visit_break<'b, B, V: Visitor<B>>(
+        slice: &'a [u8],
+        visit: &'b mut V,
+    ) -> BResult<'a, Self, B> {
         let Len { mut consumed, n } = parse_len(slice)?;
         let mut remaining = &slice[consumed..];
         let total_inputs = n as usize;
@@ -22,8 +26,8 @@ impl<'a> Visit<'a> for TxIns<'a> {
             let tx_in = TxIn::parse(remaining)?;
             remaining = tx_in.remaining();
             consumed += tx_in.consumed();
-            if let ControlFlow::Break(_) = visit.visit_tx_in(i, tx_in.parsed()) {
-                return Err(crate::Error::VisitBreak);
+            if let ControlFlow::Break(value) = visit.visit_tx_in(i, tx_in.parsed()) {
+                return Err(Stop::Break { value, remaining });
             }
         }
 
@@ -55,6 +59,62 @@ impl<'a> AsRef<[u8]> for TxIns<'a> {
 
 impl<'a> TxIns<'a> {}
 
+impl AsSlice for TxIns<'_> {
+    fn as_slice(&self) -> &[u8] {
+        self.as_ref()
+    }
+}
+
+impl<'a> FromSlice<'a> for TxIns<'a> {
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        crate::codec::exact(TxIns::parse(slice))
+    }
+
+    fn from_trusted_slice(slice: &'a [u8]) -> Self {
+        let n = parse_len(slice)
+            .expect("inserted data is not a valid TxIns")
+            .n() as usize;
+        TxIns { slice, n }
+    }
+}
+
+#[cfg(feature = "redb")]
+impl<'o> redb::RedbValue for TxIns<'o> {
+    // TODO fix where position once MSRV allows
+    type SelfType<'a>
+    where
+        Self: 'a,
+    = TxIns<'a>;
+
+    type AsBytes<'a>
+    where
+        Self: 'a,
+    = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+        TxIns::from_trusted_slice(data)
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value.as_ref()
+    }
+
+    fn type_name() -> redb::TypeName {
+        redb::TypeName::new("bsl::TxIns")
+    }
+}
+
 #[cfg(test)]
 mod test {
     use core::ops::ControlFlow;
@@ -1,5 +1,6 @@
 use crate::bsl::Script;
 use crate::number::U64;
+use crate::{AsSlice, Error, FromSlice};
 use crate::{Parse, ParseResult, SResult};
 
 /// Contains a single transaction output
@@ -10,7 +11,7 @@ pub struct TxOut<'a> {
     script_pubkey: Script<'a>,
 }
 impl<'a> Parse<'a> for TxOut<'a> {
-    fn parse(slice: &'a [u8]) -> SResult<Self> {
+    fn parse(slice: &'a [u8]) -> SResult<'a, Self> {
         let value = U64::parse(slice)?;
         let script = Script::parse(value.remaining())?;
         let consumed = value.consumed() + script.consumed();
@@ -29,7 +30,7 @@ impl<'a> TxOut<'a> {
         self.value
     }
     /// Return the script pubkey of this output
-    pub fn script_pubkey(&self) -> &[u8] {
+    pub fn script_pubkey(&self) -> &'a [u8] {
         self.script_pubkey.script()
     }
 }
@@ -40,6 +41,18 @@ impl<'a> AsRef<[u8]> for TxOut<'a> {
     }
 }
 
+impl AsSlice for TxOut<'_> {
+    fn as_slice(&self) -> &[u8] {
+        self.as_ref()
+    }
+}
+
+impl<'a> FromSlice<'a> for TxOut<'a> {
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        crate::codec::exact(TxOut::parse(slice))
+    }
+}
+
 #[cfg(feature = "redb")]
 impl<'o> redb::RedbValue for TxOut<'o> {
     // TODO fix where position once MSRV allows
@@ -61,9 +74,7 @@ impl<'o> redb::RedbValue for TxOut<'o> {
     where
         Self: 'a,
     {
-        TxOut::parse(data)
-            .expect("inserted data is not a TxOut")
-            .parsed_owned()
+        TxOut::from_trusted_slice(data)
     }
 
     fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
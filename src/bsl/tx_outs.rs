@@ -2,7 +2,8 @@ use core::ops::ControlFlow;
 
 use super::len::{parse_len, Len};
 use crate::bsl::TxOut;
-use crate::{Parse, ParseResult, SResult, Visit, Visitor};
+use crate::{AsSlice, Error, FromSlice};
+use crate::{BResult, Parse, ParseResult, Stop, Visit, Visitor};
 
 /// The transaction outputs of a transaction
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -12,7 +13,10 @@ pub struct TxOuts<'a> {
 }
 
 impl<'a> Visit<'a> for TxOuts<'a> {
-    fn visit<'b, V: Visitor>(slice: &'a [u8], visit: &'b mut V) -> SResult<'a, Self> {
+    fn visit_break<'b, B, V: Visitor<B>>(
+        slice: &'a [u8],
+        visit: &'b mut V,
+    ) -> BResult<'a, Self, B> {
         let Len { mut consumed, n } = parse_len(slice)?;
         let mut remaining = &slice[consumed..];
         let total_outputs = n as usize;
@@ -22,8 +26,8 @@ impl<'a> Visit<'a> for TxOuts<'a> {
             let tx_out = TxOut::parse(remaining)?;
             remaining = tx_out.remaining();
             consumed += tx_out.consumed();
-            if let ControlFlow::Break(_) = visit.visit_tx_out(i, tx_out.parsed()) {
-                return Err(crate::Error::VisitBreak);
+            if let ControlFlow::Break(value) = visit.visit_tx_out(i, tx_out.parsed()) {
+                return Err(Stop::Break { value, remaining });
             }
         }
         Ok(ParseResult::new(
@@ -106,6 +110,25 @@ impl<'a> AsRef<[u8]> for TxOuts<'a> {
     }
 }
 
+impl AsSlice for TxOuts<'_> {
+    fn as_slice(&self) -> &[u8] {
+        self.as_ref()
+    }
+}
+
+impl<'a> FromSlice<'a> for TxOuts<'a> {
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        crate::codec::exact(TxOuts::parse(slice))
+    }
+
+    fn from_trusted_slice(slice: &'a [u8]) -> Self {
+        let n = parse_len(slice)
+            .expect("inserted data is not a valid TxOuts")
+            .n() as usize;
+        TxOuts { slice, n }
+    }
+}
+
 #[cfg(feature = "redb")]
 impl<'o> redb::RedbValue for TxOuts<'o> {
     // TODO fix where position once MSRV allows
@@ -127,10 +150,7 @@ impl<'o> redb::RedbValue for TxOuts<'o> {
     where
         Self: 'a,
     {
-        let n = parse_len(&data)
-            .expect("inserted data is not a valid TxOuts")
-            .n() as usize;
-        TxOuts { slice: data, n }
+        TxOuts::from_trusted_slice(data)
     }
 
     fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
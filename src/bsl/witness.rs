@@ -1,6 +1,7 @@
 use super::len::{parse_len, Len};
 use crate::Visit;
-use crate::{slice::read_slice, ParseResult, SResult, Visitor};
+use crate::{slice::read_slice, BResult, ParseResult, Visitor};
+use crate::{AsSlice, Error, FromSlice, Parse};
 
 /// A single witness associated with a single transaction input.
 /// Logically is a vector of bytes vector.
@@ -16,7 +17,10 @@ impl<'a> AsRef<[u8]> for Witness<'a> {
 }
 
 impl<'a> Visit<'a> for Witness<'a> {
-    fn visit<'b, V: Visitor>(slice: &'a [u8], visit: &'b mut V) -> SResult<'a, Witness<'a>> {
+    fn visit_break<'b, B, V: Visitor<B>>(
+        slice: &'a [u8],
+        visit: &'b mut V,
+    ) -> BResult<'a, Witness<'a>, B> {
         let Len { mut consumed, n } = parse_len(slice)?;
         let mut remaining = &slice[consumed..];
         let witness_total_element = n as usize;
@@ -43,6 +47,59 @@ impl<'a> Witness<'a> {
     }
 }
 
+impl AsSlice for Witness<'_> {
+    fn as_slice(&self) -> &[u8] {
+        self.as_ref()
+    }
+}
+
+impl<'a> FromSlice<'a> for Witness<'a> {
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        crate::codec::exact(Witness::parse(slice))
+    }
+
+    fn from_trusted_slice(slice: &'a [u8]) -> Self {
+        Witness { slice }
+    }
+}
+
+#[cfg(feature = "redb")]
+impl<'o> redb::RedbValue for Witness<'o> {
+    // TODO fix where position once MSRV allows
+    type SelfType<'a>
+    where
+        Self: 'a,
+    = Witness<'a>;
+
+    type AsBytes<'a>
+    where
+        Self: 'a,
+    = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+        Witness::from_trusted_slice(data)
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value.as_ref()
+    }
+
+    fn type_name() -> redb::TypeName {
+        redb::TypeName::new("bsl::Witness")
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{bsl::Witness, Parse, ParseResult, Visit, Visitor};
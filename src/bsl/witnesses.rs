@@ -1,7 +1,8 @@
 use core::ops::ControlFlow;
 
 use crate::bsl::Witness;
-use crate::{ParseResult, SResult, Visit};
+use crate::{AsSlice, Error, FromSlice, Parse};
+use crate::{BResult, ParseResult, SResult, Stop, Visit};
 
 /// Struct containining all the Witness in the tx (which is the same number as the inputs)
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -17,7 +18,7 @@ impl<'a> AsRef<[u8]> for Witnesses<'a> {
 
 impl<'a> Witnesses<'a> {
     /// Parse the witnesses in the slice
-    pub fn parse(slice: &'a [u8], total_inputs: usize) -> SResult<Self> {
+    pub fn parse(slice: &'a [u8], total_inputs: usize) -> SResult<'a, Self> {
         Self::visit(slice, total_inputs, &mut crate::visit::EmptyVisitor {})
     }
     /// Visit the witnesses in the slice
@@ -26,15 +27,25 @@ impl<'a> Witnesses<'a> {
         total_inputs: usize,
         visit: &'b mut V,
     ) -> SResult<'a, Self> {
+        Self::visit_break(slice, total_inputs, visit).map_err(Stop::into_error)
+    }
+
+    /// Visit the witnesses in the slice, the visitor may break with a value of type `B`, see
+    /// [`Visit::visit_break`]
+    pub fn visit_break<'b, B, V: crate::Visitor<B>>(
+        slice: &'a [u8],
+        total_inputs: usize,
+        visit: &'b mut V,
+    ) -> BResult<'a, Self, B> {
         let mut remaining = slice;
         let mut consumed = 0;
         let mut all_empty = true;
         for i in 0..total_inputs {
-            if let ControlFlow::Break(_) = visit.visit_witness(i) {
-                return Err(crate::Error::VisitBreak);
+            if let ControlFlow::Break(value) = visit.visit_witness(i) {
+                return Err(Stop::Break { value, remaining });
             }
 
-            let witness = Witness::visit(remaining, visit)?;
+            let witness = Witness::visit_break(remaining, visit)?;
             visit.visit_witness_end();
 
             remaining = witness.remaining();
@@ -56,6 +67,63 @@ impl<'a> Witnesses<'a> {
     }
 }
 
+impl AsSlice for Witnesses<'_> {
+    fn as_slice(&self) -> &[u8] {
+        self.slice
+    }
+}
+
+/// The witnesses are stored without their count, which is the number of inputs of the transaction
+impl<'a> FromSlice<'a> for Witnesses<'a> {
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        let mut remaining = slice;
+        let mut all_empty = true;
+        while !remaining.is_empty() {
+            let witness = Witness::parse(remaining)?;
+            all_empty &= witness.parsed().is_empty();
+            remaining = witness.remaining();
+        }
+        Ok(Witnesses { slice, all_empty })
+    }
+}
+
+#[cfg(feature = "redb")]
+impl<'o> redb::RedbValue for Witnesses<'o> {
+    // TODO fix where position once MSRV allows
+    type SelfType<'a>
+    where
+        Self: 'a,
+    = Witnesses<'a>;
+
+    type AsBytes<'a>
+    where
+        Self: 'a,
+    = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+        Witnesses::from_trusted_slice(data)
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value.as_ref()
+    }
+
+    fn type_name() -> redb::TypeName {
+        redb::TypeName::new("bsl::Witnesses")
+    }
+}
+
 #[cfg(test)]
 mod test {
     use core::ops::ControlFlow;
@@ -89,7 +157,7 @@ mod test {
                 match self.witness_vin {
                     0 => assert_eq!(witness_total, 1),
                     1 => assert_eq!(witness_total, 2),
-                    _ => assert!(false),
+                    _ => unreachable!(),
                 }
             }
             fn visit_witness_element(&mut self, _witness_i: usize, witness_element: &[u8]) {
@@ -97,7 +165,7 @@ mod test {
                     (0, 0) => assert_eq!(witness_element, &[0u8]),
                     (1, 0) => assert_eq!(witness_element, &[1u8]),
                     (1, 1) => assert_eq!(witness_element, &[2u8]),
-                    _ => assert!(false),
+                    _ => unreachable!(),
                 }
                 self.witness_el_i += 1;
             }
@@ -116,4 +184,48 @@ mod test {
         )
         .unwrap();
     }
+
+    #[cfg(feature = "redb")]
+    #[test]
+    fn test_witnesses_redb() {
+        use redb::ReadableTable;
+
+        use crate::{bsl::Witness, Parse};
+
+        const WITNESSES: redb::TableDefinition<u32, Witnesses> =
+            redb::TableDefinition::new("witnesses");
+        const WITNESS: redb::TableDefinition<u32, Witness> = redb::TableDefinition::new("witness");
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let db = redb::Database::create(path).unwrap();
+        let witnesses_bytes = hex!("0101000201000100");
+        let witnesses = Witnesses::parse(&witnesses_bytes[..], 2)
+            .unwrap()
+            .parsed_owned();
+        let empty_bytes = hex!("0000");
+        let empty = Witnesses::parse(&empty_bytes[..], 2)
+            .unwrap()
+            .parsed_owned();
+        let witness = Witness::parse(&witnesses_bytes[..]).unwrap().parsed_owned();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(WITNESSES).unwrap();
+            table.insert(0, &witnesses).unwrap();
+            table.insert(1, &empty).unwrap();
+            let mut table = write_txn.open_table(WITNESS).unwrap();
+            table.insert(0, &witness).unwrap();
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(WITNESSES).unwrap();
+        let read = table.get(0).unwrap().unwrap();
+        assert_eq!(read.value(), witnesses);
+        assert!(!read.value().all_empty());
+        let read = table.get(1).unwrap().unwrap();
+        assert_eq!(read.value(), empty);
+        assert!(read.value().all_empty());
+        let table = read_txn.open_table(WITNESS).unwrap();
+        assert_eq!(table.get(0).unwrap().unwrap().value(), witness);
+    }
 }
@@ -0,0 +1,701 @@
+//! Serialization of new transactions, without allocations.
+//!
+//! The [`TransactionWriter`] streams the transaction fields, in consensus order, into a [`Sink`],
+//! which can be a caller provided buffer through [`SliceWriter`] or any [`Extend<u8>`] like a
+//! `Vec<u8>`. The number of inputs and outputs and whether the transaction has witnesses must be
+//! known up front since they are serialized before the items.
+//!
+//! Existing transactions can be edited with methods like [`Transaction::replace_witness`], which
+//! write a new transaction in a [`Sink`] copying the untouched parts of the original.
+//!
+//! ```
+//! use bitcoin_slices::builder::{SliceWriter, TransactionWriter};
+//!
+//! let mut buffer = [0u8; 100];
+//! let mut writer = TransactionWriter::new(SliceWriter::new(&mut buffer), 2, 1, false).unwrap();
+//! writer.tx_in(&[0xaa; 32], 0, &[], 0xffffffff).unwrap();
+//! writer.tx_outs(1).unwrap();
+//! writer.tx_out(1000, &[0x6a]).unwrap();
+//! let tx = writer.finish(0).unwrap();
+//! assert_eq!(tx.as_ref().len(), 61);
+//! ```
+
+use core::fmt;
+
+use crate::{
+    bsl::{encode_len, parse_len, Transaction, TxIn, TxIns, TxOuts, Witness},
+    Error, Parse,
+};
+
+/// Where the [`TransactionWriter`] writes the serialized bytes
+pub trait Sink {
+    /// The value returned once the transaction is completely written
+    type Output;
+
+    /// Appends `bytes` to the sink
+    fn write(&mut self, bytes: &[u8]) -> Result<(), BuildError>;
+
+    /// Completes the writing
+    fn finish(self) -> Result<Self::Output, BuildError>;
+}
+
+/// Writes in a caller provided buffer, returning the [`Transaction`] borrowing it
+#[derive(Debug)]
+pub struct SliceWriter<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    /// Creates a writer starting at the beginning of `buffer`
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        SliceWriter { buffer, len: 0 }
+    }
+}
+
+impl<'a> Sink for SliceWriter<'a> {
+    type Output = Transaction<'a>;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), BuildError> {
+        let end = self.len + bytes.len();
+        self.buffer
+            .get_mut(self.len..end)
+            .ok_or(BuildError::BufferTooSmall)?
+            .copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Self::Output, BuildError> {
+        let buffer: &'a [u8] = self.buffer;
+        let tx = Transaction::parse(&buffer[..self.len]).map_err(BuildError::Invalid)?;
+        Ok(tx.parsed_owned())
+    }
+}
+
+/// Writes in any collection, like a `Vec<u8>`, which is returned when finished
+impl<E: Extend<u8>> Sink for E {
+    type Output = E;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), BuildError> {
+        self.extend(bytes.iter().copied());
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Self::Output, BuildError> {
+        Ok(self)
+    }
+}
+
+/// The reasons a transaction can't be written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// The buffer of the [`SliceWriter`] can't contain the transaction
+    BufferTooSmall,
+
+    /// An input, output or witness was written after all the declared ones, or out of order
+    UnexpectedItem,
+
+    /// Finished before writing all the declared inputs, outputs and witnesses
+    MissingItems,
+
+    /// A transaction must have inputs: without, a legacy serialization is ambiguous with the segwit
+    /// marker and a segwit one has no witness to write
+    NoInputs,
+
+    /// A transaction declared with witnesses must have at least one non-empty witness
+    EmptyWitnesses,
+
+    /// The edited input or output with the given index doesn't exist
+    IndexOutOfRange(usize),
+
+    /// The written transaction doesn't parse
+    Invalid(Error),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::BufferTooSmall => write!(f, "buffer too small"),
+            BuildError::UnexpectedItem => write!(f, "item not expected at this point"),
+            BuildError::MissingItems => write!(f, "some declared items are not written"),
+            BuildError::NoInputs => write!(f, "transaction without inputs"),
+            BuildError::EmptyWitnesses => write!(f, "segwit transaction with all empty witnesses"),
+            BuildError::IndexOutOfRange(i) => write!(f, "index {} out of range", i),
+            BuildError::Invalid(e) => write!(f, "invalid transaction: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuildError::Invalid(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The section of the transaction being written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Inputs,
+    Outputs,
+    Witnesses,
+}
+
+/// Writes a transaction field by field in a [`Sink`], see the [module docs](self)
+#[derive(Debug)]
+pub struct TransactionWriter<S> {
+    sink: S,
+    section: Section,
+    inputs: usize,
+    outputs: usize,
+    written: usize,
+    segwit: bool,
+    any_witness: bool,
+}
+
+impl<S: Sink> TransactionWriter<S> {
+    /// Starts a transaction with the given `version` and number of `inputs`, with `segwit` the
+    /// transaction is serialized with witnesses, which must be written after the outputs.
+    ///
+    /// Returns [`BuildError::NoInputs`] if `inputs` is 0, legacy or segwit.
+    pub fn new(mut sink: S, version: i32, inputs: usize, segwit: bool) -> Result<Self, BuildError> {
+        if inputs == 0 {
+            return Err(BuildError::NoInputs);
+        }
+        sink.write(&version.to_le_bytes())?;
+        if segwit {
+            sink.write(&[0, 1])?;
+        }
+        write_len(&mut sink, inputs)?;
+        Ok(TransactionWriter {
+            sink,
+            section: Section::Inputs,
+            inputs,
+            outputs: 0,
+            written: 0,
+            segwit,
+            any_witness: false,
+        })
+    }
+
+    /// Writes the next input, spending the output `vout` of the transaction `txid`
+    pub fn tx_in(
+        &mut self,
+        txid: &[u8; 32],
+        vout: u32,
+        script_sig: &[u8],
+        sequence: u32,
+    ) -> Result<(), BuildError> {
+        self.next_item(Section::Inputs, self.inputs)?;
+        self.sink.write(txid)?;
+        self.sink.write(&vout.to_le_bytes())?;
+        write_var_bytes(&mut self.sink, script_sig)?;
+        self.sink.write(&sequence.to_le_bytes())
+    }
+
+    /// Declares the number of `outputs`, after all the inputs are written
+    pub fn tx_outs(&mut self, outputs: usize) -> Result<(), BuildError> {
+        if self.section != Section::Inputs || self.written != self.inputs {
+            return Err(BuildError::UnexpectedItem);
+        }
+        write_len(&mut self.sink, outputs)?;
+        self.section = Section::Outputs;
+        self.outputs = outputs;
+        self.written = 0;
+        Ok(())
+    }
+
+    /// Writes the next output
+    pub fn tx_out(&mut self, value: u64, script_pubkey: &[u8]) -> Result<(), BuildError> {
+        self.next_item(Section::Outputs, self.outputs)?;
+        self.sink.write(&value.to_le_bytes())?;
+        write_var_bytes(&mut self.sink, script_pubkey)
+    }
+
+    /// Writes the witness of the next input, after all the outputs are written
+    pub fn witness(&mut self, elements: &[&[u8]]) -> Result<(), BuildError> {
+        if self.segwit && self.section == Section::Outputs && self.written == self.outputs {
+            self.section = Section::Witnesses;
+            self.written = 0;
+        }
+        self.next_item(Section::Witnesses, self.inputs)?;
+        write_len(&mut self.sink, elements.len())?;
+        for element in elements {
+            write_var_bytes(&mut self.sink, element)?;
+        }
+        self.any_witness |= !elements.is_empty();
+        Ok(())
+    }
+
+    /// Writes the `locktime` and completes the transaction, returning the output of the sink
+    pub fn finish(mut self, locktime: u32) -> Result<S::Output, BuildError> {
+        let complete = match self.section {
+            Section::Inputs => false,
+            Section::Outputs => !self.segwit && self.written == self.outputs,
+            Section::Witnesses => self.written == self.inputs,
+        };
+        if !complete {
+            return Err(BuildError::MissingItems);
+        }
+        if self.segwit && !self.any_witness {
+            return Err(BuildError::EmptyWitnesses);
+        }
+        self.sink.write(&locktime.to_le_bytes())?;
+        self.sink.finish()
+    }
+
+    fn next_item(&mut self, section: Section, declared: usize) -> Result<(), BuildError> {
+        if self.section != section || self.written == declared {
+            return Err(BuildError::UnexpectedItem);
+        }
+        self.written += 1;
+        Ok(())
+    }
+}
+
+fn write_len<S: Sink>(sink: &mut S, n: usize) -> Result<(), BuildError> {
+    let (bytes, len) = encode_len(n as u64);
+    sink.write(&bytes[..len])
+}
+
+/// The serialized sections of a transaction
+struct Sections<'s> {
+    version: &'s [u8],
+    /// The inputs, with the compact int count
+    tx_ins: &'s [u8],
+    inputs: usize,
+    /// The outputs, with the compact int count
+    tx_outs: &'s [u8],
+    /// Empty for legacy transactions
+    witnesses: &'s [u8],
+    locktime: &'s [u8],
+}
+
+impl<'s> Sections<'s> {
+    fn new(tx: &'s Transaction) -> Self {
+        let slice = tx.as_ref();
+        let from = if tx.has_witness() { 6 } else { 4 };
+        let tx_ins = TxIns::parse(&slice[from..]).expect("slice validated during parsing");
+        let tx_outs = TxOuts::parse(tx_ins.remaining()).expect("slice validated during parsing");
+        let outs_end = from + tx_ins.consumed() + tx_outs.consumed();
+        let locktime_start = slice.len() - 4;
+        Sections {
+            version: &slice[..4],
+            tx_ins: &slice[from..from + tx_ins.consumed()],
+            inputs: tx_ins.parsed().n(),
+            tx_outs: &slice[from + tx_ins.consumed()..outs_end],
+            witnesses: &slice[outs_end..locktime_start],
+            locktime: &slice[locktime_start..],
+        }
+    }
+
+    /// Writes version, the segwit marker and flag if `segwit`, inputs and outputs
+    fn write_head<S: Sink>(&self, sink: &mut S, segwit: bool) -> Result<(), BuildError> {
+        sink.write(self.version)?;
+        if segwit {
+            sink.write(&[0, 1])?;
+        }
+        sink.write(self.tx_ins)?;
+        sink.write(self.tx_outs)
+    }
+}
+
+/// Returns the start and the end of the item with the given `index` in `items`, serialized one
+/// after the other
+fn item_range<'s, T: Parse<'s>>(items: &'s [u8], index: usize) -> (usize, usize) {
+    let mut start = 0;
+    for _ in 0..index {
+        start += T::parse(&items[start..])
+            .expect("slice validated during parsing")
+            .consumed();
+    }
+    let len = T::parse(&items[start..])
+        .expect("slice validated during parsing")
+        .consumed();
+    (start, start + len)
+}
+
+/// Returns the compact int prefix of a list of items, and the serialized items
+fn split_count(slice: &[u8]) -> (&[u8], &[u8]) {
+    let len = parse_len(slice).expect("slice validated during parsing");
+    slice.split_at(len.consumed())
+}
+
+fn write_var_bytes<S: Sink>(sink: &mut S, bytes: &[u8]) -> Result<(), BuildError> {
+    write_len(sink, bytes.len())?;
+    sink.write(bytes)
+}
+
+impl<'a> Transaction<'a> {
+    /// Writes this transaction without witnesses in `sink`, the result is the contiguous
+    /// [`Transaction::txid_preimage`].
+    pub fn strip_witness<S: Sink>(&self, mut sink: S) -> Result<S::Output, BuildError> {
+        let sections = Sections::new(self);
+        if sections.inputs == 0 {
+            return Err(BuildError::NoInputs);
+        }
+        sections.write_head(&mut sink, false)?;
+        sink.write(sections.locktime)?;
+        sink.finish()
+    }
+
+    /// Writes this transaction in `sink`, with the witness of the input `index` replaced by
+    /// `elements`. Legacy transactions become segwit, unless `elements` is empty.
+    pub fn replace_witness<S: Sink>(
+        &self,
+        mut sink: S,
+        index: usize,
+        elements: &[&[u8]],
+    ) -> Result<S::Output, BuildError> {
+        let sections = Sections::new(self);
+        if index >= sections.inputs {
+            return Err(BuildError::IndexOutOfRange(index));
+        }
+        let (start, end) = if self.has_witness() {
+            item_range::<Witness>(sections.witnesses, index)
+        } else {
+            (index, index + 1) // every witness is the single byte empty count
+        };
+        // an empty witness is the single byte zero count, non-empty ones are longer
+        let others_empty = !self.has_witness() || {
+            let (before, after) = (&sections.witnesses[..start], &sections.witnesses[end..]);
+            before.len() == index && after.len() == sections.inputs - index - 1
+        };
+        if elements.is_empty() && others_empty {
+            return self.strip_witness(sink);
+        }
+
+        sections.write_head(&mut sink, true)?;
+        if self.has_witness() {
+            sink.write(&sections.witnesses[..start])?;
+        } else {
+            for _ in 0..index {
+                sink.write(&[0])?;
+            }
+        }
+        write_len(&mut sink, elements.len())?;
+        for element in elements {
+            write_var_bytes(&mut sink, element)?;
+        }
+        if self.has_witness() {
+            sink.write(&sections.witnesses[end..])?;
+        } else {
+            for _ in index + 1..sections.inputs {
+                sink.write(&[0])?;
+            }
+        }
+        sink.write(sections.locktime)?;
+        sink.finish()
+    }
+
+    /// Writes this transaction in `sink`, with the script sig of the input `index` replaced by
+    /// `script_sig`
+    pub fn replace_script_sig<S: Sink>(
+        &self,
+        mut sink: S,
+        index: usize,
+        script_sig: &[u8],
+    ) -> Result<S::Output, BuildError> {
+        let sections = Sections::new(self);
+        if index >= sections.inputs {
+            return Err(BuildError::IndexOutOfRange(index));
+        }
+        let (count, tx_ins) = split_count(sections.tx_ins);
+        let (start, end) = item_range::<TxIn>(tx_ins, index);
+
+        sink.write(sections.version)?;
+        if self.has_witness() {
+            sink.write(&[0, 1])?;
+        }
+        sink.write(count)?;
+        sink.write(&tx_ins[..start + 36])?; // up to the prevout of the replaced input
+        write_var_bytes(&mut sink, script_sig)?;
+        sink.write(&tx_ins[end - 4..])?; // from the sequence of the replaced input
+        sink.write(sections.tx_outs)?;
+        sink.write(sections.witnesses)?;
+        sink.write(sections.locktime)?;
+        sink.finish()
+    }
+
+    /// Writes this transaction in `sink`, with a new output appended
+    pub fn append_output<S: Sink>(
+        &self,
+        mut sink: S,
+        value: u64,
+        script_pubkey: &[u8],
+    ) -> Result<S::Output, BuildError> {
+        let sections = Sections::new(self);
+        let (count, tx_outs) = split_count(sections.tx_outs);
+        let outputs = parse_len(count)
+            .expect("slice validated during parsing")
+            .n();
+
+        sink.write(sections.version)?;
+        if self.has_witness() {
+            sink.write(&[0, 1])?;
+        }
+        sink.write(sections.tx_ins)?;
+        write_len(&mut sink, outputs as usize + 1)?;
+        sink.write(tx_outs)?;
+        sink.write(&value.to_le_bytes())?;
+        write_var_bytes(&mut sink, script_pubkey)?;
+        sink.write(sections.witnesses)?;
+        sink.write(sections.locktime)?;
+        sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hex_lit::hex;
+
+    use super::{BuildError, SliceWriter, TransactionWriter};
+    use crate::{bsl::Transaction, test_common::GENESIS_TX, Parse};
+
+    #[test]
+    fn genesis() {
+        let genesis = Transaction::parse(&GENESIS_TX[..]).unwrap().parsed_owned();
+        let script_sig = &GENESIS_TX[42..42 + 77];
+        let script_pubkey = &GENESIS_TX[GENESIS_TX.len() - 4 - 67..GENESIS_TX.len() - 4];
+
+        let mut buffer = [0u8; 300];
+        let mut writer =
+            TransactionWriter::new(SliceWriter::new(&mut buffer), 1, 1, false).unwrap();
+        writer
+            .tx_in(&[0u8; 32], 0xffffffff, script_sig, 0xffffffff)
+            .unwrap();
+        writer.tx_outs(1).unwrap();
+        writer.tx_out(50 * 100_000_000, script_pubkey).unwrap();
+        let tx = writer.finish(0).unwrap();
+        assert_eq!(tx, genesis);
+
+        let mut writer = TransactionWriter::new(Vec::new(), 1, 1, false).unwrap();
+        writer
+            .tx_in(&[0u8; 32], 0xffffffff, script_sig, 0xffffffff)
+            .unwrap();
+        writer.tx_outs(1).unwrap();
+        writer.tx_out(50 * 100_000_000, script_pubkey).unwrap();
+        assert_eq!(writer.finish(0).unwrap(), GENESIS_TX.to_vec());
+
+        let mut buffer = [0u8; 150];
+        let mut writer =
+            TransactionWriter::new(SliceWriter::new(&mut buffer), 1, 1, false).unwrap();
+        writer
+            .tx_in(&[0u8; 32], 0xffffffff, script_sig, 0xffffffff)
+            .unwrap();
+        writer.tx_outs(1).unwrap();
+        assert_eq!(
+            writer.tx_out(50 * 100_000_000, script_pubkey),
+            Err(BuildError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn segwit() {
+        let segwit_tx = hex!("010000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff3603da1b0e00045503bd5704c7dd8a0d0ced13bb5785010800000000000a636b706f6f6c122f4e696e6a61506f6f6c2f5345475749542fffffffff02b4e5a212000000001976a914876fbb82ec05caa6af7a3b5e5a983aae6c6cc6d688ac0000000000000000266a24aa21a9edf91c46b49eb8a29089980f02ee6b57e7d63d33b18b4fddac2bcd7db2a39837040120000000000000000000000000000000000000000000000000000000000000000000000000");
+        let mut writer = TransactionWriter::new(Vec::new(), 1, 1, true).unwrap();
+        writer
+            .tx_in(&[0u8; 32], 0xffffffff, &segwit_tx[44..98], 0xffffffff)
+            .unwrap();
+        writer.tx_outs(2).unwrap();
+        writer.tx_out(312665524, &segwit_tx[112..137]).unwrap();
+        writer.tx_out(0, &segwit_tx[146..184]).unwrap();
+        assert_eq!(writer.witness(&[]), Ok(()));
+        assert_eq!(writer.witness(&[]), Err(BuildError::UnexpectedItem));
+
+        let mut writer = TransactionWriter::new(Vec::new(), 1, 1, true).unwrap();
+        writer
+            .tx_in(&[0u8; 32], 0xffffffff, &segwit_tx[44..98], 0xffffffff)
+            .unwrap();
+        assert_eq!(
+            writer.witness(&[&[0u8; 32]]),
+            Err(BuildError::UnexpectedItem)
+        );
+        writer.tx_outs(2).unwrap();
+        writer.tx_out(312665524, &segwit_tx[112..137]).unwrap();
+        assert_eq!(
+            writer.witness(&[&[0u8; 32]]),
+            Err(BuildError::UnexpectedItem)
+        );
+        writer.tx_out(0, &segwit_tx[146..184]).unwrap();
+        writer.witness(&[&[0u8; 32]]).unwrap();
+        assert_eq!(writer.finish(0).unwrap(), segwit_tx.to_vec());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            TransactionWriter::new(Vec::new(), 2, 0, false).unwrap_err(),
+            BuildError::NoInputs
+        );
+        assert_eq!(
+            TransactionWriter::new(Vec::new(), 2, 0, true).unwrap_err(),
+            BuildError::NoInputs
+        );
+
+        let mut writer = TransactionWriter::new(Vec::new(), 2, 2, false).unwrap();
+        writer.tx_in(&[1u8; 32], 0, &[], 0).unwrap();
+        assert_eq!(writer.tx_outs(0), Err(BuildError::UnexpectedItem));
+        assert_eq!(writer.tx_out(0, &[]), Err(BuildError::UnexpectedItem));
+        writer.tx_in(&[1u8; 32], 1, &[], 0).unwrap();
+        assert_eq!(
+            writer.tx_in(&[1u8; 32], 2, &[], 0),
+            Err(BuildError::UnexpectedItem)
+        );
+        writer.tx_outs(1).unwrap();
+        assert_eq!(writer.witness(&[]), Err(BuildError::UnexpectedItem));
+        assert_eq!(writer.finish(0).unwrap_err(), BuildError::MissingItems);
+
+        let mut writer = TransactionWriter::new(Vec::new(), 2, 1, true).unwrap();
+        writer.tx_in(&[1u8; 32], 0, &[], 0).unwrap();
+        writer.tx_outs(0).unwrap();
+        assert_eq!(writer.finish(0).unwrap_err(), BuildError::MissingItems);
+
+        let mut writer = TransactionWriter::new(Vec::new(), 2, 1, true).unwrap();
+        writer.tx_in(&[1u8; 32], 0, &[], 0).unwrap();
+        writer.tx_outs(0).unwrap();
+        writer.witness(&[]).unwrap();
+        assert_eq!(writer.finish(0).unwrap_err(), BuildError::EmptyWitnesses);
+    }
+
+    fn segwit_coinbase() -> Vec<u8> {
+        hex!("010000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff3603da1b0e00045503bd5704c7dd8a0d0ced13bb5785010800000000000a636b706f6f6c122f4e696e6a61506f6f6c2f5345475749542fffffffff02b4e5a212000000001976a914876fbb82ec05caa6af7a3b5e5a983aae6c6cc6d688ac0000000000000000266a24aa21a9edf91c46b49eb8a29089980f02ee6b57e7d63d33b18b4fddac2bcd7db2a39837040120000000000000000000000000000000000000000000000000000000000000000000000000").to_vec()
+    }
+
+    #[test]
+    fn strip_witness() {
+        let bytes = segwit_coinbase();
+        let tx = Transaction::parse(&bytes[..]).unwrap().parsed_owned();
+        let (a, b, c) = tx.txid_preimage();
+        let stripped = tx.strip_witness(Vec::new()).unwrap();
+        assert_eq!(stripped, [a, b, c].concat());
+
+        let mut buffer = [0u8; 200];
+        let stripped = tx.strip_witness(SliceWriter::new(&mut buffer)).unwrap();
+        assert!(!stripped.has_witness());
+        assert_eq!(stripped.as_ref(), [a, b, c].concat());
+
+        let genesis = Transaction::parse(&GENESIS_TX[..]).unwrap().parsed_owned();
+        assert_eq!(genesis.strip_witness(Vec::new()).unwrap(), GENESIS_TX);
+    }
+
+    #[test]
+    fn replace_witness() {
+        let genesis = Transaction::parse(&GENESIS_TX[..]).unwrap().parsed_owned();
+        let mut buffer = [0u8; 300];
+        let segwit = genesis
+            .replace_witness(SliceWriter::new(&mut buffer), 0, &[&[1, 2]])
+            .unwrap();
+        assert!(segwit.has_witness());
+        assert_eq!(segwit.strip_witness(Vec::new()).unwrap(), GENESIS_TX);
+        assert_eq!(
+            &segwit.as_ref()[segwit.as_ref().len() - 8..],
+            hex!("0102010200000000")
+        );
+        assert_eq!(
+            genesis.replace_witness(Vec::new(), 0, &[]).unwrap(),
+            GENESIS_TX
+        );
+        assert_eq!(
+            genesis.replace_witness(Vec::new(), 1, &[]).unwrap_err(),
+            BuildError::IndexOutOfRange(1)
+        );
+
+        let bytes = segwit_coinbase();
+        let tx = Transaction::parse(&bytes[..]).unwrap().parsed_owned();
+        let replaced = tx.replace_witness(Vec::new(), 0, &[&[7u8; 32]]).unwrap();
+        let replaced = Transaction::parse(&replaced[..]).unwrap().parsed_owned();
+        assert_eq!(replaced.coinbase_witness_reserved_value(), Some(&[7u8; 32]));
+        assert_eq!(
+            tx.replace_witness(Vec::new(), 0, &[]).unwrap(),
+            tx.strip_witness(Vec::new()).unwrap()
+        );
+    }
+
+    #[test]
+    fn replace_script_sig_append_output() {
+        let bytes = segwit_coinbase();
+        let tx = Transaction::parse(&bytes[..]).unwrap().parsed_owned();
+
+        let mut expected = TransactionWriter::new(Vec::new(), 1, 1, true).unwrap();
+        expected
+            .tx_in(&[0u8; 32], 0xffffffff, &[0x51, 0x51], 0xffffffff)
+            .unwrap();
+        expected.tx_outs(2).unwrap();
+        expected.tx_out(312665524, &bytes[112..137]).unwrap();
+        expected.tx_out(0, &bytes[146..184]).unwrap();
+        expected.witness(&[&[0u8; 32]]).unwrap();
+        let expected = expected.finish(0).unwrap();
+        let replaced = tx.replace_script_sig(Vec::new(), 0, &[0x51, 0x51]).unwrap();
+        assert_eq!(replaced, expected);
+        assert_eq!(
+            tx.replace_script_sig(Vec::new(), 1, &[]).unwrap_err(),
+            BuildError::IndexOutOfRange(1)
+        );
+
+        let mut expected = TransactionWriter::new(Vec::new(), 1, 1, true).unwrap();
+        expected
+            .tx_in(&[0u8; 32], 0xffffffff, &bytes[44..98], 0xffffffff)
+            .unwrap();
+        expected.tx_outs(3).unwrap();
+        expected.tx_out(312665524, &bytes[112..137]).unwrap();
+        expected.tx_out(0, &bytes[146..184]).unwrap();
+        expected.tx_out(330, &[0x51, 0x02, 0x4e, 0x73]).unwrap();
+        expected.witness(&[&[0u8; 32]]).unwrap();
+        let expected = expected.finish(0).unwrap();
+        let mut buffer = [0u8; 300];
+        let appended = tx
+            .append_output(
+                SliceWriter::new(&mut buffer),
+                330,
+                &[0x51, 0x02, 0x4e, 0x73],
+            )
+            .unwrap();
+        assert_eq!(appended.as_ref(), &expected[..]);
+    }
+
+    #[test]
+    fn edit_middle_input() {
+        fn tx(script_sig: &[u8], witness: Option<&[&[u8]]>) -> Vec<u8> {
+            let mut writer = TransactionWriter::new(Vec::new(), 2, 3, witness.is_some()).unwrap();
+            writer.tx_in(&[1u8; 32], 0, &[0xaa], 1).unwrap();
+            writer.tx_in(&[2u8; 32], 1, script_sig, 2).unwrap();
+            writer.tx_in(&[3u8; 32], 2, &[0xbb], 3).unwrap();
+            writer.tx_outs(1).unwrap();
+            writer.tx_out(1000, &[0x6a]).unwrap();
+            if let Some(witness) = witness {
+                writer.witness(&[]).unwrap();
+                writer.witness(witness).unwrap();
+                writer.witness(&[]).unwrap();
+            }
+            writer.finish(0).unwrap()
+        }
+        let bytes = tx(&[0xcc], None);
+        let legacy = Transaction::parse(&bytes[..]).unwrap().parsed_owned();
+        assert_eq!(
+            legacy
+                .replace_script_sig(Vec::new(), 1, &[0xdd, 0xee])
+                .unwrap(),
+            tx(&[0xdd, 0xee], None)
+        );
+        let segwit = legacy.replace_witness(Vec::new(), 1, &[&[9u8; 3]]).unwrap();
+        assert_eq!(segwit, tx(&[0xcc], Some(&[&[9u8; 3]])));
+
+        let segwit = Transaction::parse(&segwit[..]).unwrap().parsed_owned();
+        assert_eq!(
+            segwit
+                .replace_witness(Vec::new(), 1, &[&[], &[4u8]])
+                .unwrap(),
+            tx(&[0xcc], Some(&[&[], &[4u8]]))
+        );
+        assert_eq!(segwit.replace_witness(Vec::new(), 1, &[]).unwrap(), bytes);
+        assert_eq!(
+            segwit.replace_script_sig(Vec::new(), 1, &[]).unwrap(),
+            tx(&[], Some(&[&[9u8; 3]]))
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Conversion of the [`crate::bsl`] types from and to byte slices, independent of the storage.
+//!
+//! The types are stored as their serialization, so encoding is free and decoding doesn't copy.
+//! The `redb` feature implements `redb::RedbValue` and the `heed` feature the `heed` codecs on top
+//! of these traits, other embedded stores exposing the values as byte slices can store
+//! [`AsSlice::as_slice`] and read back with [`FromSlice::from_slice`].
+//!
+//! There is no `fjall` feature: fjall is out of scope for now. Its keyspaces take keys and
+//! values as `impl Into<fjall::Slice>`, which `&[u8]` implements, and return an owned
+//! `fjall::Slice` dereferencing to `[u8]`. So there is no codec trait to implement:
+//! `partition.insert(key.as_slice(), value.as_slice())` writes, and
+//! `TxOut::from_slice(&partition.get(key.as_slice())?.unwrap())` reads back, borrowing the
+//! returned slice.
+
+use crate::{Error, SResult};
+
+#[cfg(feature = "heed")]
+mod heed;
+
+/// Types that can be stored as a byte slice
+pub trait AsSlice {
+    /// Returns the bytes to store
+    fn as_slice(&self) -> &[u8];
+}
+
+/// Types that can be read back from the byte slice written by [`AsSlice::as_slice`]
+pub trait FromSlice<'a>: Sized {
+    /// Decodes the value from `slice`, validating it.
+    ///
+    /// The value must span the whole slice, otherwise [`Error::TrailingBytes`] is returned.
+    fn from_slice(slice: &'a [u8]) -> Result<Self, Error>;
+
+    /// Decodes the value from `slice`, which must come from [`AsSlice::as_slice`], for example
+    /// because it has been read from a database written only by this crate.
+    ///
+    /// Implementations may skip the validation done in [`FromSlice::from_slice`].
+    ///
+    /// # Panics
+    ///
+    /// If `slice` doesn't contain a valid value
+    fn from_trusted_slice(slice: &'a [u8]) -> Self {
+        Self::from_slice(slice).unwrap_or_else(|e| {
+            panic!(
+                "inserted data is not a {}: {}",
+                core::any::type_name::<Self>(),
+                e
+            )
+        })
+    }
+}
+
+/// Returns the parsed value if it consumed all the bytes of the slice
+pub(crate) fn exact<T: AsRef<[u8]>>(parsed: SResult<'_, T>) -> Result<T, Error> {
+    let parsed = parsed?;
+    match parsed.remaining().len() {
+        0 => Ok(parsed.parsed_owned()),
+        n => Err(Error::TrailingBytes(n as u32)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AsSlice, FromSlice};
+    use crate::{
+        bsl::{Block, BlockHeader, Transaction, TxOut, Txid, Witnesses},
+        test_common::{GENESIS_BLOCK, GENESIS_TX},
+        Error,
+    };
+
+    #[test]
+    fn roundtrip() {
+        let block = Block::from_slice(&GENESIS_BLOCK[..]).unwrap();
+        assert_eq!(block.as_slice(), &GENESIS_BLOCK[..]);
+        assert_eq!(Block::from_trusted_slice(block.as_slice()), block);
+        assert_eq!(block.total_transactions(), 1);
+
+        let tx = Transaction::from_slice(&GENESIS_TX[..]).unwrap();
+        assert_eq!(Transaction::from_trusted_slice(tx.as_slice()), tx);
+
+        let header = BlockHeader::from_slice(&GENESIS_BLOCK[..80]).unwrap();
+        assert_eq!(header.as_slice(), &GENESIS_BLOCK[..80]);
+
+        let tx_out =
+            TxOut::from_slice(&GENESIS_TX[GENESIS_TX.len() - 80..GENESIS_TX.len() - 4]).unwrap();
+        assert_eq!(tx_out.value(), 5_000_000_000);
+        assert_eq!(
+            TxOut::from_slice(&GENESIS_TX[GENESIS_TX.len() - 80..]),
+            Err(Error::TrailingBytes(4))
+        );
+
+        let witnesses = Witnesses::from_slice(&[0x01, 0x01, 0x00, 0x00][..]).unwrap();
+        assert!(!witnesses.all_empty());
+        assert_eq!(
+            Witnesses::from_trusted_slice(witnesses.as_slice()),
+            witnesses
+        );
+
+        let txid = Txid::from_slice(&[1u8; 32][..]).unwrap();
+        assert_eq!(txid.as_slice(), &[1u8; 32][..]);
+
+        assert_eq!(Txid::from_slice(&[1u8; 20][..]), Err(Error::Needed(12)));
+        assert_eq!(
+            Txid::from_slice(&[1u8; 33][..]),
+            Err(Error::TrailingBytes(1))
+        );
+        assert_eq!(
+            BlockHeader::from_slice(&GENESIS_BLOCK[..]),
+            Err(Error::TrailingBytes(GENESIS_BLOCK.len() as u32 - 80))
+        );
+        assert_eq!(TxOut::from_slice(&[0u8; 8][..]), Err(Error::Needed(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "inserted data is not a")]
+    fn trusted_invalid() {
+        BlockHeader::from_trusted_slice(&[0u8; 10][..]);
+    }
+}
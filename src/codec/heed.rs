@@ -0,0 +1,115 @@
+//! `heed` codecs, so that the [`crate::bsl`] types can be used as key and value of an LMDB
+//! `heed::Database`.
+//!
+//! Encoding borrows [`AsSlice::as_slice`], decoding validates with [`FromSlice::from_slice`].
+
+use super::{AsSlice, FromSlice};
+use crate::bsl::{
+    Block, BlockHash, BlockHeader, OutPoint, Script, ScriptHash, Transaction, TxIn, TxIns, TxOut,
+    TxOuts, Txid, Witness, Witnesses,
+};
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use heed::{BoxedError, BytesDecode, BytesEncode};
+
+macro_rules! heed_codec {
+    ($($name:ident),*) => {
+        $(
+            impl<'a, 'o> BytesEncode<'a> for $name<'o> {
+                type EItem = $name<'a>;
+
+                fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
+                    Ok(Cow::Borrowed(item.as_slice()))
+                }
+            }
+
+            impl<'a, 'o> BytesDecode<'a> for $name<'o> {
+                type DItem = $name<'a>;
+
+                fn bytes_decode(bytes: &'a [u8]) -> Result<Self::DItem, BoxedError> {
+                    $name::from_slice(bytes).map_err(|e| Box::new(e) as BoxedError)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! heed_hash_codec {
+    ($($name:ident),*) => {
+        $(
+            impl<'a> BytesEncode<'a> for $name {
+                type EItem = $name;
+
+                fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
+                    Ok(Cow::Borrowed(item.as_slice()))
+                }
+            }
+
+            impl<'a> BytesDecode<'a> for $name {
+                type DItem = $name;
+
+                fn bytes_decode(bytes: &'a [u8]) -> Result<Self::DItem, BoxedError> {
+                    $name::from_slice(bytes).map_err(|e| Box::new(e) as BoxedError)
+                }
+            }
+        )*
+    };
+}
+
+heed_codec!(
+    Block,
+    BlockHeader,
+    Transaction,
+    TxIn,
+    TxIns,
+    TxOut,
+    TxOuts,
+    OutPoint,
+    Script,
+    Witness,
+    Witnesses
+);
+
+heed_hash_codec!(Txid, BlockHash, ScriptHash);
+
+#[cfg(test)]
+mod test {
+    use crate::bsl::{OutPoint, Transaction, TxOut, Txid};
+    use crate::test_common::GENESIS_TX;
+    use crate::{AsSlice, Error, FromSlice};
+    use heed::{BytesDecode, Database, EnvOpenOptions};
+
+    #[test]
+    fn roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = unsafe {
+            EnvOpenOptions::new()
+                .map_size(10 * 1024 * 1024)
+                .max_dbs(2)
+                .open(dir.path())
+                .unwrap()
+        };
+        let mut wtxn = env.write_txn().unwrap();
+        let utxos: Database<OutPoint<'static>, TxOut<'static>> =
+            env.create_database(&mut wtxn, Some("utxos")).unwrap();
+        let txs: Database<Txid, Transaction<'static>> =
+            env.create_database(&mut wtxn, Some("txs")).unwrap();
+
+        let tx = Transaction::from_slice(&GENESIS_TX[..]).unwrap();
+        let txid = Txid::from_slice(&[1u8; 32][..]).unwrap();
+        let out_point = OutPoint::from_slice(&[0u8; 36][..]).unwrap();
+        let tx_out =
+            TxOut::from_slice(&GENESIS_TX[GENESIS_TX.len() - 80..GENESIS_TX.len() - 4]).unwrap();
+        utxos.put(&mut wtxn, &out_point, &tx_out).unwrap();
+        txs.put(&mut wtxn, &txid, &tx).unwrap();
+        wtxn.commit().unwrap();
+
+        let rtxn = env.read_txn().unwrap();
+        assert_eq!(utxos.get(&rtxn, &out_point).unwrap(), Some(tx_out));
+        let stored = txs.get(&rtxn, &txid).unwrap().unwrap();
+        assert_eq!(stored.as_slice(), &GENESIS_TX[..]);
+
+        let err = TxOut::bytes_decode(&GENESIS_TX[GENESIS_TX.len() - 80..]).unwrap_err();
+        assert_eq!(err.downcast_ref::<Error>(), Some(&Error::TrailingBytes(4)));
+    }
+}
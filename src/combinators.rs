@@ -0,0 +1,590 @@
+//! Generic [`Visitor`] adapters, allowing to run many visitors in a single pass over the data.
+//!
+//! Tuples (up to 5 elements), arrays `[V; N]` and `&mut V` (including `&mut dyn Visitor`) are
+//! visitors forwarding every callback to their members in order.
+//! Callbacks returning a [`ControlFlow`] are called on every member and break if any member breaks,
+//! returning the break value of the first member that broke.
+//!
+//! A subtree is skipped only if every member asks to skip it in [`Visitor::visit_subtree`],
+//! otherwise members asking to skip it still receive its callbacks.
+//!
+//! Visitors implementing [`Merge`] can be run on many parts of the data independently, like
+//! [`bsl::Block::par_visit`] does, and their states combined afterwards.
+
+use core::ops::ControlFlow;
+
+use crate::{bsl, Descend, Subtree, Visitor};
+
+impl<B, V: Visitor<B> + ?Sized> Visitor<B> for &mut V {
+    fn visit_block_header(&mut self, header: &bsl::BlockHeader) -> ControlFlow<B> {
+        (**self).visit_block_header(header)
+    }
+    fn visit_block_begin(&mut self, total_transactions: usize) {
+        (**self).visit_block_begin(total_transactions)
+    }
+    fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<B, Descend> {
+        (**self).visit_subtree(subtree)
+    }
+    fn visit_transaction(&mut self, tx: &bsl::Transaction) -> ControlFlow<B> {
+        (**self).visit_transaction(tx)
+    }
+    fn visit_tx_ins(&mut self, total_inputs: usize) {
+        (**self).visit_tx_ins(total_inputs)
+    }
+    fn visit_tx_in(&mut self, vin: usize, tx_in: &bsl::TxIn) -> ControlFlow<B> {
+        (**self).visit_tx_in(vin, tx_in)
+    }
+    fn visit_tx_outs(&mut self, total_outputs: usize) {
+        (**self).visit_tx_outs(total_outputs)
+    }
+    fn visit_tx_out(&mut self, vout: usize, tx_out: &bsl::TxOut) -> ControlFlow<B> {
+        (**self).visit_tx_out(vout, tx_out)
+    }
+    fn visit_witness(&mut self, vin: usize) -> ControlFlow<B> {
+        (**self).visit_witness(vin)
+    }
+    fn visit_witness_total_element(&mut self, witness_total: usize) {
+        (**self).visit_witness_total_element(witness_total)
+    }
+    fn visit_witness_element(&mut self, witness_i: usize, witness_element: &[u8]) {
+        (**self).visit_witness_element(witness_i, witness_element)
+    }
+    fn visit_witness_end(&mut self) {
+        (**self).visit_witness_end()
+    }
+}
+
+/// Merge the results of the members, breaking if any member breaks and keeping the first break.
+///
+/// Every member is called even if a previous one already broke, so that all of them see the same
+/// sequence of callbacks.
+#[doc(hidden)]
+pub fn merge<B>(a: ControlFlow<B>, b: ControlFlow<B>) -> ControlFlow<B> {
+    match a {
+        ControlFlow::Break(_) => a,
+        ControlFlow::Continue(_) => b,
+    }
+}
+
+/// Merge the decisions of the members about a subtree, which is skipped only if all of them skip it.
+#[doc(hidden)]
+pub fn merge_descend<B>(
+    a: ControlFlow<B, Descend>,
+    b: ControlFlow<B, Descend>,
+) -> ControlFlow<B, Descend> {
+    match (a, b) {
+        (ControlFlow::Break(value), _) | (_, ControlFlow::Break(value)) => {
+            ControlFlow::Break(value)
+        }
+        (ControlFlow::Continue(Descend::Skip), ControlFlow::Continue(Descend::Skip)) => {
+            ControlFlow::Continue(Descend::Skip)
+        }
+        _ => ControlFlow::Continue(Descend::Visit),
+    }
+}
+
+/// Call `f` on every visitor in the slice, breaking if any of them breaks.
+fn merge_all<B, V>(
+    visitors: &mut [V],
+    mut f: impl FnMut(&mut V) -> ControlFlow<B>,
+) -> ControlFlow<B> {
+    let mut result = ControlFlow::Continue(());
+    for v in visitors.iter_mut() {
+        result = merge(result, f(v));
+    }
+    result
+}
+
+macro_rules! impl_visitor_tuple {
+    ($($name:ident $idx:tt),+) => {
+        impl<BR, $($name: Visitor<BR>),+> Visitor<BR> for ($($name,)+) {
+            fn visit_block_header(&mut self, header: &bsl::BlockHeader) -> ControlFlow<BR> {
+                let mut result = ControlFlow::Continue(());
+                $(result = merge(result, self.$idx.visit_block_header(header));)+
+                result
+            }
+            fn visit_block_begin(&mut self, total_transactions: usize) {
+                $(self.$idx.visit_block_begin(total_transactions);)+
+            }
+            fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<BR, Descend> {
+                let mut result = ControlFlow::Continue(Descend::Skip);
+                $(result = merge_descend(result, self.$idx.visit_subtree(subtree));)+
+                result
+            }
+            fn visit_transaction(&mut self, tx: &bsl::Transaction) -> ControlFlow<BR> {
+                let mut result = ControlFlow::Continue(());
+                $(result = merge(result, self.$idx.visit_transaction(tx));)+
+                result
+            }
+            fn visit_tx_ins(&mut self, total_inputs: usize) {
+                $(self.$idx.visit_tx_ins(total_inputs);)+
+            }
+            fn visit_tx_in(&mut self, vin: usize, tx_in: &bsl::TxIn) -> ControlFlow<BR> {
+                let mut result = ControlFlow::Continue(());
+                $(result = merge(result, self.$idx.visit_tx_in(vin, tx_in));)+
+                result
+            }
+            fn visit_tx_outs(&mut self, total_outputs: usize) {
+                $(self.$idx.visit_tx_outs(total_outputs);)+
+            }
+            fn visit_tx_out(&mut self, vout: usize, tx_out: &bsl::TxOut) -> ControlFlow<BR> {
+                let mut result = ControlFlow::Continue(());
+                $(result = merge(result, self.$idx.visit_tx_out(vout, tx_out));)+
+                result
+            }
+            fn visit_witness(&mut self, vin: usize) -> ControlFlow<BR> {
+                let mut result = ControlFlow::Continue(());
+                $(result = merge(result, self.$idx.visit_witness(vin));)+
+                result
+            }
+            fn visit_witness_total_element(&mut self, witness_total: usize) {
+                $(self.$idx.visit_witness_total_element(witness_total);)+
+            }
+            fn visit_witness_element(&mut self, witness_i: usize, witness_element: &[u8]) {
+                $(self.$idx.visit_witness_element(witness_i, witness_element);)+
+            }
+            fn visit_witness_end(&mut self) {
+                $(self.$idx.visit_witness_end();)+
+            }
+        }
+    };
+}
+
+impl_visitor_tuple!(A 0, B 1);
+impl_visitor_tuple!(A 0, B 1, C 2);
+impl_visitor_tuple!(A 0, B 1, C 2, D 3);
+impl_visitor_tuple!(A 0, B 1, C 2, D 3, E 4);
+
+/// A visitor whose state can be combined with the state of another visitor of the same type.
+///
+/// Used to visit disjoint parts of the data with different clones of a visitor, for example by
+/// [`bsl::Block::par_visit`].
+pub trait Merge {
+    /// Combine into `self` the state of `other`, which visited the data following the data
+    /// visited by `self`.
+    fn merge(&mut self, other: Self);
+}
+
+macro_rules! impl_merge_tuple {
+    ($($name:ident $idx:tt),+) => {
+        impl<$($name: Merge),+> Merge for ($($name,)+) {
+            fn merge(&mut self, other: Self) {
+                $(self.$idx.merge(other.$idx);)+
+            }
+        }
+    };
+}
+
+impl_merge_tuple!(A 0, B 1);
+impl_merge_tuple!(A 0, B 1, C 2);
+impl_merge_tuple!(A 0, B 1, C 2, D 3);
+impl_merge_tuple!(A 0, B 1, C 2, D 3, E 4);
+
+impl<V: Merge, const N: usize> Merge for [V; N] {
+    fn merge(&mut self, other: Self) {
+        for (v, o) in self.iter_mut().zip(other) {
+            v.merge(o);
+        }
+    }
+}
+
+impl<B, V: Visitor<B>, const N: usize> Visitor<B> for [V; N] {
+    fn visit_block_header(&mut self, header: &bsl::BlockHeader) -> ControlFlow<B> {
+        merge_all(self, |v| v.visit_block_header(header))
+    }
+    fn visit_block_begin(&mut self, total_transactions: usize) {
+        self.iter_mut()
+            .for_each(|v| v.visit_block_begin(total_transactions))
+    }
+    fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<B, Descend> {
+        let mut result = ControlFlow::Continue(Descend::Skip);
+        for v in self.iter_mut() {
+            result = merge_descend(result, v.visit_subtree(subtree));
+        }
+        result
+    }
+    fn visit_transaction(&mut self, tx: &bsl::Transaction) -> ControlFlow<B> {
+        merge_all(self, |v| v.visit_transaction(tx))
+    }
+    fn visit_tx_ins(&mut self, total_inputs: usize) {
+        self.iter_mut().for_each(|v| v.visit_tx_ins(total_inputs))
+    }
+    fn visit_tx_in(&mut self, vin: usize, tx_in: &bsl::TxIn) -> ControlFlow<B> {
+        merge_all(self, |v| v.visit_tx_in(vin, tx_in))
+    }
+    fn visit_tx_outs(&mut self, total_outputs: usize) {
+        self.iter_mut().for_each(|v| v.visit_tx_outs(total_outputs))
+    }
+    fn visit_tx_out(&mut self, vout: usize, tx_out: &bsl::TxOut) -> ControlFlow<B> {
+        merge_all(self, |v| v.visit_tx_out(vout, tx_out))
+    }
+    fn visit_witness(&mut self, vin: usize) -> ControlFlow<B> {
+        merge_all(self, |v| v.visit_witness(vin))
+    }
+    fn visit_witness_total_element(&mut self, witness_total: usize) {
+        self.iter_mut()
+            .for_each(|v| v.visit_witness_total_element(witness_total))
+    }
+    fn visit_witness_element(&mut self, witness_i: usize, witness_element: &[u8]) {
+        self.iter_mut()
+            .for_each(|v| v.visit_witness_element(witness_i, witness_element))
+    }
+    fn visit_witness_end(&mut self) {
+        self.iter_mut().for_each(|v| v.visit_witness_end())
+    }
+}
+
+/// Run the inner visitor only on the transactions matching a predicate.
+///
+/// Since [`Visitor::visit_transaction`] is called once the transaction has been fully parsed, the
+/// inner visitor is called by walking again the inputs, outputs and witnesses of the matching
+/// transaction, without validating them again and honoring the inner
+/// [`Visitor::visit_subtree`]. The inner visitor never sees the inputs, outputs or witnesses of
+/// non-matching transactions, and for segwit transactions it doesn't receive the
+/// `visit_tx_ins(0)` that parsing emits for the segwit marker.
+///
+/// Block level callbacks ([`Visitor::visit_block_header`] and [`Visitor::visit_block_begin`]) are
+/// always forwarded to the inner visitor. The first pass skips every transaction subtree, since the
+/// predicate sees only the whole transaction.
+pub struct TxFilter<P, V> {
+    predicate: P,
+    inner: V,
+}
+
+impl<P, V> TxFilter<P, V>
+where
+    P: FnMut(&bsl::Transaction) -> bool,
+{
+    /// Creates a filter running `inner` only on transactions for which `predicate` returns `true`
+    pub fn new(predicate: P, inner: V) -> Self {
+        Self { predicate, inner }
+    }
+
+    /// Returns a reference to the inner visitor
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// Returns the inner visitor
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+}
+
+impl<B, P, V> Visitor<B> for TxFilter<P, V>
+where
+    P: FnMut(&bsl::Transaction) -> bool,
+    V: Visitor<B>,
+{
+    fn visit_block_header(&mut self, header: &bsl::BlockHeader) -> ControlFlow<B> {
+        self.inner.visit_block_header(header)
+    }
+    fn visit_block_begin(&mut self, total_transactions: usize) {
+        self.inner.visit_block_begin(total_transactions)
+    }
+    fn visit_subtree(&mut self, _subtree: Subtree) -> ControlFlow<B, Descend> {
+        ControlFlow::Continue(Descend::Skip)
+    }
+    fn visit_transaction(&mut self, tx: &bsl::Transaction) -> ControlFlow<B> {
+        if !(self.predicate)(tx) {
+            return ControlFlow::Continue(());
+        }
+        tx.visit_subtrees(&mut self.inner)?;
+        self.inner.visit_transaction(tx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::ops::ControlFlow;
+
+    use bitcoin_test_data::blocks::mainnet_702861;
+
+    use crate::{bsl, Descend, Error, Subtree, Visit, Visitor};
+
+    use super::TxFilter;
+
+    #[derive(Default, Clone, Copy)]
+    struct Counter {
+        txs: usize,
+        inputs: usize,
+        outputs: usize,
+        witness_elements: usize,
+    }
+    impl Visitor for Counter {
+        fn visit_transaction(&mut self, _tx: &bsl::Transaction) -> ControlFlow<()> {
+            self.txs += 1;
+            ControlFlow::Continue(())
+        }
+        fn visit_tx_in(&mut self, _vin: usize, _tx_in: &bsl::TxIn) -> ControlFlow<()> {
+            self.inputs += 1;
+            ControlFlow::Continue(())
+        }
+        fn visit_tx_out(&mut self, _vout: usize, _tx_out: &bsl::TxOut) -> ControlFlow<()> {
+            self.outputs += 1;
+            ControlFlow::Continue(())
+        }
+        fn visit_witness_element(&mut self, _witness_i: usize, _witness_element: &[u8]) {
+            self.witness_elements += 1;
+        }
+    }
+
+    struct Sum(u64);
+    impl Visitor for Sum {
+        fn visit_tx_out(&mut self, _vout: usize, tx_out: &bsl::TxOut) -> ControlFlow<()> {
+            self.0 += tx_out.value();
+            ControlFlow::Continue(())
+        }
+    }
+
+    struct BreakAt(usize, usize);
+    impl Visitor for BreakAt {
+        fn visit_transaction(&mut self, _tx: &bsl::Transaction) -> ControlFlow<()> {
+            self.1 += 1;
+            if self.1 == self.0 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    #[test]
+    fn tuple() {
+        let mut counter = Counter::default();
+        bsl::Block::visit(mainnet_702861(), &mut counter).unwrap();
+
+        let mut visitor = (Counter::default(), Sum(0));
+        bsl::Block::visit(mainnet_702861(), &mut visitor).unwrap();
+        assert_eq!(visitor.0.txs, counter.txs);
+        assert_eq!(visitor.0.inputs, counter.inputs);
+        assert_eq!(visitor.0.outputs, counter.outputs);
+        assert_eq!(visitor.0.witness_elements, counter.witness_elements);
+        assert_eq!(visitor.1 .0, 2_883_682_728_990);
+
+        let mut visitor = (Sum(0), (Counter::default(), Sum(0)), Counter::default());
+        bsl::Block::visit(mainnet_702861(), &mut visitor).unwrap();
+        assert_eq!(visitor.0 .0, visitor.1 .1 .0);
+        assert_eq!(visitor.1 .0.outputs, visitor.2.outputs);
+    }
+
+    #[test]
+    fn array_and_dyn() {
+        let mut visitor = [Counter::default(); 3];
+        bsl::Block::visit(mainnet_702861(), &mut visitor).unwrap();
+        assert!(visitor[0].txs > 0);
+        assert_eq!(visitor[0].txs, visitor[2].txs);
+
+        let mut sum = Sum(0);
+        let mut counter = Counter::default();
+        {
+            let mut visitors: [&mut dyn Visitor; 2] = [&mut sum, &mut counter];
+            bsl::Block::visit(mainnet_702861(), &mut visitors).unwrap();
+        }
+        assert_eq!(sum.0, 2_883_682_728_990);
+        assert_eq!(counter.txs, visitor[0].txs);
+    }
+
+    struct OutputsOnly(usize);
+    impl Visitor for OutputsOnly {
+        fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<(), Descend> {
+            match subtree {
+                Subtree::TxOuts => ControlFlow::Continue(Descend::Visit),
+                _ => ControlFlow::Continue(Descend::Skip),
+            }
+        }
+        fn visit_tx_out(&mut self, _vout: usize, _tx_out: &bsl::TxOut) -> ControlFlow<()> {
+            self.0 += 1;
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn skip_subtree() {
+        let mut counter = Counter::default();
+        bsl::Block::visit(mainnet_702861(), &mut counter).unwrap();
+
+        let mut visitor = (OutputsOnly(0), Counter::default());
+        bsl::Block::visit(mainnet_702861(), &mut visitor).unwrap();
+        assert_eq!(visitor.0 .0, counter.outputs);
+        assert_eq!(visitor.1.inputs, counter.inputs);
+        assert_eq!(visitor.1.witness_elements, counter.witness_elements);
+
+        let mut visitor = [OutputsOnly(0), OutputsOnly(0)];
+        assert_eq!(
+            visitor.visit_subtree(Subtree::TxIns),
+            ControlFlow::Continue(Descend::Skip)
+        );
+        assert_eq!(
+            visitor.visit_subtree(Subtree::TxOuts),
+            ControlFlow::Continue(Descend::Visit)
+        );
+        bsl::Block::visit(mainnet_702861(), &mut visitor).unwrap();
+        assert_eq!(visitor[1].0, counter.outputs);
+    }
+
+    #[test]
+    fn breaking() {
+        let mut visitor = (Counter::default(), BreakAt(3, 0));
+        let result = bsl::Block::visit(mainnet_702861(), &mut visitor);
+        assert_eq!(result, Err(Error::VisitBreak));
+        assert_eq!(visitor.0.txs, 3);
+    }
+
+    #[test]
+    fn filter() {
+        let mut all = Counter::default();
+        bsl::Block::visit(mainnet_702861(), &mut all).unwrap();
+
+        let mut visitor = (
+            TxFilter::new(
+                |tx: &bsl::Transaction| tx.version() == 1,
+                Counter::default(),
+            ),
+            TxFilter::new(
+                |tx: &bsl::Transaction| tx.version() != 1,
+                Counter::default(),
+            ),
+        );
+        bsl::Block::visit(mainnet_702861(), &mut visitor).unwrap();
+        let (v1, others) = (visitor.0.into_inner(), visitor.1.into_inner());
+        assert!(v1.txs > 0);
+        assert!(others.txs > 0);
+        assert_eq!(v1.txs + others.txs, all.txs);
+        assert_eq!(v1.inputs + others.inputs, all.inputs);
+        assert_eq!(v1.outputs + others.outputs, all.outputs);
+        assert_eq!(
+            v1.witness_elements + others.witness_elements,
+            all.witness_elements
+        );
+
+        let mut visitor = TxFilter::new(|_: &bsl::Transaction| true, BreakAt(2, 0));
+        let result = bsl::Block::visit(mainnet_702861(), &mut visitor);
+        assert_eq!(result, Err(Error::VisitBreak));
+        assert_eq!(visitor.inner().1, 2);
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod derive_test {
+    use core::ops::ControlFlow;
+
+    use bitcoin_test_data::blocks::mainnet_702861;
+
+    use crate::{bsl, Descend, Subtree, Visit, Visitor};
+
+    #[derive(Default)]
+    struct Sum {
+        value: u64,
+        outputs: usize,
+        inputs: usize,
+    }
+
+    #[crate::visitor]
+    impl Sum {
+        #[visit(tx_out)]
+        fn on_out(&mut self, _vout: usize, out: &bsl::TxOut) {
+            self.value += out.value();
+            self.outputs += 1;
+        }
+
+        fn not_a_callback(&self) -> u64 {
+            self.value
+        }
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        txs: usize,
+        inputs: usize,
+    }
+
+    #[crate::visitor]
+    impl Counter {
+        #[visit(transaction)]
+        fn on_tx(&mut self, _tx: &bsl::Transaction) -> ControlFlow<()> {
+            self.txs += 1;
+            ControlFlow::Continue(())
+        }
+        #[visit(tx_in)]
+        fn on_in(&mut self, _vin: usize, _tx_in: &bsl::TxIn) {
+            self.inputs += 1;
+        }
+    }
+
+    struct FindValue(u64);
+
+    #[crate::visitor(break_type = usize)]
+    impl FindValue {
+        #[visit(tx_out)]
+        fn on_out(&mut self, vout: usize, out: &bsl::TxOut) -> ControlFlow<usize> {
+            if out.value() == self.0 {
+                ControlFlow::Break(vout)
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    #[derive(crate::Visitor)]
+    struct Pipeline<F: FnMut(&bsl::Transaction)> {
+        sum: Sum,
+        counter: Counter,
+        #[visit(transaction)]
+        on_tx: F,
+        #[visit(skip)]
+        _label: &'static str,
+    }
+
+    #[derive(crate::Visitor)]
+    struct Tuple(Sum, Sum);
+
+    #[test]
+    fn attribute() {
+        let mut sum = Sum::default();
+        bsl::Block::visit(mainnet_702861(), &mut sum).unwrap();
+        assert_eq!(sum.not_a_callback(), 2_883_682_728_990);
+        assert_eq!(sum.inputs, 0);
+
+        assert_eq!(
+            sum.visit_subtree(Subtree::TxIns),
+            ControlFlow::Continue(Descend::Skip)
+        );
+        assert_eq!(
+            sum.visit_subtree(Subtree::TxOuts),
+            ControlFlow::Continue(Descend::Visit)
+        );
+
+        let mut counter = Counter::default();
+        bsl::Block::visit(mainnet_702861(), &mut counter).unwrap();
+        assert!(counter.inputs > counter.txs);
+
+        let found = bsl::Block::visit_break(mainnet_702861(), &mut FindValue(629_948_405));
+        assert_eq!(found.unwrap_err().into_break(), Some(0)); // first output of the coinbase
+        assert!(bsl::Block::visit_break(mainnet_702861(), &mut FindValue(sum.value)).is_ok());
+    }
+
+    #[test]
+    fn derive() {
+        let mut total_txs = 0usize;
+        let mut pipeline = Pipeline {
+            sum: Sum::default(),
+            counter: Counter::default(),
+            on_tx: |_tx: &bsl::Transaction| total_txs += 1,
+            _label: "pipeline",
+        };
+        bsl::Block::visit(mainnet_702861(), &mut pipeline).unwrap();
+        assert_eq!(pipeline.sum.value, 2_883_682_728_990);
+        let (counter, outputs) = (pipeline.counter.txs, pipeline.sum.outputs);
+        assert!(outputs > counter);
+        assert_eq!(total_txs, counter);
+
+        let mut tuple = Tuple(Sum::default(), Sum::default());
+        assert_eq!(
+            tuple.visit_subtree(Subtree::Witnesses),
+            ControlFlow::Continue(Descend::Skip)
+        );
+        bsl::Block::visit(mainnet_702861(), &mut tuple).unwrap();
+        assert_eq!(tuple.0.value, tuple.1.value);
+    }
+}
@@ -0,0 +1,805 @@
+//! Consensus constants and context-free validity checks, mirroring Bitcoin Core.
+//!
+//! Parsing only ensures the data is syntactically valid, checks like
+//! [`crate::bsl::Transaction::check_sanity`] reject data that can never be valid.
+
+use core::{fmt, ops::ControlFlow};
+
+use crate::{
+    bsl::{parse_len, Transaction, TxIn, TxOut},
+    Descend, Subtree, Visit, Visitor,
+};
+
+/// The amount of satoshi in one bitcoin
+pub const COIN: u64 = 100_000_000;
+
+/// The maximum amount of satoshi that can ever exist, any output or sum of outputs must not exceed
+/// this value
+pub const MAX_MONEY: u64 = 21_000_000 * COIN;
+
+/// The maximum weight of a block, as defined by BIP 141
+pub const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+
+/// The factor between the weight and the size of non-witness data
+pub const WITNESS_SCALE_FACTOR: u64 = 4;
+
+/// The reasons [`Transaction::check_sanity`] rejects a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxSanityError {
+    /// The transaction has no inputs
+    NoInputs,
+
+    /// The transaction has no outputs
+    NoOutputs,
+
+    /// The transaction without witnesses would be heavier than [`MAX_BLOCK_WEIGHT`]
+    Oversize,
+
+    /// The output with the given index has a value greater than [`MAX_MONEY`]
+    OutputValueTooLarge(usize),
+
+    /// The sum of the outputs values is greater than [`MAX_MONEY`]
+    TotalOutputValueTooLarge,
+
+    /// The input with the given index spends the same outpoint of a previous input
+    DuplicateInput(usize),
+
+    /// The coinbase script sig length, which is not between 2 and 100 bytes
+    CoinbaseScriptSigLength(usize),
+
+    /// The input with the given index of a non-coinbase transaction spends a null outpoint
+    NullPrevout(usize),
+}
+
+impl fmt::Display for TxSanityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxSanityError::NoInputs => write!(f, "transaction has no inputs"),
+            TxSanityError::NoOutputs => write!(f, "transaction has no outputs"),
+            TxSanityError::Oversize => write!(f, "transaction exceeds the block weight limit"),
+            TxSanityError::OutputValueTooLarge(vout) => {
+                write!(f, "output {} value exceeds the max money", vout)
+            }
+            TxSanityError::TotalOutputValueTooLarge => {
+                write!(f, "total output value exceeds the max money")
+            }
+            TxSanityError::DuplicateInput(vin) => {
+                write!(
+                    f,
+                    "input {} spends the same outpoint of a previous input",
+                    vin
+                )
+            }
+            TxSanityError::CoinbaseScriptSigLength(len) => {
+                write!(f, "coinbase script sig length {} not in 2..=100", len)
+            }
+            TxSanityError::NullPrevout(vin) => write!(f, "input {} spends a null outpoint", vin),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TxSanityError {}
+
+/// Collects what's needed for the sanity checks in a single pass over the transaction
+struct Sanity<'s> {
+    scratch: &'s mut [([u8; 36], u32)],
+    inputs: usize,
+    outputs: usize,
+    total_value: u64,
+    first_script_sig_len: usize,
+    first_prevout_null: bool,
+    null_prevout: Option<usize>,
+    error: Option<TxSanityError>,
+}
+
+impl Visitor for Sanity<'_> {
+    fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<(), Descend> {
+        match subtree {
+            Subtree::Witnesses => ControlFlow::Continue(Descend::Skip),
+            _ => ControlFlow::Continue(Descend::Visit),
+        }
+    }
+    fn visit_tx_in(&mut self, vin: usize, tx_in: &TxIn) -> ControlFlow<()> {
+        self.inputs += 1;
+        let null = tx_in.prevout().is_null();
+        if vin == 0 {
+            self.first_script_sig_len = tx_in.script_sig().len();
+            self.first_prevout_null = null;
+        }
+        if null && self.null_prevout.is_none() {
+            self.null_prevout = Some(vin);
+        }
+        if let Some(slot) = self.scratch.get_mut(vin) {
+            slot.0.copy_from_slice(tx_in.prevout().as_ref());
+            slot.1 = vin as u32;
+        }
+        ControlFlow::Continue(())
+    }
+    fn visit_tx_out(&mut self, vout: usize, tx_out: &TxOut) -> ControlFlow<()> {
+        self.outputs += 1;
+        if tx_out.value() > MAX_MONEY {
+            self.error = Some(TxSanityError::OutputValueTooLarge(vout));
+            return ControlFlow::Break(());
+        }
+        self.total_value += tx_out.value();
+        if self.total_value > MAX_MONEY {
+            self.error = Some(TxSanityError::TotalOutputValueTooLarge);
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Iterates over the outpoints spent by an already validated transaction, without parsing the
+/// inputs
+#[derive(Clone)]
+struct Outpoints<'a> {
+    remaining: &'a [u8],
+    left: usize,
+}
+
+impl<'a> Outpoints<'a> {
+    fn new(tx: &'a Transaction) -> Self {
+        let start = if tx.has_witness() { 6 } else { 4 };
+        let tx_ins = &tx.as_ref()[start..];
+        let len = parse_len(tx_ins).expect("granted from parsing");
+        Outpoints {
+            remaining: &tx_ins[len.consumed()..],
+            left: len.n() as usize,
+        }
+    }
+}
+
+impl<'a> Iterator for Outpoints<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.left == 0 {
+            return None;
+        }
+        self.left -= 1;
+        let (outpoint, rest) = self.remaining.split_at(36);
+        let script_sig = parse_len(rest).expect("granted from parsing");
+        self.remaining = &rest[script_sig.slice_len() + 4..];
+        Some(outpoint)
+    }
+}
+
+/// Returns the index of the first input spending the same outpoint of a previous input.
+///
+/// The first `scratch.len()` outpoints with their index are already in `scratch`. Inputs are
+/// compared against chunks of `scratch.len()` sorted outpoints, so the cost is O(n log n) if every
+/// input fits in `scratch` and O(n² / scratch.len()) otherwise.
+fn find_duplicate(
+    tx: &Transaction,
+    scratch: &mut [([u8; 36], u32)],
+    inputs: usize,
+) -> Option<usize> {
+    if scratch.is_empty() {
+        return Outpoints::new(tx)
+            .enumerate()
+            .find(|(vin, outpoint)| Outpoints::new(tx).take(*vin).any(|prev| prev == *outpoint))
+            .map(|(vin, _)| vin);
+    }
+
+    let mut first = None;
+    let mut start = 0;
+    // a duplicate of an input in this chunk comes after `start`, it can't precede `first`
+    while start < inputs && !matches!(first, Some(first) if start >= first) {
+        let chunk_len = (inputs - start).min(scratch.len());
+        let chunk = &mut scratch[..chunk_len];
+        if start > 0 {
+            let outpoints = Outpoints::new(tx).enumerate().skip(start);
+            for (slot, (vin, outpoint)) in chunk.iter_mut().zip(outpoints) {
+                slot.0.copy_from_slice(outpoint);
+                slot.1 = vin as u32;
+            }
+        }
+        chunk.sort_unstable();
+
+        if chunk.len() == inputs {
+            // equal outpoints are adjacent and sorted by index
+            return chunk
+                .windows(2)
+                .filter(|w| w[0].0 == w[1].0)
+                .map(|w| w[1].1 as usize)
+                .min();
+        }
+        for (vin, outpoint) in Outpoints::new(tx).enumerate().skip(start + 1) {
+            if matches!(first, Some(first) if vin >= first) {
+                break;
+            }
+            // the first entry with this outpoint has the lowest index
+            let i = chunk.partition_point(|(prev, _)| prev[..] < *outpoint);
+            if matches!(chunk.get(i), Some((prev, prev_vin)) if prev[..] == *outpoint && (*prev_vin as usize) < vin)
+            {
+                first = Some(vin);
+                break;
+            }
+        }
+        start += chunk.len();
+    }
+    first
+}
+
+pub(crate) fn check_sanity(
+    tx: &Transaction,
+    scratch: &mut [([u8; 36], u32)],
+) -> Result<(), TxSanityError> {
+    let mut sanity = Sanity {
+        scratch,
+        inputs: 0,
+        outputs: 0,
+        total_value: 0,
+        first_script_sig_len: 0,
+        first_prevout_null: false,
+        null_prevout: None,
+        error: None,
+    };
+    let _ = Transaction::visit(tx.as_ref(), &mut sanity);
+
+    if sanity.inputs == 0 {
+        return Err(TxSanityError::NoInputs);
+    }
+    if sanity.outputs == 0 {
+        return Err(TxSanityError::NoOutputs);
+    }
+    if tx.base_size() as u64 * WITNESS_SCALE_FACTOR > MAX_BLOCK_WEIGHT {
+        return Err(TxSanityError::Oversize);
+    }
+    if let Some(error) = sanity.error {
+        return Err(error);
+    }
+
+    if let Some(vin) = find_duplicate(tx, sanity.scratch, sanity.inputs) {
+        return Err(TxSanityError::DuplicateInput(vin));
+    }
+
+    if sanity.inputs == 1 && sanity.first_prevout_null {
+        let len = sanity.first_script_sig_len;
+        if !(2..=100).contains(&len) {
+            return Err(TxSanityError::CoinbaseScriptSigLength(len));
+        }
+    } else if let Some(vin) = sanity.null_prevout {
+        return Err(TxSanityError::NullPrevout(vin));
+    }
+    Ok(())
+}
+
+/// The reasons [`Block::check`](crate::bsl::Block::check) rejects a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError {
+    /// The merkle root in the header doesn't match the transactions
+    MerkleRootMismatch,
+
+    /// The transactions contain duplicates giving the same merkle root of a valid block
+    MutatedMerkleTree,
+
+    /// The block contains no transactions
+    NoTransactions,
+
+    /// The block weight, which is greater than [`MAX_BLOCK_WEIGHT`]
+    Weight(u64),
+
+    /// The first transaction is not a coinbase
+    FirstNotCoinbase,
+
+    /// The transaction with the given index is a coinbase, but it's not the first
+    MultipleCoinbase(usize),
+
+    /// The coinbase script sig doesn't start with the expected height as required by BIP 34
+    Bip34Height,
+
+    /// The coinbase commits to witnesses but its witness isn't a single 32 bytes element
+    WitnessReservedValue,
+
+    /// The witness commitment in the coinbase doesn't match the witnesses
+    WitnessCommitmentMismatch,
+
+    /// Transactions with witnesses but no witness commitment in the coinbase
+    UnexpectedWitness,
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::MerkleRootMismatch => write!(f, "merkle root mismatch"),
+            BlockError::MutatedMerkleTree => write!(f, "duplicate transactions in merkle tree"),
+            BlockError::NoTransactions => write!(f, "block has no transactions"),
+            BlockError::Weight(weight) => write!(f, "block weight {} exceeds the limit", weight),
+            BlockError::FirstNotCoinbase => write!(f, "first transaction is not a coinbase"),
+            BlockError::MultipleCoinbase(i) => write!(f, "transaction {} is a coinbase", i),
+            BlockError::Bip34Height => write!(f, "coinbase doesn't start with the block height"),
+            BlockError::WitnessReservedValue => write!(f, "invalid witness reserved value"),
+            BlockError::WitnessCommitmentMismatch => write!(f, "witness commitment mismatch"),
+            BlockError::UnexpectedWitness => write!(f, "unexpected witness"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BlockError {}
+
+/// The prefix of the script pubkey of the coinbase output containing the witness commitment
+pub const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// Returns the bytes that must prefix the coinbase script sig of a block at the given `height`,
+/// as required by BIP 34, and how many of them are used.
+pub fn bip34_prefix(height: u32) -> ([u8; 6], usize) {
+    let mut prefix = [0u8; 6];
+    match height {
+        0 => (prefix, 1), // OP_0
+        1..=16 => {
+            prefix[0] = 0x50 + height as u8; // OP_1..OP_16
+            (prefix, 1)
+        }
+        _ => {
+            let bytes = height.to_le_bytes();
+            let mut len = 4 - height.leading_zeros() as usize / 8;
+            prefix[1..1 + len].copy_from_slice(&bytes[..len]);
+            if bytes[len - 1] & 0x80 != 0 {
+                len += 1; // the script number would be negative without an extra zero byte
+            }
+            prefix[0] = len as u8;
+            (prefix, len + 1)
+        }
+    }
+}
+
+/// Computes the merkle root of a stream of hashes without allocating, keeping one pending hash
+/// per level of the tree. Detects the mutation of CVE-2012-2459, like Bitcoin Core.
+#[cfg(feature = "bitcoin_hashes")]
+#[derive(Default)]
+pub(crate) struct MerkleRoot {
+    inner: [[u8; 32]; 32],
+    count: u32,
+    mutated: bool,
+}
+
+#[cfg(feature = "bitcoin_hashes")]
+impl MerkleRoot {
+    fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+        use crate::bitcoin_hashes::{sha256d, Hash, HashEngine};
+        let mut engine = sha256d::Hash::engine();
+        engine.input(a);
+        engine.input(b);
+        sha256d::Hash::from_engine(engine).to_byte_array()
+    }
+
+    pub(crate) fn push(&mut self, mut hash: [u8; 32]) {
+        self.count += 1;
+        let mut level = 0;
+        while self.count & (1 << level) == 0 {
+            self.mutated |= self.inner[level] == hash;
+            hash = Self::hash_pair(&self.inner[level], &hash);
+            level += 1;
+        }
+        self.inner[level] = hash;
+    }
+
+    /// Returns the merkle root and whether the tree is mutated
+    pub(crate) fn finish(mut self) -> ([u8; 32], bool) {
+        if self.count == 0 {
+            return ([0u8; 32], false);
+        }
+        let mut level = self.count.trailing_zeros() as usize;
+        let mut hash = self.inner[level];
+        while self.count != 1 << level {
+            // an odd number of hashes at this level, the last one is paired with itself
+            hash = Self::hash_pair(&hash, &hash);
+            self.count += 1 << level;
+            level += 1;
+            while self.count & (1 << level) == 0 {
+                hash = Self::hash_pair(&self.inner[level], &hash);
+                level += 1;
+            }
+        }
+        (hash, self.mutated)
+    }
+}
+
+/// The coinbase witness commitment and witness reserved value
+#[cfg(feature = "bitcoin_hashes")]
+#[derive(Default)]
+struct Coinbase {
+    commitment: Option<[u8; 32]>,
+    witness_elements: usize,
+    reserved_value: Option<[u8; 32]>,
+}
+
+#[cfg(feature = "bitcoin_hashes")]
+impl Visitor for Coinbase {
+    fn visit_tx_out(&mut self, _vout: usize, tx_out: &TxOut) -> ControlFlow<()> {
+        let script = tx_out.script_pubkey();
+        if script.len() >= 38 && script[..6] == WITNESS_COMMITMENT_HEADER {
+            // the last matching output is the commitment
+            self.commitment = Some(script[6..38].try_into().expect("32 bytes"));
+        }
+        ControlFlow::Continue(())
+    }
+    fn visit_witness_total_element(&mut self, witness_total: usize) {
+        self.witness_elements = witness_total;
+    }
+    fn visit_witness_element(&mut self, _witness_i: usize, witness_element: &[u8]) {
+        self.reserved_value = witness_element.try_into().ok();
+    }
+}
+
+/// Collects what's needed for the block checks in a single pass over the block
+#[cfg(feature = "bitcoin_hashes")]
+#[derive(Default)]
+struct BlockChecks {
+    merkle: MerkleRoot,
+    witness_merkle: MerkleRoot,
+    weight: u64,
+    txs: usize,
+    any_witness: bool,
+    first_coinbase_script_sig: Option<([u8; 6], usize)>,
+    coinbase: Coinbase,
+    error: Option<BlockError>,
+}
+
+#[cfg(feature = "bitcoin_hashes")]
+impl Visitor for BlockChecks {
+    fn visit_block_begin(&mut self, total_transactions: usize) {
+        self.weight = (80 + crate::bsl::len_size(total_transactions as u64)) as u64 * 4;
+    }
+    fn visit_subtree(&mut self, _subtree: Subtree) -> ControlFlow<(), Descend> {
+        ControlFlow::Continue(Descend::Skip)
+    }
+    fn visit_transaction(&mut self, tx: &Transaction) -> ControlFlow<()> {
+        use crate::bitcoin_hashes::Hash;
+        let index = self.txs;
+        self.txs += 1;
+        self.merkle.push(tx.txid().to_byte_array());
+        self.weight += tx.weight();
+        self.any_witness |= tx.has_witness();
+
+        // errors are recorded and the visit continues, the merkle root is checked first
+        let (is_coinbase, script_sig) = coinbase_script_sig(tx);
+        if index == 0 {
+            if is_coinbase {
+                self.first_coinbase_script_sig = Some(script_sig);
+                let _ = Transaction::visit(tx.as_ref(), &mut self.coinbase);
+            } else {
+                self.error = Some(BlockError::FirstNotCoinbase);
+            }
+            self.witness_merkle.push([0u8; 32]);
+        } else {
+            if is_coinbase && self.error.is_none() {
+                self.error = Some(BlockError::MultipleCoinbase(index));
+            }
+            self.witness_merkle.push(tx.wtxid().to_byte_array());
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Returns whether the transaction is a coinbase and the first 6 bytes of its script sig, with
+/// their length
+#[cfg(feature = "bitcoin_hashes")]
+fn coinbase_script_sig(tx: &Transaction) -> (bool, ([u8; 6], usize)) {
+    let mut prefix = [0u8; 6];
+    match tx.coinbase_tx_in() {
+        Some(tx_in) => {
+            let script_sig = tx_in.script_sig();
+            let len = script_sig.len().min(6);
+            prefix[..len].copy_from_slice(&script_sig[..len]);
+            (true, (prefix, len))
+        }
+        None => (false, (prefix, 0)),
+    }
+}
+
+#[cfg(feature = "bitcoin_hashes")]
+pub(crate) fn check_block(
+    block: &crate::bsl::Block,
+    height: Option<u32>,
+) -> Result<(), BlockError> {
+    use crate::bitcoin_hashes::{sha256d, Hash, HashEngine};
+
+    let mut checks = BlockChecks::default();
+    let _ = crate::bsl::Block::visit(block.as_ref(), &mut checks);
+
+    let (root, mutated) = core::mem::take(&mut checks.merkle).finish();
+    if root[..] != block.header().merkle_root()[..] {
+        return Err(BlockError::MerkleRootMismatch);
+    }
+    if mutated {
+        return Err(BlockError::MutatedMerkleTree);
+    }
+    if checks.txs == 0 {
+        return Err(BlockError::NoTransactions);
+    }
+    if let Some(error) = checks.error {
+        return Err(error);
+    }
+    if let (Some(height), Some((script_sig, len))) = (height, checks.first_coinbase_script_sig) {
+        let (prefix, prefix_len) = bip34_prefix(height);
+        if len < prefix_len || script_sig[..prefix_len] != prefix[..prefix_len] {
+            return Err(BlockError::Bip34Height);
+        }
+    }
+
+    match checks.coinbase.commitment {
+        Some(commitment) => {
+            let reserved_value = match checks.coinbase.reserved_value {
+                Some(value) if checks.coinbase.witness_elements == 1 => value,
+                _ => return Err(BlockError::WitnessReservedValue),
+            };
+            let (witness_root, _) = core::mem::take(&mut checks.witness_merkle).finish();
+            let mut engine = sha256d::Hash::engine();
+            engine.input(&witness_root);
+            engine.input(&reserved_value);
+            if sha256d::Hash::from_engine(engine).to_byte_array() != commitment {
+                return Err(BlockError::WitnessCommitmentMismatch);
+            }
+        }
+        None if checks.any_witness => return Err(BlockError::UnexpectedWitness),
+        None => (),
+    }
+
+    if checks.weight > MAX_BLOCK_WEIGHT {
+        return Err(BlockError::Weight(checks.weight));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use core::ops::ControlFlow;
+
+    use super::{TxSanityError, MAX_MONEY};
+    use crate::{bsl::Transaction, test_common::GENESIS_TX, Parse, Visit, Visitor};
+
+    const NULL: [u8; 36] = {
+        let mut outpoint = [0u8; 36];
+        outpoint[32] = 0xff;
+        outpoint[33] = 0xff;
+        outpoint[34] = 0xff;
+        outpoint[35] = 0xff;
+        outpoint
+    };
+
+    /// Serialize a legacy transaction with the given inputs and outputs values
+    fn legacy_tx(inputs: &[([u8; 36], &[u8])], outputs: &[u64]) -> Vec<u8> {
+        let mut tx = vec![1u8, 0, 0, 0];
+        tx.push(inputs.len() as u8);
+        for (outpoint, script_sig) in inputs {
+            tx.extend_from_slice(outpoint);
+            tx.push(script_sig.len() as u8);
+            tx.extend_from_slice(script_sig);
+            tx.extend_from_slice(&[0xff; 4]);
+        }
+        tx.push(outputs.len() as u8);
+        for value in outputs {
+            tx.extend_from_slice(&value.to_le_bytes());
+            tx.push(0);
+        }
+        tx.extend_from_slice(&[0u8; 4]);
+        tx
+    }
+
+    fn check(tx: &[u8], scratch: &mut [([u8; 36], u32)]) -> Result<(), TxSanityError> {
+        Transaction::parse(tx)
+            .unwrap()
+            .parsed()
+            .check_sanity(scratch)
+    }
+
+    #[test]
+    fn sanity() {
+        let mut scratch = [([0u8; 36], 0); 4];
+        let mut scratch_buf = [([0u8; 36], 0); 8];
+        let genesis = Transaction::parse(&GENESIS_TX[..]).unwrap();
+        assert_eq!(genesis.parsed().check_sanity(&mut scratch), Ok(()));
+
+        let a = [1u8; 36];
+        let b = [2u8; 36];
+        assert_eq!(
+            check(&legacy_tx(&[(a, &[]), (b, &[])], &[1]), &mut scratch),
+            Ok(())
+        );
+        assert_eq!(
+            check(&legacy_tx(&[(a, &[])], &[]), &mut scratch),
+            Err(TxSanityError::NoOutputs)
+        );
+
+        // segwit serialization is the only way to express a transaction without inputs
+        let no_inputs = [
+            1u8, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert_eq!(
+            check(&no_inputs, &mut scratch),
+            Err(TxSanityError::NoInputs)
+        );
+
+        assert_eq!(
+            check(&legacy_tx(&[(a, &[])], &[1, MAX_MONEY + 1]), &mut scratch),
+            Err(TxSanityError::OutputValueTooLarge(1))
+        );
+        assert_eq!(
+            check(&legacy_tx(&[(a, &[])], &[MAX_MONEY, 1]), &mut scratch),
+            Err(TxSanityError::TotalOutputValueTooLarge)
+        );
+
+        let duplicate = legacy_tx(&[(a, &[]), (b, &[]), (a, &[])], &[1]);
+        assert_eq!(
+            check(&duplicate, &mut scratch),
+            Err(TxSanityError::DuplicateInput(2))
+        );
+        assert_eq!(
+            check(&duplicate, &mut []),
+            Err(TxSanityError::DuplicateInput(2))
+        );
+
+        // the first duplicate is found whatever the scratch length
+        let (c, d) = ([3u8; 36], [4u8; 36]);
+        let inputs = [a, b, c, d, b, a, c].map(|outpoint| (outpoint, &[][..]));
+        let duplicate = legacy_tx(&inputs, &[1]);
+        for len in 0..=8 {
+            assert_eq!(
+                check(&duplicate, &mut scratch_buf[..len]),
+                Err(TxSanityError::DuplicateInput(4)),
+                "scratch len {}",
+                len
+            );
+        }
+        let unique = legacy_tx(&[(a, &[]), (b, &[1, 2]), (c, &[]), (d, &[3])], &[1]);
+        for len in 0..=8 {
+            assert_eq!(check(&unique, &mut scratch_buf[..len]), Ok(()));
+        }
+
+        assert_eq!(
+            check(&legacy_tx(&[(NULL, &[1])], &[1]), &mut scratch),
+            Err(TxSanityError::CoinbaseScriptSigLength(1))
+        );
+        assert_eq!(
+            check(&legacy_tx(&[(NULL, &[1; 101])], &[1]), &mut scratch),
+            Err(TxSanityError::CoinbaseScriptSigLength(101))
+        );
+        assert_eq!(
+            check(&legacy_tx(&[(NULL, &[1; 100])], &[1]), &mut scratch),
+            Ok(())
+        );
+        assert_eq!(
+            check(&legacy_tx(&[(a, &[]), (NULL, &[1, 2])], &[1]), &mut scratch),
+            Err(TxSanityError::NullPrevout(1))
+        );
+    }
+
+    #[test]
+    fn sanity_mainnet_block() {
+        struct CheckAll([([u8; 36], u32); 16], usize);
+        impl Visitor for CheckAll {
+            fn visit_transaction(&mut self, tx: &Transaction) -> ControlFlow<()> {
+                assert_eq!(tx.check_sanity(&mut self.0), Ok(()));
+                self.1 += 1;
+                ControlFlow::Continue(())
+            }
+        }
+        let mut visitor = CheckAll([([0u8; 36], 0); 16], 0);
+        crate::bsl::Block::visit(bitcoin_test_data::blocks::mainnet_702861(), &mut visitor)
+            .unwrap();
+        assert_eq!(visitor.1, 2500);
+    }
+
+    #[test]
+    fn bip34_prefix() {
+        use super::bip34_prefix;
+        let check = |height, expected: &[u8]| {
+            let (prefix, len) = bip34_prefix(height);
+            assert_eq!(&prefix[..len], expected, "height {}", height);
+        };
+        check(0, &[0x00]);
+        check(1, &[0x51]);
+        check(16, &[0x60]);
+        check(17, &[0x01, 17]);
+        check(127, &[0x01, 0x7f]);
+        check(128, &[0x02, 0x80, 0x00]);
+        check(255, &[0x02, 0xff, 0x00]);
+        check(256, &[0x02, 0x00, 0x01]);
+        check(702861, &[0x03, 0x8d, 0xb9, 0x0a]);
+        check(8_388_608, &[0x04, 0x00, 0x00, 0x80, 0x00]);
+        check(u32::MAX, &[0x05, 0xff, 0xff, 0xff, 0xff, 0x00]);
+    }
+
+    #[cfg(feature = "bitcoin_hashes")]
+    #[test]
+    fn merkle_root() {
+        use super::MerkleRoot;
+        use bitcoin::hashes::Hash;
+
+        let leaf = |i: u8| [i; 32];
+        for n in 1..20u8 {
+            let mut merkle = MerkleRoot::default();
+            (0..n).for_each(|i| merkle.push(leaf(i)));
+            let (root, mutated) = merkle.finish();
+            let expected = bitcoin::merkle_tree::calculate_root(
+                (0..n).map(|i| bitcoin::Txid::from_byte_array(leaf(i))),
+            )
+            .unwrap();
+            assert_eq!(root, expected.to_byte_array(), "{} leaves", n);
+            assert!(!mutated);
+        }
+
+        // duplicating the last leaf of an odd level gives the same root
+        let mut merkle = MerkleRoot::default();
+        [0, 1, 2].iter().for_each(|i| merkle.push(leaf(*i)));
+        let (root, _) = merkle.finish();
+        let mut merkle = MerkleRoot::default();
+        [0, 1, 2, 2].iter().for_each(|i| merkle.push(leaf(*i)));
+        assert_eq!(merkle.finish(), (root, true));
+
+        assert_eq!(MerkleRoot::default().finish(), ([0u8; 32], false));
+    }
+
+    #[cfg(feature = "bitcoin_hashes")]
+    #[test]
+    fn check_block() {
+        use super::{BlockError, MerkleRoot};
+        use crate::bsl::Block;
+        use crate::test_common::GENESIS_BLOCK;
+
+        let genesis = Block::parse(&GENESIS_BLOCK[..]).unwrap();
+        assert_eq!(genesis.parsed().check(None), Ok(()));
+
+        let bytes = bitcoin_test_data::blocks::mainnet_702861();
+        let block = Block::parse(bytes).unwrap();
+        assert_eq!(block.parsed().check(Some(702861)), Ok(()));
+        assert_eq!(block.parsed().check(None), Ok(()));
+        assert_eq!(
+            block.parsed().check(Some(702862)),
+            Err(BlockError::Bip34Height)
+        );
+
+        let mut bytes = bytes.to_vec();
+        bytes[40] ^= 1;
+        let block = Block::parse(&bytes).unwrap();
+        assert_eq!(
+            block.parsed().check(None),
+            Err(BlockError::MerkleRootMismatch)
+        );
+        bytes[40] ^= 1;
+
+        // change the witness reserved value, which is not part of the txid
+        let coinbase_len = Transaction::parse(&bytes[83..]).unwrap().consumed();
+        bytes[83 + coinbase_len - 5] ^= 1;
+        let block = Block::parse(&bytes).unwrap();
+        assert_eq!(
+            block.parsed().check(None),
+            Err(BlockError::WitnessCommitmentMismatch)
+        );
+
+        // blocks with a valid merkle root but misplaced coinbases
+        let block_with = |txs: &[&[u8]]| {
+            use crate::bitcoin_hashes::Hash;
+            let mut merkle = MerkleRoot::default();
+            for tx in txs {
+                let txid = Transaction::parse(tx).unwrap().parsed().txid();
+                merkle.push(txid.to_byte_array());
+            }
+            let mut block = GENESIS_BLOCK[..80].to_vec();
+            block[36..68].copy_from_slice(&merkle.finish().0);
+            block.push(txs.len() as u8);
+            txs.iter().for_each(|tx| block.extend_from_slice(tx));
+            block
+        };
+        let spend = legacy_tx(&[([7u8; 36], &[])], &[1]);
+        let other_coinbase = legacy_tx(&[(NULL, &[0x02, 0x01, 0x00])], &[1]);
+
+        let bytes = block_with(&[&spend, &GENESIS_TX]);
+        assert_eq!(
+            Block::parse(&bytes).unwrap().parsed().check(None),
+            Err(BlockError::FirstNotCoinbase)
+        );
+        let bytes = block_with(&[&GENESIS_TX, &spend, &other_coinbase]);
+        assert_eq!(
+            Block::parse(&bytes).unwrap().parsed().check(None),
+            Err(BlockError::MultipleCoinbase(2))
+        );
+        let bytes = block_with(&[&GENESIS_TX, &spend]);
+        assert_eq!(Block::parse(&bytes).unwrap().parsed().check(None), Ok(()));
+    }
+}
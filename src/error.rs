@@ -22,14 +22,48 @@ pub enum Error {
     /// minimal encoding is `0x01`
     NonMinimalVarInt,
 
-    /// The implemented visitor decided to break by returning `true` from [`crate::visit::Visitor::visit_transaction`]
-    /// for example because it found what it was searching for
+    /// The implemented visitor decided to break by returning `ControlFlow::Break` from one of
+    /// its callbacks, for example because it found what it was searching for.
+    ///
+    /// Use [`crate::Visit::visit_break`] to get a value out of the visitor.
     VisitBreak,
+
+    /// Returned by [`crate::FromSlice::from_slice`] when the slice contains this many bytes after
+    /// the decoded value.
+    TrailingBytes(u32),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Needed(n) => write!(f, "needed {} more bytes", n),
+            Error::UnknownSegwitFlag(flag) => write!(f, "unknown segwit flag {}", flag),
+            Error::SegwitFlagWithoutWitnesses => {
+                write!(f, "segwit flag set but all witnesses are empty")
+            }
+            Error::NonMinimalVarInt => write!(f, "compact int not minimally encoded"),
+            Error::VisitBreak => write!(f, "the visitor stopped the visit"),
+            Error::TrailingBytes(n) => write!(f, "{} trailing bytes after the value", n),
+        }
+    }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
 #[cfg(test)]
 mod test {
 
+    #[test]
+    fn display() {
+        use super::Error;
+        assert_eq!(Error::Needed(12).to_string(), "needed 12 more bytes");
+        assert_eq!(
+            Error::NonMinimalVarInt.to_string(),
+            "compact int not minimally encoded"
+        );
+    }
+
     #[cfg(target_pointer_width = "64")]
     #[test]
     fn size_of() {
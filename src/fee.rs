@@ -0,0 +1,284 @@
+//! Fee computation, given the values of the outputs spent by a transaction.
+//!
+//! The spent outputs are not part of the transaction, they are provided through the
+//! [`PrevoutProvider`] trait, implemented for closures and, with the relative features, for
+//! [`crate::SliceCache`] and `redb` tables of [`OutPoint`] to [`TxOut`].
+
+use core::{convert::Infallible, fmt, ops::ControlFlow};
+
+use crate::{
+    bsl::{OutPoint, Transaction, TxIn, TxOut},
+    consensus::MAX_MONEY,
+    Error, Stop, Visit,
+};
+
+/// Provides the value of the outputs spent by transaction inputs
+pub trait PrevoutProvider {
+    /// The error returned by the provider when the value can't be retrieved
+    type Error;
+
+    /// Returns the value in satoshi of the output spent by `outpoint`, or `None` if unknown
+    fn prevout_value(&mut self, outpoint: &OutPoint) -> Result<Option<u64>, Self::Error>;
+}
+
+impl<F: FnMut(&OutPoint) -> Option<u64>> PrevoutProvider for F {
+    type Error = Infallible;
+
+    fn prevout_value(&mut self, outpoint: &OutPoint) -> Result<Option<u64>, Self::Error> {
+        Ok(self(outpoint))
+    }
+}
+
+/// A cache of serialized [`TxOut`] with the serialized [`OutPoint`] as key, like the one filled
+/// while visiting blocks to compute the fees of the following ones
+#[cfg(feature = "slice_cache")]
+impl PrevoutProvider for crate::SliceCache<[u8; 36]> {
+    type Error = Infallible;
+
+    fn prevout_value(&mut self, outpoint: &OutPoint) -> Result<Option<u64>, Self::Error> {
+        use crate::Parse;
+        let key: [u8; 36] = outpoint.as_ref().try_into().expect("outpoint is 36 bytes");
+        Ok(self
+            .get(&key)
+            .and_then(|slice| TxOut::parse(slice).ok())
+            .map(|tx_out| tx_out.parsed().value()))
+    }
+}
+
+#[cfg(feature = "redb")]
+impl PrevoutProvider for redb::ReadOnlyTable<'_, OutPoint<'static>, TxOut<'static>> {
+    type Error = redb::StorageError;
+
+    fn prevout_value(&mut self, outpoint: &OutPoint) -> Result<Option<u64>, Self::Error> {
+        use redb::ReadableTable;
+        Ok(self.get(outpoint)?.map(|tx_out| tx_out.value().value()))
+    }
+}
+
+#[cfg(feature = "redb")]
+impl PrevoutProvider for redb::Table<'_, '_, OutPoint<'static>, TxOut<'static>> {
+    type Error = redb::StorageError;
+
+    fn prevout_value(&mut self, outpoint: &OutPoint) -> Result<Option<u64>, Self::Error> {
+        use redb::ReadableTable;
+        Ok(self.get(outpoint)?.map(|tx_out| tx_out.value().value()))
+    }
+}
+
+/// The reasons the fee of a transaction can't be computed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeeError<E> {
+    /// The transaction is a coinbase, which doesn't pay fees
+    Coinbase,
+
+    /// The provider doesn't know the output spent by the input with the given index
+    MissingPrevout(usize),
+
+    /// The outputs value is greater than the inputs value
+    NegativeFee,
+
+    /// The inputs or the outputs values sum to more than [`MAX_MONEY`]
+    ValueOutOfRange,
+
+    /// The transaction bytes are invalid, for example because it has been created with
+    /// [`Transaction::from_trusted_slice`] from bytes which are not a transaction
+    Invalid(Error),
+
+    /// The provider returned an error
+    Provider(E),
+}
+
+impl<E: fmt::Display> fmt::Display for FeeError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeeError::Coinbase => write!(f, "coinbase transactions don't pay fees"),
+            FeeError::MissingPrevout(vin) => write!(f, "missing prevout of input {}", vin),
+            FeeError::NegativeFee => write!(f, "outputs value exceeds inputs value"),
+            FeeError::ValueOutOfRange => write!(f, "values sum out of the money range"),
+            FeeError::Invalid(e) => write!(f, "invalid transaction: {}", e),
+            FeeError::Provider(e) => write!(f, "prevout provider error: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug + fmt::Display> std::error::Error for FeeError<E> {}
+
+/// Sums the inputs values, asking them to the provider, and the outputs values
+struct Fee<'p, P> {
+    provider: &'p mut P,
+    inputs: u64,
+    outputs: u64,
+}
+
+impl<P: PrevoutProvider> crate::Visitor<FeeError<P::Error>> for Fee<'_, P> {
+    fn visit_subtree(
+        &mut self,
+        subtree: crate::Subtree,
+    ) -> ControlFlow<FeeError<P::Error>, crate::Descend> {
+        match subtree {
+            crate::Subtree::Witnesses => ControlFlow::Continue(crate::Descend::Skip),
+            _ => ControlFlow::Continue(crate::Descend::Visit),
+        }
+    }
+    fn visit_tx_in(&mut self, vin: usize, tx_in: &TxIn) -> ControlFlow<FeeError<P::Error>> {
+        if tx_in.prevout().is_null() {
+            return ControlFlow::Break(FeeError::Coinbase);
+        }
+        match self.provider.prevout_value(tx_in.prevout()) {
+            Ok(Some(value)) => match money_add(self.inputs, value) {
+                Some(inputs) => {
+                    self.inputs = inputs;
+                    ControlFlow::Continue(())
+                }
+                None => ControlFlow::Break(FeeError::ValueOutOfRange),
+            },
+            Ok(None) => ControlFlow::Break(FeeError::MissingPrevout(vin)),
+            Err(e) => ControlFlow::Break(FeeError::Provider(e)),
+        }
+    }
+    fn visit_tx_out(&mut self, _vout: usize, tx_out: &TxOut) -> ControlFlow<FeeError<P::Error>> {
+        match money_add(self.outputs, tx_out.value()) {
+            Some(outputs) => {
+                self.outputs = outputs;
+                ControlFlow::Continue(())
+            }
+            None => ControlFlow::Break(FeeError::ValueOutOfRange),
+        }
+    }
+}
+
+/// Adds `value` to `total` if the result is in the money range, as in `check_sanity`
+fn money_add(total: u64, value: u64) -> Option<u64> {
+    total.checked_add(value).filter(|sum| *sum <= MAX_MONEY)
+}
+
+pub(crate) fn fee<P: PrevoutProvider>(
+    tx: &Transaction,
+    provider: &mut P,
+) -> Result<u64, FeeError<P::Error>> {
+    let mut fee = Fee {
+        provider,
+        inputs: 0,
+        outputs: 0,
+    };
+    Transaction::visit_break(tx.as_ref(), &mut fee).map_err(|stop| match stop {
+        Stop::Break { value, .. } => value,
+        Stop::Error(e) => FeeError::Invalid(e),
+    })?;
+    fee.inputs
+        .checked_sub(fee.outputs)
+        .ok_or(FeeError::NegativeFee)
+}
+
+#[cfg(test)]
+mod test {
+    use hex_lit::hex;
+
+    use super::FeeError;
+    use crate::{
+        bsl::{OutPoint, Transaction},
+        consensus::MAX_MONEY,
+        test_common::GENESIS_TX,
+        Error, Parse,
+    };
+
+    /// 1 input spending 1000000 sat, 2 P2WPKH outputs of 500000 and 499000 sat
+    fn tx_bytes() -> Vec<u8> {
+        let mut tx = hex!("0200000001").to_vec();
+        tx.extend_from_slice(&[0xaa; 32]);
+        tx.extend_from_slice(&hex!("0100000000ffffffff02"));
+        for value in [500_000u64, 499_000] {
+            tx.extend_from_slice(&value.to_le_bytes());
+            tx.extend_from_slice(&hex!("160014"));
+            tx.extend_from_slice(&[0u8; 20]);
+        }
+        tx.extend_from_slice(&[0u8; 4]);
+        tx
+    }
+
+    #[test]
+    fn fee() {
+        let bytes = tx_bytes();
+        let tx = Transaction::parse(&bytes[..]).unwrap().parsed_owned();
+        assert_eq!(tx.vsize(), 113);
+
+        let mut provider = |outpoint: &OutPoint| {
+            (outpoint.txid() == [0xaa; 32] && outpoint.vout() == 1).then_some(1_000_000)
+        };
+        assert_eq!(tx.fee(&mut provider), Ok(1000));
+        assert_eq!(tx.feerate(&mut provider), Ok(1000.0 / 113.0));
+
+        assert_eq!(
+            tx.fee(&mut |_: &OutPoint| None),
+            Err(FeeError::MissingPrevout(0))
+        );
+        assert_eq!(
+            tx.fee(&mut |_: &OutPoint| Some(1)),
+            Err(FeeError::NegativeFee)
+        );
+
+        assert_eq!(
+            tx.fee(&mut |_: &OutPoint| Some(MAX_MONEY + 1)),
+            Err(FeeError::ValueOutOfRange)
+        );
+        assert_eq!(
+            tx.fee(&mut |_: &OutPoint| Some(u64::MAX)),
+            Err(FeeError::ValueOutOfRange)
+        );
+
+        let genesis = Transaction::parse(&GENESIS_TX[..]).unwrap();
+        assert_eq!(genesis.parsed().fee(&mut provider), Err(FeeError::Coinbase));
+
+        let truncated = Transaction::from_trusted_slice(&bytes[..bytes.len() - 1]);
+        assert_eq!(
+            truncated.fee(&mut provider),
+            Err(FeeError::Invalid(Error::Needed(1)))
+        );
+    }
+
+    #[cfg(feature = "slice_cache")]
+    #[test]
+    fn fee_slice_cache() {
+        let bytes = tx_bytes();
+        let tx = Transaction::parse(&bytes[..]).unwrap().parsed_owned();
+        let mut cache = crate::SliceCache::new(1000);
+        let mut outpoint = [0xaa; 36];
+        outpoint[32..].copy_from_slice(&1u32.to_le_bytes());
+        // value and empty script
+        let tx_out = hex!("40420f000000000000");
+        cache.insert(outpoint, &tx_out).unwrap();
+
+        assert_eq!(tx.fee(&mut cache), Ok(1000));
+    }
+
+    #[cfg(feature = "redb")]
+    #[test]
+    fn fee_redb() {
+        use crate::bsl::TxOut;
+
+        const TABLE: redb::TableDefinition<OutPoint, TxOut> = redb::TableDefinition::new("utxo");
+        let bytes = tx_bytes();
+        let tx = Transaction::parse(&bytes[..]).unwrap().parsed_owned();
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let db = redb::Database::create(path).unwrap();
+
+        let mut outpoint = [0xaa; 36];
+        outpoint[32..].copy_from_slice(&1u32.to_le_bytes());
+        let outpoint = OutPoint::parse(&outpoint[..]).unwrap().parsed_owned();
+        let tx_out = hex!("40420f000000000000");
+        let tx_out = TxOut::parse(&tx_out[..]).unwrap().parsed_owned();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            table.insert(&outpoint, &tx_out).unwrap();
+            assert_eq!(tx.fee(&mut table).unwrap(), 1000);
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let mut table = read_txn.open_table(TABLE).unwrap();
+        assert_eq!(tx.fee(&mut table).unwrap(), 1000);
+    }
+}
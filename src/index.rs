@@ -0,0 +1,507 @@
+//! A script history index in a redb database, answering the queries of electrum servers.
+//!
+//! [`index_block`] visits a block and records:
+//! * in [`HISTORY`] the outputs funding every script and the inputs spending them,
+//! * in [`TX_POSITIONS`] the height and the position in the block of every transaction,
+//! * in [`SPENDS`] the input spending every outpoint.
+//!
+//! Inputs are attributed to the script they spend through the [`OUTPUTS`] table, so blocks must be
+//! indexed in order and starting from genesis to have complete histories: inputs spending outputs
+//! which weren't indexed are recorded in [`SPENDS`] only.
+//!
+//! Provably unspendable outputs, see [`crate::utxo::is_unspendable`], are not indexed.
+
+use core::ops::ControlFlow;
+use std::vec::Vec;
+
+use redb::{
+    MultimapTable, MultimapTableDefinition, ReadTransaction, ReadableMultimapTable, ReadableTable,
+    Table, TableDefinition, WriteTransaction,
+};
+
+use crate::{
+    bsl::{Block, OutPoint, ScriptHash, Transaction, TxIn, TxOut, Txid},
+    utxo::{for_each_tx, is_unspendable, outpoint},
+    Descend, FromSlice, Stop, Subtree, Visit, Visitor,
+};
+
+/// The funding and spending entries of every script, sorted by height and position in the block
+pub const HISTORY: MultimapTableDefinition<ScriptHash, HistoryEntry> =
+    MultimapTableDefinition::new("history");
+
+/// The height and the position in the block of every transaction
+pub const TX_POSITIONS: TableDefinition<Txid, (u32, u32)> = TableDefinition::new("tx_positions");
+
+/// The transaction and the input index spending every outpoint
+pub const SPENDS: TableDefinition<OutPoint, (Txid, u32)> = TableDefinition::new("spends");
+
+/// The script hash and the value of every indexed output
+pub const OUTPUTS: TableDefinition<OutPoint, (ScriptHash, u64)> = TableDefinition::new("outputs");
+
+/// Whether an history entry adds or removes value from a script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Direction {
+    /// An output paying to the script, the entry index is the `vout`
+    Funding,
+    /// An input spending an output of the script, the entry index is the `vin`
+    Spending,
+}
+
+/// An element of the history of a script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HistoryEntry {
+    /// The height of the block containing the transaction
+    pub height: u32,
+    /// The position of the transaction in the block
+    pub position: u32,
+    /// The transaction funding or spending the script
+    pub txid: Txid,
+    /// The output index when funding, the input index when spending
+    pub index: u32,
+    /// Whether the transaction is funding or spending the script
+    pub direction: Direction,
+    /// The value of the output created or spent
+    pub value: u64,
+}
+
+const HISTORY_ENTRY_LEN: usize = 53;
+
+impl HistoryEntry {
+    /// Serializes the entry so that the bytes order is the order of the fields
+    fn to_bytes(self) -> [u8; HISTORY_ENTRY_LEN] {
+        let mut bytes = [0u8; HISTORY_ENTRY_LEN];
+        bytes[..4].copy_from_slice(&self.height.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.position.to_be_bytes());
+        bytes[8..40].copy_from_slice(&self.txid.0);
+        bytes[40..44].copy_from_slice(&self.index.to_be_bytes());
+        bytes[44] = match self.direction {
+            Direction::Funding => 0,
+            Direction::Spending => 1,
+        };
+        bytes[45..].copy_from_slice(&self.value.to_be_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; HISTORY_ENTRY_LEN]) -> Self {
+        let u32_at = |i: usize| u32::from_be_bytes(bytes[i..i + 4].try_into().expect("4 bytes"));
+        HistoryEntry {
+            height: u32_at(0),
+            position: u32_at(4),
+            txid: Txid(bytes[8..40].try_into().expect("32 bytes")),
+            index: u32_at(40),
+            direction: match bytes[44] {
+                0 => Direction::Funding,
+                _ => Direction::Spending,
+            },
+            value: u64::from_be_bytes(bytes[45..].try_into().expect("8 bytes")),
+        }
+    }
+}
+
+impl redb::RedbValue for HistoryEntry {
+    // TODO fix where position once MSRV allows
+    type SelfType<'a>
+    where
+        Self: 'a,
+    = HistoryEntry;
+
+    type AsBytes<'a>
+    where
+        Self: 'a,
+    = [u8; HISTORY_ENTRY_LEN];
+
+    fn fixed_width() -> Option<usize> {
+        Some(HISTORY_ENTRY_LEN)
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+        HistoryEntry::from_bytes(
+            data.try_into()
+                .expect("inserted data is not a HistoryEntry"),
+        )
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value.to_bytes()
+    }
+
+    fn type_name() -> redb::TypeName {
+        redb::TypeName::new("bsl::HistoryEntry")
+    }
+}
+
+impl redb::RedbKey for HistoryEntry {
+    fn compare(data1: &[u8], data2: &[u8]) -> core::cmp::Ordering {
+        data1.cmp(data2)
+    }
+}
+
+/// The tables written while indexing a block
+struct Tables<'db, 'txn> {
+    history: MultimapTable<'db, 'txn, ScriptHash, HistoryEntry>,
+    tx_positions: Table<'db, 'txn, Txid, (u32, u32)>,
+    spends: Table<'db, 'txn, OutPoint<'static>, (Txid, u32)>,
+    outputs: Table<'db, 'txn, OutPoint<'static>, (ScriptHash, u64)>,
+}
+
+impl<'db, 'txn> Tables<'db, 'txn> {
+    fn open(txn: &'txn WriteTransaction<'db>) -> Result<Self, redb::Error> {
+        Ok(Tables {
+            history: txn.open_multimap_table(HISTORY)?,
+            tx_positions: txn.open_table(TX_POSITIONS)?,
+            spends: txn.open_table(SPENDS)?,
+            outputs: txn.open_table(OUTPUTS)?,
+        })
+    }
+}
+
+/// Indexes, or removes from the index, the inputs or the outputs of a transaction
+struct IndexTx<'t, 'db, 'txn> {
+    tables: &'t mut Tables<'db, 'txn>,
+    height: u32,
+    position: u32,
+    txid: Txid,
+    inputs: bool,
+    outputs: bool,
+    remove: bool,
+}
+
+impl IndexTx<'_, '_, '_> {
+    fn entry(&self, index: usize, direction: Direction, value: u64) -> HistoryEntry {
+        HistoryEntry {
+            height: self.height,
+            position: self.position,
+            txid: self.txid,
+            index: index as u32,
+            direction,
+            value,
+        }
+    }
+
+    fn tx_in(&mut self, vin: usize, tx_in: &TxIn) -> Result<(), redb::Error> {
+        let prevout = tx_in.prevout();
+        let spent = self.tables.outputs.get(prevout)?.map(|e| e.value());
+        if self.remove {
+            self.tables.spends.remove(prevout)?;
+        } else {
+            self.tables
+                .spends
+                .insert(prevout, (self.txid, vin as u32))?;
+        }
+        if let Some((script_hash, value)) = spent {
+            let entry = self.entry(vin, Direction::Spending, value);
+            if self.remove {
+                self.tables.history.remove(script_hash, entry)?;
+            } else {
+                self.tables.history.insert(script_hash, entry)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn tx_out(&mut self, vout: usize, tx_out: &TxOut) -> Result<(), redb::Error> {
+        if is_unspendable(tx_out.script_pubkey()) {
+            return Ok(());
+        }
+        let outpoint = outpoint(&self.txid.0, vout);
+        let outpoint = OutPoint::from_trusted_slice(&outpoint[..]);
+        let script_hash = ScriptHash::from_script(tx_out.script_pubkey());
+        let entry = self.entry(vout, Direction::Funding, tx_out.value());
+        if self.remove {
+            self.tables.outputs.remove(&outpoint)?;
+            self.tables.history.remove(script_hash, entry)?;
+        } else {
+            self.tables
+                .outputs
+                .insert(&outpoint, (script_hash, tx_out.value()))?;
+            self.tables.history.insert(script_hash, entry)?;
+        }
+        Ok(())
+    }
+}
+
+fn control_flow(result: Result<(), redb::Error>) -> ControlFlow<redb::Error> {
+    match result {
+        Ok(()) => ControlFlow::Continue(()),
+        Err(e) => ControlFlow::Break(e),
+    }
+}
+
+impl Visitor<redb::Error> for IndexTx<'_, '_, '_> {
+    fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<redb::Error, Descend> {
+        let visit = match subtree {
+            Subtree::TxIns => self.inputs && self.position != 0,
+            Subtree::TxOuts => self.outputs,
+            Subtree::Witnesses => false,
+        };
+        if visit {
+            ControlFlow::Continue(Descend::Visit)
+        } else {
+            ControlFlow::Continue(Descend::Skip)
+        }
+    }
+    fn visit_tx_in(&mut self, vin: usize, tx_in: &TxIn) -> ControlFlow<redb::Error> {
+        control_flow(self.tx_in(vin, tx_in))
+    }
+    fn visit_tx_out(&mut self, vout: usize, tx_out: &TxOut) -> ControlFlow<redb::Error> {
+        control_flow(self.tx_out(vout, tx_out))
+    }
+}
+
+/// Visits the inputs and/or the outputs of every transaction in the block, indexing them or
+/// removing them. The transaction positions are handled together with the inputs.
+fn visit_block(
+    tables: &mut Tables,
+    block: &Block,
+    height: u32,
+    (inputs, outputs): (bool, bool),
+    remove: bool,
+) -> Result<(), redb::Error> {
+    use crate::bitcoin_hashes::Hash;
+
+    for_each_tx(block, |position, tx| {
+        let txid = Txid(tx.txid().to_byte_array());
+        if inputs {
+            if remove {
+                tables.tx_positions.remove(txid)?;
+            } else {
+                tables
+                    .tx_positions
+                    .insert(txid, (height, position as u32))?;
+            }
+        }
+        let mut index_tx = IndexTx {
+            tables: &mut *tables,
+            height,
+            position: position as u32,
+            txid,
+            inputs,
+            outputs,
+            remove,
+        };
+        match Transaction::visit_break(tx.as_ref(), &mut index_tx) {
+            Ok(_) => Ok(()),
+            Err(Stop::Break { value, .. }) => Err(value),
+            Err(Stop::Error(_)) => unreachable!("transaction already parsed"),
+        }
+    })
+}
+
+/// Indexes `block` at `height`.
+///
+/// On error the write transaction contains a partially indexed block and should be aborted.
+pub fn index_block(txn: &WriteTransaction, block: &Block, height: u32) -> Result<(), redb::Error> {
+    let mut tables = Tables::open(txn)?;
+    // inputs spending outputs of previous transactions in the block find them in the outputs
+    // table, since every transaction is indexed before the next one
+    visit_block(&mut tables, block, height, (true, true), false)
+}
+
+/// Removes `block` at `height`, previously indexed with [`index_block`], for example during a
+/// reorg.
+///
+/// On error the write transaction contains a partially removed block and should be aborted.
+pub fn unindex_block(
+    txn: &WriteTransaction,
+    block: &Block,
+    height: u32,
+) -> Result<(), redb::Error> {
+    let mut tables = Tables::open(txn)?;
+    // inputs are removed first, since they need the outputs they spend
+    visit_block(&mut tables, block, height, (true, false), true)?;
+    visit_block(&mut tables, block, height, (false, true), true)
+}
+
+/// Returns the funding and spending entries of the script with the given hash, sorted by height
+/// and position in the block
+pub fn history(
+    txn: &ReadTransaction,
+    script_hash: &ScriptHash,
+) -> Result<Vec<HistoryEntry>, redb::Error> {
+    let table = txn.open_multimap_table(HISTORY)?;
+    let mut result = Vec::new();
+    for entry in table.get(script_hash)? {
+        result.push(entry?.value());
+    }
+    Ok(result)
+}
+
+/// Returns the funding entries of the script with the given hash whose output is not spent
+pub fn utxos(
+    txn: &ReadTransaction,
+    script_hash: &ScriptHash,
+) -> Result<Vec<HistoryEntry>, redb::Error> {
+    let spends = txn.open_table(SPENDS)?;
+    let mut result = Vec::new();
+    for entry in history(txn, script_hash)? {
+        if entry.direction == Direction::Spending {
+            continue;
+        }
+        let outpoint = outpoint(&entry.txid.0, entry.index as usize);
+        let outpoint = OutPoint::from_trusted_slice(&outpoint[..]);
+        if spends.get(&outpoint)?.is_none() {
+            result.push(entry);
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the sum of the values of the unspent outputs of the script with the given hash
+pub fn balance(txn: &ReadTransaction, script_hash: &ScriptHash) -> Result<u64, redb::Error> {
+    Ok(utxos(txn, script_hash)?.iter().map(|e| e.value).sum())
+}
+
+/// Returns the height and the position in the block of the transaction with the given id
+pub fn tx_position(txn: &ReadTransaction, txid: &Txid) -> Result<Option<(u32, u32)>, redb::Error> {
+    let table = txn.open_table(TX_POSITIONS)?;
+    let position = table.get(txid)?.map(|e| e.value());
+    Ok(position)
+}
+
+/// Returns the transaction and the input index spending the given outpoint
+pub fn spending_input(
+    txn: &ReadTransaction,
+    outpoint: &OutPoint,
+) -> Result<Option<(Txid, u32)>, redb::Error> {
+    let table = txn.open_table(SPENDS)?;
+    let spending = table.get(outpoint)?.map(|e| e.value());
+    Ok(spending)
+}
+
+#[cfg(test)]
+mod test {
+    use bitcoin::hashes::Hash;
+
+    use super::{
+        balance, history, index_block, spending_input, tx_position, unindex_block, utxos,
+        Direction, HistoryEntry,
+    };
+    use crate::{
+        bsl::{Block, OutPoint, ScriptHash, Txid},
+        builder::TransactionWriter,
+        FromSlice, Parse,
+    };
+
+    #[test]
+    fn index_unindex() {
+        let block_bytes = bitcoin_test_data::blocks::mainnet_702861();
+        let block = Block::parse(block_bytes).unwrap().parsed_owned();
+        let block_bitcoin: bitcoin::Block = bitcoin::consensus::deserialize(block_bytes).unwrap();
+        let coinbase = &block_bitcoin.txdata[0];
+        let coinbase_txid = Txid(coinbase.compute_txid().to_byte_array());
+        let coinbase_script = ScriptHash::from_script(coinbase.output[0].script_pubkey.as_bytes());
+        let coinbase_value = coinbase.output[0].value.to_sat();
+
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let db = redb::Database::create(path).unwrap();
+        let write_txn = db.begin_write().unwrap();
+        index_block(&write_txn, &block, 702861).unwrap();
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        for (i, tx) in block_bitcoin.txdata.iter().enumerate() {
+            let txid = Txid(tx.compute_txid().to_byte_array());
+            assert_eq!(
+                tx_position(&read_txn, &txid).unwrap(),
+                Some((702861, i as u32))
+            );
+        }
+        let tx1 = &block_bitcoin.txdata[1];
+        let prevout = bitcoin::consensus::serialize(&tx1.input[0].previous_output);
+        let prevout = OutPoint::parse(&prevout[..]).unwrap().parsed_owned();
+        assert_eq!(
+            spending_input(&read_txn, &prevout).unwrap(),
+            Some((Txid(tx1.compute_txid().to_byte_array()), 0))
+        );
+        let funding = HistoryEntry {
+            height: 702861,
+            position: 0,
+            txid: coinbase_txid,
+            index: 0,
+            direction: Direction::Funding,
+            value: coinbase_value,
+        };
+        let history_before = history(&read_txn, &coinbase_script).unwrap();
+        assert_eq!(history_before, vec![funding]);
+        assert_eq!(utxos(&read_txn, &coinbase_script).unwrap(), vec![funding]);
+        assert_eq!(
+            balance(&read_txn, &coinbase_script).unwrap(),
+            coinbase_value
+        );
+        drop(read_txn);
+
+        // next block spends the coinbase output
+        let mut next_coinbase = TransactionWriter::new(Vec::new(), 1, 1, false).unwrap();
+        next_coinbase
+            .tx_in(&[0u8; 32], u32::MAX, &[0x51, 0x51], 0)
+            .unwrap();
+        next_coinbase.tx_outs(1).unwrap();
+        next_coinbase.tx_out(0, &[0x6a]).unwrap();
+        let next_coinbase = next_coinbase.finish(0).unwrap();
+        let mut spending = TransactionWriter::new(Vec::new(), 2, 1, false).unwrap();
+        spending.tx_in(&coinbase_txid.0, 0, &[0x51], 0).unwrap();
+        spending.tx_outs(1).unwrap();
+        spending.tx_out(coinbase_value - 1000, &[0x52]).unwrap();
+        let spending = spending.finish(0).unwrap();
+        let mut next_bytes = block_bytes[..80].to_vec();
+        next_bytes.push(2);
+        next_bytes.extend_from_slice(&next_coinbase);
+        next_bytes.extend_from_slice(&spending);
+        let next = Block::parse(&next_bytes[..]).unwrap().parsed_owned();
+        let spending_txid = {
+            let tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&spending).unwrap();
+            Txid(tx.compute_txid().to_byte_array())
+        };
+
+        let write_txn = db.begin_write().unwrap();
+        index_block(&write_txn, &next, 702862).unwrap();
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let spent = HistoryEntry {
+            height: 702862,
+            position: 1,
+            txid: spending_txid,
+            index: 0,
+            direction: Direction::Spending,
+            value: coinbase_value,
+        };
+        assert_eq!(
+            history(&read_txn, &coinbase_script).unwrap(),
+            vec![funding, spent]
+        );
+        assert!(utxos(&read_txn, &coinbase_script).unwrap().is_empty());
+        assert_eq!(balance(&read_txn, &coinbase_script).unwrap(), 0);
+        let new_script = ScriptHash::from_script(&[0x52]);
+        assert_eq!(
+            balance(&read_txn, &new_script).unwrap(),
+            coinbase_value - 1000
+        );
+        assert!(history(&read_txn, &ScriptHash::from_script(&[0x6a]))
+            .unwrap()
+            .is_empty());
+        drop(read_txn);
+
+        let write_txn = db.begin_write().unwrap();
+        unindex_block(&write_txn, &next, 702862).unwrap();
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        assert_eq!(
+            history(&read_txn, &coinbase_script).unwrap(),
+            history_before
+        );
+        assert!(history(&read_txn, &new_script).unwrap().is_empty());
+        assert_eq!(tx_position(&read_txn, &spending_txid).unwrap(), None);
+        let coinbase_outpoint = crate::utxo::outpoint(&coinbase_txid.0, 0);
+        let coinbase_outpoint = OutPoint::from_trusted_slice(&coinbase_outpoint[..]);
+        assert_eq!(spending_input(&read_txn, &coinbase_outpoint).unwrap(), None);
+    }
+}
@@ -1,34 +1,92 @@
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 #![deny(missing_docs)]
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![warn(missing_docs)]
 #![doc = include_str!("../README.md")]
 
 pub mod bsl;
+pub mod builder;
+pub mod codec;
+pub mod combinators;
+pub mod consensus;
 mod error;
+pub mod fee;
+#[cfg(all(feature = "redb", feature = "bitcoin_hashes", feature = "std"))]
+pub mod index;
+mod located;
 pub mod number;
 mod parse_result;
+pub mod policy;
 mod slice;
+pub mod timelock;
+#[cfg(all(feature = "redb", feature = "bitcoin_hashes", feature = "std"))]
+pub mod utxo;
 mod visit;
 
 #[cfg(feature = "slice_cache")]
 mod slice_cache;
 
-#[cfg(feature = "slice_cache")]
-#[macro_use]
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "slice_cache", macro_use)]
 extern crate alloc;
 
 #[cfg(feature = "slice_cache")]
-pub use slice_cache::SliceCache;
+pub use slice_cache::{EvictionPolicy, SliceCache, Usage};
+
+#[cfg(all(feature = "slice_cache", feature = "std"))]
+pub use slice_cache::{ShardedSliceCache, SliceGuard};
 
+pub use codec::{AsSlice, FromSlice};
 pub use error::Error;
+pub use located::{ErrorPath, Field, LocatedError, PathItem};
 pub use parse_result::ParseResult;
 pub use slice::read_slice;
-pub use visit::{EmptyVisitor, Parse, Visit, Visitor};
+pub use visit::{Descend, EmptyVisitor, Parse, Stop, Subtree, Visit, Visitor};
 
 /// Common result type throughout the lib
 pub type SResult<'a, T> = Result<ParseResult<'a, T>, Error>;
 
+/// Result type of [`Visit::visit_break`], where the visitor may stop with a value of type `B`
+pub type BResult<'a, T, B> = Result<ParseResult<'a, T>, Stop<'a, B>>;
+
+#[cfg(feature = "derive")]
+pub use bitcoin_slices_derive::{visitor, Visitor};
+
+#[cfg(feature = "derive")]
+extern crate self as bitcoin_slices;
+
+/// Not public API, used by the code generated by the `derive` feature macros
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __private {
+    use core::ops::ControlFlow;
+
+    pub use crate::combinators::{merge, merge_descend};
+
+    /// Allows callbacks returning `ControlFlow` to be implemented with methods returning `()`
+    pub trait IntoControlFlow<B, C> {
+        fn into_control_flow(self) -> ControlFlow<B, C>;
+    }
+
+    impl<B> IntoControlFlow<B, ()> for () {
+        fn into_control_flow(self) -> ControlFlow<B, ()> {
+            ControlFlow::Continue(())
+        }
+    }
+
+    impl<B, C> IntoControlFlow<B, C> for ControlFlow<B, C> {
+        fn into_control_flow(self) -> ControlFlow<B, C> {
+            self
+        }
+    }
+
+    impl<B> IntoControlFlow<B, crate::Descend> for crate::Descend {
+        fn into_control_flow(self) -> ControlFlow<B, crate::Descend> {
+            ControlFlow::Continue(self)
+        }
+    }
+}
+
 #[cfg(feature = "bitcoin_hashes")]
 pub use bitcoin_hashes;
 
@@ -38,10 +96,14 @@ pub use sha2;
 #[cfg(feature = "redb")]
 pub use redb;
 
+#[cfg(feature = "heed")]
+pub use heed;
+
 #[cfg(feature = "bitcoin")]
 pub use bitcoin;
 
 #[cfg(test)]
+#[allow(missing_docs)]
 pub mod test_common {
     use hex_lit::hex;
 
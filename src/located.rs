@@ -0,0 +1,369 @@
+//! Errors enriched with the position in the data where parsing failed.
+//!
+//! Parsing returns the compact [`Error`] for performance, the location is computed only when an
+//! error happened, by walking again the data up to the failing element, see for example
+//! [`crate::bsl::Block::parse_located`].
+
+use core::fmt;
+
+use crate::{bsl::BlockHeader, bsl::Len, slice::read_slice, Error, Parse};
+
+/// An [`Error`] with the byte offset and the path of the element where parsing failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocatedError {
+    /// The error returned by parsing
+    pub error: Error,
+
+    /// The offset, from the start of the parsed slice, of the first byte of the failing element
+    pub offset: usize,
+
+    /// The element where parsing failed
+    pub path: ErrorPath,
+}
+
+/// The path of an element in a block, displayed like `tx 1234 / input 5 / script_sig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorPath {
+    /// The index of the transaction in the block, `None` if not in a transaction or if a single
+    /// transaction is parsed
+    pub tx: Option<usize>,
+
+    /// The input, output or witness of the transaction containing the element, if any
+    pub item: Option<PathItem>,
+
+    /// The failing element
+    pub field: Field,
+}
+
+/// An input, output or witness in a transaction, with its index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathItem {
+    /// The transaction input with the given index
+    Input(usize),
+
+    /// The transaction output with the given index
+    Output(usize),
+
+    /// The witness of the transaction input with the given index
+    Witness(usize),
+}
+
+/// An element of a block or of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// The block header
+    Header,
+
+    /// The number of transactions in the block
+    TxCount,
+
+    /// The transaction version
+    Version,
+
+    /// The segwit flag following the segwit marker
+    SegwitFlag,
+
+    /// The number of inputs of the transaction
+    InputCount,
+
+    /// The previous output spent by the input
+    Prevout,
+
+    /// The script sig of the input
+    ScriptSig,
+
+    /// The sequence of the input
+    Sequence,
+
+    /// The number of outputs of the transaction
+    OutputCount,
+
+    /// The amount of the output
+    Value,
+
+    /// The script pubkey of the output
+    ScriptPubkey,
+
+    /// The number of elements in the witness
+    ElementCount,
+
+    /// The witness element with the given index
+    Element(usize),
+
+    /// The transaction locktime
+    LockTime,
+}
+
+impl fmt::Display for LocatedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {} ({})", self.error, self.offset, self.path)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LocatedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl fmt::Display for ErrorPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(tx) = self.tx {
+            write!(f, "tx {} / ", tx)?;
+        }
+        match self.item {
+            Some(PathItem::Input(i)) => write!(f, "input {} / ", i)?,
+            Some(PathItem::Output(i)) => write!(f, "output {} / ", i)?,
+            Some(PathItem::Witness(i)) => write!(f, "witness {} / ", i)?,
+            None => (),
+        }
+        match self.field {
+            Field::Header => write!(f, "header"),
+            Field::TxCount => write!(f, "tx_count"),
+            Field::Version => write!(f, "version"),
+            Field::SegwitFlag => write!(f, "segwit_flag"),
+            Field::InputCount => write!(f, "input_count"),
+            Field::Prevout => write!(f, "prevout"),
+            Field::ScriptSig => write!(f, "script_sig"),
+            Field::Sequence => write!(f, "sequence"),
+            Field::OutputCount => write!(f, "output_count"),
+            Field::Value => write!(f, "value"),
+            Field::ScriptPubkey => write!(f, "script_pubkey"),
+            Field::ElementCount => write!(f, "element_count"),
+            Field::Element(i) => write!(f, "element {}", i),
+            Field::LockTime => write!(f, "locktime"),
+        }
+    }
+}
+
+/// Walks the data keeping track of the offset and path of the element being parsed
+struct Walker<'a> {
+    slice: &'a [u8],
+    offset: usize,
+    path: ErrorPath,
+}
+
+impl<'a> Walker<'a> {
+    fn new(slice: &'a [u8], tx: Option<usize>, field: Field) -> Self {
+        Walker {
+            slice,
+            offset: 0,
+            path: ErrorPath {
+                tx,
+                item: None,
+                field,
+            },
+        }
+    }
+
+    fn located(&self, error: Error) -> LocatedError {
+        LocatedError {
+            error,
+            offset: self.offset,
+            path: self.path,
+        }
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.slice[self.offset..]
+    }
+
+    /// Read `n` bytes as the given field
+    fn bytes(&mut self, field: Field, n: usize) -> Result<&'a [u8], LocatedError> {
+        self.path.field = field;
+        let read = read_slice(self.remaining(), n).map_err(|e| self.located(e))?;
+        self.offset += n;
+        Ok(read.parsed_owned())
+    }
+
+    /// Read a compact int as the given field
+    fn len(&mut self, field: Field) -> Result<usize, LocatedError> {
+        self.path.field = field;
+        let Len { consumed, n } =
+            crate::bsl::parse_len(self.remaining()).map_err(|e| self.located(e))?;
+        self.offset += consumed;
+        Ok(n as usize)
+    }
+
+    /// Read a compact int prefixed byte vector, like a script, as the given field
+    fn var_bytes(&mut self, field: Field) -> Result<&'a [u8], LocatedError> {
+        let start = self.offset;
+        let n = self.len(field)?;
+        let read = read_slice(self.remaining(), n).map_err(|e| LocatedError {
+            offset: start,
+            ..self.located(e)
+        })?;
+        self.offset += n;
+        Ok(read.parsed_owned())
+    }
+
+    fn block(&mut self) -> Result<(), LocatedError> {
+        self.path.field = Field::Header;
+        BlockHeader::parse(self.slice).map_err(|e| self.located(e))?;
+        self.offset += 80;
+        let total_txs = self.len(Field::TxCount)?;
+        for i in 0..total_txs {
+            self.path.tx = Some(i);
+            self.transaction()?;
+            self.path.item = None;
+        }
+        Ok(())
+    }
+
+    fn transaction(&mut self) -> Result<(), LocatedError> {
+        self.bytes(Field::Version, 4)?;
+        let mut total_inputs = self.len(Field::InputCount)?;
+        let segwit = total_inputs == 0;
+        if segwit {
+            let flag_offset = self.offset;
+            let flag = self.bytes(Field::SegwitFlag, 1)?[0];
+            if flag != 1 {
+                self.offset = flag_offset;
+                return Err(self.located(Error::UnknownSegwitFlag(flag)));
+            }
+            total_inputs = self.len(Field::InputCount)?;
+            self.inputs_outputs(total_inputs)?;
+
+            let mut all_empty = true;
+            for i in 0..total_inputs {
+                self.path.item = Some(PathItem::Witness(i));
+                let total_elements = self.len(Field::ElementCount)?;
+                for j in 0..total_elements {
+                    self.var_bytes(Field::Element(j))?;
+                }
+                all_empty &= total_elements == 0;
+            }
+            self.path.item = None;
+            if total_inputs > 0 && all_empty {
+                self.offset = flag_offset;
+                self.path.field = Field::SegwitFlag;
+                return Err(self.located(Error::SegwitFlagWithoutWitnesses));
+            }
+        } else {
+            self.inputs_outputs(total_inputs)?;
+        }
+        self.bytes(Field::LockTime, 4)?;
+        Ok(())
+    }
+
+    fn inputs_outputs(&mut self, total_inputs: usize) -> Result<(), LocatedError> {
+        for i in 0..total_inputs {
+            self.path.item = Some(PathItem::Input(i));
+            self.bytes(Field::Prevout, 36)?;
+            self.var_bytes(Field::ScriptSig)?;
+            self.bytes(Field::Sequence, 4)?;
+        }
+        self.path.item = None;
+        let total_outputs = self.len(Field::OutputCount)?;
+        for i in 0..total_outputs {
+            self.path.item = Some(PathItem::Output(i));
+            self.bytes(Field::Value, 8)?;
+            self.var_bytes(Field::ScriptPubkey)?;
+        }
+        self.path.item = None;
+        Ok(())
+    }
+}
+
+/// Locate `error`, returned by parsing `slice` as a block.
+pub(crate) fn locate_block(slice: &[u8], error: Error) -> LocatedError {
+    let mut walker = Walker::new(slice, None, Field::Header);
+    walker.block().err().unwrap_or(LocatedError {
+        error,
+        offset: 0,
+        path: walker.path,
+    })
+}
+
+/// Locate `error`, returned by parsing `slice` as a transaction.
+pub(crate) fn locate_transaction(slice: &[u8], error: Error) -> LocatedError {
+    let mut walker = Walker::new(slice, None, Field::Version);
+    walker.transaction().err().unwrap_or(LocatedError {
+        error,
+        offset: 0,
+        path: walker.path,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ErrorPath, Field, LocatedError, PathItem};
+    use crate::{
+        bsl::{Block, Transaction},
+        test_common::GENESIS_BLOCK,
+        Error, Parse,
+    };
+
+    #[test]
+    fn display() {
+        let err = LocatedError {
+            error: Error::Needed(10),
+            offset: 122,
+            path: ErrorPath {
+                tx: Some(1234),
+                item: Some(PathItem::Input(5)),
+                field: Field::ScriptSig,
+            },
+        };
+        assert_eq!(
+            err.to_string(),
+            "needed 10 more bytes at byte 122 (tx 1234 / input 5 / script_sig)"
+        );
+    }
+
+    #[test]
+    fn locate_genesis() {
+        let err = Block::parse_located(&GENESIS_BLOCK[..190]).unwrap_err();
+        assert_eq!(err.error, Error::Needed(10));
+        assert_eq!(err.offset, 122);
+        assert_eq!(err.path.to_string(), "tx 0 / input 0 / script_sig");
+
+        let err = Block::parse_located(&GENESIS_BLOCK[..200]).unwrap_err();
+        assert_eq!(err.error, Error::Needed(4));
+        assert_eq!(err.offset, 200);
+        assert_eq!(err.path.to_string(), "tx 0 / input 0 / sequence");
+
+        let err = Block::parse_located(&GENESIS_BLOCK[..50]).unwrap_err();
+        assert_eq!(err.error, Error::Needed(18));
+        assert_eq!(err.offset, 0);
+        assert_eq!(err.path.to_string(), "header");
+
+        assert!(Block::parse_located(&GENESIS_BLOCK[..]).is_ok());
+    }
+
+    #[test]
+    fn locate_transaction() {
+        // the segwit coinbase of the block, with a 32 bytes witness reserved value
+        let block = bitcoin_test_data::blocks::mainnet_702861();
+        let coinbase = Transaction::parse(&block[83..]).unwrap().parsed_owned();
+        let mut tx = coinbase.as_ref().to_vec();
+        assert!(Transaction::parse_located(&tx[..]).is_ok());
+
+        let err = Transaction::parse_located(&tx[..tx.len() - 36]).unwrap_err();
+        assert_eq!(err.error, Error::Needed(32));
+        assert_eq!(err.offset, tx.len() - 37);
+        assert_eq!(err.path.to_string(), "witness 0 / element 0");
+
+        tx[5] = 2;
+        let err = Transaction::parse_located(&tx[..]).unwrap_err();
+        assert_eq!(err.error, Error::UnknownSegwitFlag(2));
+        assert_eq!(err.offset, 5);
+        assert_eq!(err.path.to_string(), "segwit_flag");
+    }
+
+    #[test]
+    fn same_error_as_parse() {
+        let block = bitcoin_test_data::blocks::mainnet_702861();
+        for len in (0..block.len()).step_by(block.len() / 50) {
+            let slice = &block[..len];
+            let err = Block::parse(slice).unwrap_err();
+            let located = Block::parse_located(slice).unwrap_err();
+            assert_eq!(located.error, err);
+            assert!(located.offset <= len);
+            if len > 81 {
+                assert!(located.path.tx.is_some(), "{}", located);
+            }
+        }
+    }
+}
@@ -46,10 +46,10 @@ macro_rules! impl_number {
         pub struct $newtype([u8; $size]);
 
         impl<'a> Visit<'a> for $newtype {
-            fn visit<'b, V: crate::Visitor>(
+            fn visit_break<'b, B, V: crate::Visitor<B>>(
                 slice: &'a [u8],
                 _visit: &'b mut V,
-            ) -> SResult<'a, Self> {
+            ) -> crate::BResult<'a, Self, B> {
                 let p = read_slice(slice, $size)?;
                 let remaining = p.remaining();
                 let arr = p
@@ -0,0 +1,1114 @@
+//! Standardness policy, mirroring the Bitcoin Core rules a node applies before accepting a
+//! transaction in its mempool.
+//!
+//! Unlike the [`crate::consensus`] rules, policy rules are not enforced in blocks and may change
+//! between node versions. The limits here follow the Bitcoin Core defaults.
+
+use core::{fmt, ops::ControlFlow};
+
+use crate::{
+    bsl::{len_size, parse_len, Transaction, TxIn, TxIns, TxOut, TxOuts, Witness},
+    Descend, Parse, Subtree, Visit, Visitor,
+};
+
+/// The maximum weight of a standard transaction
+pub const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+
+/// The minimum size of a standard transaction serialized without witnesses
+pub const MIN_STANDARD_TX_NONWITNESS_SIZE: usize = 65;
+
+/// The maximum size of a standard input script sig
+pub const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+
+/// The maximum size of a standard `OP_RETURN` output script pubkey
+pub const MAX_OP_RETURN_RELAY: usize = 83;
+
+/// The fee rate in satoshi per 1000 virtual bytes used to compute the dust threshold
+pub const DUST_RELAY_TX_FEE: u64 = 3000;
+
+/// The maximum number of dust outputs in a standard transaction, which must then pay zero fee
+pub const MAX_DUST_OUTPUTS_PER_TX: usize = 1;
+
+/// The maximum version of a standard transaction
+pub const TX_MAX_STANDARD_VERSION: i32 = 3;
+
+/// The version of TRUC (topologically restricted until confirmation) transactions, BIP 431
+pub const TRUC_VERSION: i32 = 3;
+
+/// The maximum virtual size of a TRUC transaction
+pub const TRUC_MAX_VSIZE: u64 = 10_000;
+
+/// The maximum virtual size of a TRUC transaction spending an unconfirmed TRUC parent
+pub const TRUC_CHILD_MAX_VSIZE: u64 = 1_000;
+
+/// The maximum number of unconfirmed transactions in a TRUC cluster counting the transaction and
+/// its unconfirmed ancestors, so a TRUC transaction can have at most one unconfirmed parent
+pub const TRUC_ANCESTOR_LIMIT: usize = 2;
+
+/// The maximum number of unconfirmed transactions counting a TRUC transaction and its
+/// unconfirmed descendants, so a TRUC parent can have at most one unconfirmed child
+pub const TRUC_DESCENDANT_LIMIT: usize = 2;
+
+/// The maximum number of signature operations in a standard P2SH redeem script
+pub const MAX_P2SH_SIGOPS: usize = 15;
+
+/// The maximum number of stack items in a standard P2WSH input, excluding the witness script
+pub const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
+
+/// The maximum size of a stack item in a standard P2WSH input
+pub const MAX_STANDARD_P2WSH_STACK_ITEM_SIZE: usize = 80;
+
+/// The maximum size of a standard P2WSH witness script
+pub const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3600;
+
+/// The maximum size of a stack item in a standard tapscript input
+pub const MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE: usize = 80;
+
+const OP_0: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_RETURN: u8 = 0x6a;
+const OP_EQUAL: u8 = 0x87;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_HASH160: u8 = 0xa9;
+const OP_DUP: u8 = 0x76;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_CHECKSIGVERIFY: u8 = 0xad;
+const OP_CHECKMULTISIG: u8 = 0xae;
+const OP_CHECKMULTISIGVERIFY: u8 = 0xaf;
+
+const ANNEX_TAG: u8 = 0x50;
+const TAPROOT_LEAF_MASK: u8 = 0xfe;
+const TAPROOT_LEAF_TAPSCRIPT: u8 = 0xc0;
+
+/// The template of a script pubkey
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptType {
+    /// Not matching any known template
+    NonStandard,
+
+    /// Pay to public key
+    PubKey,
+
+    /// Pay to public key hash
+    PubKeyHash,
+
+    /// Pay to script hash, BIP 16
+    ScriptHash,
+
+    /// Bare multisig, `m` of `n` keys
+    Multisig {
+        /// The number of required signatures
+        m: u8,
+        /// The number of public keys
+        n: u8,
+    },
+
+    /// Unspendable `OP_RETURN` output carrying data
+    NullData,
+
+    /// Pay to witness public key hash, BIP 141
+    WitnessV0KeyHash,
+
+    /// Pay to witness script hash, BIP 141
+    WitnessV0ScriptHash,
+
+    /// Pay to taproot, BIP 341
+    WitnessV1Taproot,
+
+    /// Pay to anchor, the keyless output spendable by anyone used for fee bumping
+    Anchor,
+
+    /// A witness program of a version or size without defined semantics
+    WitnessUnknown,
+}
+
+impl ScriptType {
+    /// Classify the given script pubkey
+    pub fn from_script(script: &[u8]) -> ScriptType {
+        if script.len() == 23
+            && script[0] == OP_HASH160
+            && script[1] == 20
+            && script[22] == OP_EQUAL
+        {
+            return ScriptType::ScriptHash;
+        }
+        if let Some((version, program)) = witness_program(script) {
+            return match (version, program.len()) {
+                (0, 20) => ScriptType::WitnessV0KeyHash,
+                (0, 32) => ScriptType::WitnessV0ScriptHash,
+                (0, _) => ScriptType::NonStandard,
+                (1, 32) => ScriptType::WitnessV1Taproot,
+                (1, 2) if program == [0x4e, 0x73] => ScriptType::Anchor,
+                _ => ScriptType::WitnessUnknown,
+            };
+        }
+        if script.first() == Some(&OP_RETURN) && is_push_only(&script[1..]) {
+            return ScriptType::NullData;
+        }
+        match script {
+            [len @ (0x21 | 0x41), key @ .., OP_CHECKSIG]
+                if key.len() == *len as usize && valid_pubkey(key) =>
+            {
+                ScriptType::PubKey
+            }
+            [OP_DUP, OP_HASH160, 20, hash @ .., OP_EQUALVERIFY, OP_CHECKSIG]
+                if hash.len() == 20 =>
+            {
+                ScriptType::PubKeyHash
+            }
+            _ => multisig(script).unwrap_or(ScriptType::NonStandard),
+        }
+    }
+
+    /// Returns true if outputs with this script type are standard
+    pub fn is_standard(&self) -> bool {
+        match self {
+            ScriptType::NonStandard => false,
+            ScriptType::Multisig { m, n } => (1..=3).contains(n) && (1..=*n).contains(m),
+            _ => true,
+        }
+    }
+}
+
+/// Returns the witness version and program if the script is a witness program
+fn witness_program(script: &[u8]) -> Option<(u8, &[u8])> {
+    if !(4..=42).contains(&script.len()) || script[1] as usize != script.len() - 2 {
+        return None;
+    }
+    match script[0] {
+        OP_0 => Some((0, &script[2..])),
+        OP_1..=OP_16 => Some((script[0] - OP_1 + 1, &script[2..])),
+        _ => None,
+    }
+}
+
+fn valid_pubkey(key: &[u8]) -> bool {
+    match key.len() {
+        33 => key[0] == 0x02 || key[0] == 0x03,
+        65 => key[0] == 0x04 || key[0] == 0x06 || key[0] == 0x07,
+        _ => false,
+    }
+}
+
+fn multisig(script: &[u8]) -> Option<ScriptType> {
+    let (&last, rest) = script.split_last()?;
+    if last != OP_CHECKMULTISIG {
+        return None;
+    }
+    let (&first, rest) = rest.split_first()?;
+    let (&count, keys) = rest.split_last()?;
+    if !(OP_1..=OP_16).contains(&first) || !(OP_1..=OP_16).contains(&count) {
+        return None;
+    }
+    let mut n = 0u8;
+    for op in Ops(keys) {
+        match op {
+            Some((_, key)) if valid_pubkey(key) => n += 1,
+            _ => return None,
+        }
+    }
+    let m = first - OP_1 + 1;
+    if n == count - OP_1 + 1 && m <= n {
+        Some(ScriptType::Multisig { m, n })
+    } else {
+        None
+    }
+}
+
+/// Iterates the opcodes in a script with the data they push, yielding `None` and stopping if a push
+/// goes beyond the end of the script
+struct Ops<'s>(&'s [u8]);
+
+impl<'s> Iterator for Ops<'s> {
+    type Item = Option<(u8, &'s [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&opcode, rest) = self.0.split_first()?;
+        let (len, rest) = match opcode {
+            0x01..=0x4b => (opcode as usize, rest),
+            OP_PUSHDATA1 | OP_PUSHDATA2 | OP_PUSHDATA4 => {
+                let size = match opcode {
+                    OP_PUSHDATA1 => 1,
+                    OP_PUSHDATA2 => 2,
+                    _ => 4,
+                };
+                if rest.len() < size {
+                    self.0 = &[];
+                    return Some(None);
+                }
+                let mut len = [0u8; 4];
+                len[..size].copy_from_slice(&rest[..size]);
+                (u32::from_le_bytes(len) as usize, &rest[size..])
+            }
+            _ => (0, rest),
+        };
+        if rest.len() < len {
+            self.0 = &[];
+            return Some(None);
+        }
+        let (data, rest) = rest.split_at(len);
+        self.0 = rest;
+        Some(Some((opcode, data)))
+    }
+}
+
+/// Returns true if the script contains only push opcodes, as required for standard script sigs
+pub fn is_push_only(script: &[u8]) -> bool {
+    Ops(script).all(|op| matches!(op, Some((opcode, _)) if opcode <= OP_16))
+}
+
+/// Counts the signature operations in the script, counting multisig keys precisely when preceded by
+/// `OP_1` to `OP_16`, like it's done for P2SH redeem scripts
+fn sigops(script: &[u8]) -> usize {
+    let mut count = 0;
+    let mut last = 0xffu8;
+    for op in Ops(script) {
+        let opcode = match op {
+            Some((opcode, _)) => opcode,
+            None => break,
+        };
+        match opcode {
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => count += 1,
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => match last {
+                OP_1..=OP_16 => count += (last - OP_1 + 1) as usize,
+                _ => count += 20,
+            },
+            _ => (),
+        }
+        last = opcode;
+    }
+    count
+}
+
+/// Returns the data pushed by the last opcode of a push only script
+fn last_push(script: &[u8]) -> Option<&[u8]> {
+    let mut last = None;
+    for op in Ops(script) {
+        match op {
+            Some((opcode, data)) if opcode <= OP_16 => last = Some(data),
+            _ => return None,
+        }
+    }
+    last
+}
+
+/// Returns the value under which an output with the given script pubkey is dust, given the
+/// dust relay fee rate in satoshi per 1000 virtual bytes.
+///
+/// An output is dust if spending it would cost more than its value at the given fee rate.
+/// Unspendable outputs have a zero threshold.
+pub fn dust_threshold(script_pubkey: &[u8], dust_relay_fee: u64) -> u64 {
+    if script_pubkey.first() == Some(&OP_RETURN) || script_pubkey.len() > 10_000 {
+        return 0;
+    }
+    let output_size = 8 + len_size(script_pubkey.len() as u64) + script_pubkey.len();
+    // outpoint, script sig length, sequence and the size of a typical script sig or witness
+    let input_size = if witness_program(script_pubkey).is_some() {
+        32 + 4 + 1 + 107 / 4 + 4
+    } else {
+        32 + 4 + 1 + 107 + 4
+    };
+    (output_size + input_size) as u64 * dust_relay_fee / 1000
+}
+
+/// Returns true if the output value is under the [`dust_threshold`] at [`DUST_RELAY_TX_FEE`]
+pub fn is_dust(tx_out: &TxOut) -> bool {
+    tx_out.value() < dust_threshold(tx_out.script_pubkey(), DUST_RELAY_TX_FEE)
+}
+
+/// The reasons a transaction is not standard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyError {
+    /// The version is not between 1 and [`TX_MAX_STANDARD_VERSION`]
+    Version(i32),
+
+    /// The weight, which is greater than [`MAX_STANDARD_TX_WEIGHT`]
+    Weight(u64),
+
+    /// The size without witnesses, which is less than [`MIN_STANDARD_TX_NONWITNESS_SIZE`]
+    TooSmall(usize),
+
+    /// The script sig of the input with the given index is bigger than
+    /// [`MAX_STANDARD_SCRIPTSIG_SIZE`]
+    ScriptSigSize(usize),
+
+    /// The script sig of the input with the given index contains non push opcodes
+    ScriptSigNotPushOnly(usize),
+
+    /// The script pubkey of the output with the given index is not standard, including
+    /// `OP_RETURN` outputs bigger than [`MAX_OP_RETURN_RELAY`]
+    ScriptPubkey(usize),
+
+    /// More than one `OP_RETURN` output
+    MultiOpReturn,
+
+    /// More than [`MAX_DUST_OUTPUTS_PER_TX`] dust outputs
+    Dust,
+
+    /// The virtual size of a TRUC transaction, which is greater than [`TRUC_MAX_VSIZE`]
+    TrucTooBig(u64),
+
+    /// The virtual size of a TRUC transaction with an unconfirmed parent, which is greater than
+    /// [`TRUC_CHILD_MAX_VSIZE`]
+    TrucChildTooBig(u64),
+
+    /// A TRUC transaction spends the unconfirmed non-TRUC parent with the given index
+    TrucSpendsNonTruc(usize),
+
+    /// A non-TRUC transaction spends the unconfirmed TRUC parent with the given index
+    NonTrucSpendsTruc(usize),
+
+    /// A TRUC transaction would have more unconfirmed ancestors than allowed by
+    /// [`TRUC_ANCESTOR_LIMIT`]
+    TrucTooManyAncestors,
+
+    /// The unconfirmed TRUC parent with the given index already has the child allowed by
+    /// [`TRUC_DESCENDANT_LIMIT`], which may still be replaced by sibling eviction
+    TrucTooManyDescendants(usize),
+
+    /// The transaction has a dust output but pays a fee, so there is no incentive to spend the dust
+    EphemeralDustFee,
+
+    /// The input with the given index spends a non standard output, or a P2SH output with a non
+    /// standard redeem script
+    NonStandardInput(usize),
+
+    /// The witness of the input with the given index is not standard
+    NonStandardWitness(usize),
+}
+
+impl PolicyError {
+    /// The reject reason Bitcoin Core gives for this error
+    pub fn reason(&self) -> &'static str {
+        match self {
+            PolicyError::Version(_) => "version",
+            PolicyError::Weight(_) => "tx-size",
+            PolicyError::TooSmall(_) => "tx-size-small",
+            PolicyError::ScriptSigSize(_) => "scriptsig-size",
+            PolicyError::ScriptSigNotPushOnly(_) => "scriptsig-not-pushonly",
+            PolicyError::ScriptPubkey(_) => "scriptpubkey",
+            PolicyError::MultiOpReturn => "multi-op-return",
+            PolicyError::Dust => "dust",
+            PolicyError::TrucTooBig(_)
+            | PolicyError::TrucChildTooBig(_)
+            | PolicyError::TrucSpendsNonTruc(_)
+            | PolicyError::NonTrucSpendsTruc(_)
+            | PolicyError::TrucTooManyAncestors
+            | PolicyError::TrucTooManyDescendants(_) => "TRUC-violation",
+            PolicyError::EphemeralDustFee => "dust",
+            PolicyError::NonStandardInput(_) => "bad-txns-nonstandard-inputs",
+            PolicyError::NonStandardWitness(_) => "bad-witness-nonstandard",
+        }
+    }
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::Version(version) => write!(f, "{} {}", self.reason(), version),
+            PolicyError::Weight(weight) => write!(f, "{} weight {}", self.reason(), weight),
+            PolicyError::TooSmall(size) => write!(f, "{} size {}", self.reason(), size),
+            PolicyError::TrucTooBig(vsize) => write!(f, "{} vsize {}", self.reason(), vsize),
+            PolicyError::TrucChildTooBig(vsize) => {
+                write!(f, "{} child vsize {}", self.reason(), vsize)
+            }
+            PolicyError::TrucSpendsNonTruc(i) => {
+                write!(f, "{} spends non-TRUC parent {}", self.reason(), i)
+            }
+            PolicyError::NonTrucSpendsTruc(i) => {
+                write!(f, "{} non-TRUC spends TRUC parent {}", self.reason(), i)
+            }
+            PolicyError::TrucTooManyAncestors => write!(f, "{} too many ancestors", self.reason()),
+            PolicyError::TrucTooManyDescendants(i) => {
+                write!(f, "{} parent {} has too many descendants", self.reason(), i)
+            }
+            PolicyError::ScriptSigSize(i)
+            | PolicyError::ScriptSigNotPushOnly(i)
+            | PolicyError::NonStandardInput(i)
+            | PolicyError::NonStandardWitness(i) => write!(f, "{} input {}", self.reason(), i),
+            PolicyError::ScriptPubkey(i) => write!(f, "{} output {}", self.reason(), i),
+            PolicyError::MultiOpReturn | PolicyError::Dust | PolicyError::EphemeralDustFee => {
+                write!(f, "{}", self.reason())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PolicyError {}
+
+/// Checks the inputs script sigs and the outputs of a transaction
+struct Standard {
+    error: Option<PolicyError>,
+    op_returns: usize,
+    dust: usize,
+}
+
+impl Visitor for Standard {
+    fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<(), Descend> {
+        match subtree {
+            Subtree::Witnesses => ControlFlow::Continue(Descend::Skip),
+            _ => ControlFlow::Continue(Descend::Visit),
+        }
+    }
+    fn visit_tx_in(&mut self, vin: usize, tx_in: &TxIn) -> ControlFlow<()> {
+        let script_sig = tx_in.script_sig();
+        if script_sig.len() > MAX_STANDARD_SCRIPTSIG_SIZE {
+            self.error = Some(PolicyError::ScriptSigSize(vin));
+            return ControlFlow::Break(());
+        }
+        if !is_push_only(script_sig) {
+            self.error = Some(PolicyError::ScriptSigNotPushOnly(vin));
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    }
+    fn visit_tx_out(&mut self, vout: usize, tx_out: &TxOut) -> ControlFlow<()> {
+        let script = tx_out.script_pubkey();
+        let script_type = ScriptType::from_script(script);
+        if !script_type.is_standard()
+            || (script_type == ScriptType::NullData && script.len() > MAX_OP_RETURN_RELAY)
+        {
+            self.error = Some(PolicyError::ScriptPubkey(vout));
+            return ControlFlow::Break(());
+        }
+        if script_type == ScriptType::NullData {
+            self.op_returns += 1;
+        } else if is_dust(tx_out) {
+            self.dust += 1;
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+pub(crate) fn check_standard(tx: &Transaction) -> Result<(), PolicyError> {
+    let version = tx.version();
+    if !(1..=TX_MAX_STANDARD_VERSION).contains(&version) {
+        return Err(PolicyError::Version(version));
+    }
+    let weight = tx.weight();
+    if weight > MAX_STANDARD_TX_WEIGHT {
+        return Err(PolicyError::Weight(weight));
+    }
+    let mut standard = Standard {
+        error: None,
+        op_returns: 0,
+        dust: 0,
+    };
+    let _ = Transaction::visit(tx.as_ref(), &mut standard);
+    if let Some(error) = standard.error {
+        return Err(error);
+    }
+    if standard.op_returns > 1 {
+        return Err(PolicyError::MultiOpReturn);
+    }
+    if standard.dust > MAX_DUST_OUTPUTS_PER_TX {
+        return Err(PolicyError::Dust);
+    }
+    if tx.base_size() < MIN_STANDARD_TX_NONWITNESS_SIZE {
+        return Err(PolicyError::TooSmall(tx.base_size()));
+    }
+    if version == TRUC_VERSION {
+        let vsize = tx.vsize();
+        if vsize > TRUC_MAX_VSIZE {
+            return Err(PolicyError::TrucTooBig(vsize));
+        }
+    }
+    Ok(())
+}
+
+/// An unconfirmed transaction spent by the transaction checked with
+/// [`Transaction::check_truc`], as known by the mempool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnconfirmedParent {
+    /// The version of the parent
+    pub version: i32,
+
+    /// The number of unconfirmed ancestors of the parent, excluding itself
+    pub ancestors: usize,
+
+    /// The number of unconfirmed descendants of the parent, excluding itself, the checked
+    /// transaction and the transactions it replaces
+    pub descendants: usize,
+}
+
+pub(crate) fn check_truc(
+    tx: &Transaction,
+    unconfirmed_parents: &[UnconfirmedParent],
+) -> Result<(), PolicyError> {
+    let truc = tx.version() == TRUC_VERSION;
+    for (i, parent) in unconfirmed_parents.iter().enumerate() {
+        match (truc, parent.version == TRUC_VERSION) {
+            (false, true) => return Err(PolicyError::NonTrucSpendsTruc(i)),
+            (true, false) => return Err(PolicyError::TrucSpendsNonTruc(i)),
+            _ => (),
+        }
+    }
+    if !truc {
+        return Ok(());
+    }
+    let vsize = tx.vsize();
+    if vsize > TRUC_MAX_VSIZE {
+        return Err(PolicyError::TrucTooBig(vsize));
+    }
+    let ancestors: usize = unconfirmed_parents.iter().map(|p| 1 + p.ancestors).sum();
+    if ancestors + 1 > TRUC_ANCESTOR_LIMIT {
+        return Err(PolicyError::TrucTooManyAncestors);
+    }
+    if let Some(parent) = unconfirmed_parents.first() {
+        if vsize > TRUC_CHILD_MAX_VSIZE {
+            return Err(PolicyError::TrucChildTooBig(vsize));
+        }
+        // counting the parent itself and the checked transaction
+        if parent.descendants + 2 > TRUC_DESCENDANT_LIMIT {
+            return Err(PolicyError::TrucTooManyDescendants(0));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn check_ephemeral(tx: &Transaction, fee: u64) -> Result<(), PolicyError> {
+    struct HasDust(bool);
+    impl Visitor for HasDust {
+        fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<(), Descend> {
+            match subtree {
+                Subtree::TxOuts => ControlFlow::Continue(Descend::Visit),
+                _ => ControlFlow::Continue(Descend::Skip),
+            }
+        }
+        fn visit_tx_out(&mut self, _vout: usize, tx_out: &TxOut) -> ControlFlow<()> {
+            self.0 |= is_dust(tx_out);
+            ControlFlow::Continue(())
+        }
+    }
+    let mut has_dust = HasDust(false);
+    let _ = Transaction::visit(tx.as_ref(), &mut has_dust);
+    if fee != 0 && has_dust.0 {
+        return Err(PolicyError::EphemeralDustFee);
+    }
+    Ok(())
+}
+
+/// Iterates the elements of a witness
+fn witness_elements(slice: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut offset = parse_len(slice).expect("parsed witness").consumed();
+    core::iter::from_fn(move || {
+        let len = parse_len(slice.get(offset..)?).ok()?;
+        let element = &slice[offset + len.consumed()..offset + len.slice_len()];
+        offset += len.slice_len();
+        Some(element)
+    })
+}
+
+/// The script checked for the witness rules: the redeem script of a P2SH prevout, otherwise the
+/// prevout script itself. Returns also whether it's a P2SH redeem script.
+fn input_script<'s>(tx_in: &TxIn<'s>, prevout_script: &'s [u8]) -> Option<(&'s [u8], bool)> {
+    match ScriptType::from_script(prevout_script) {
+        ScriptType::ScriptHash => last_push(tx_in.script_sig()).map(|script| (script, true)),
+        _ => Some((prevout_script, false)),
+    }
+}
+
+/// The input rules, as `AreInputsStandard` in Bitcoin Core
+fn check_input(vin: usize, tx_in: &TxIn, prevout_script: &[u8]) -> Result<(), PolicyError> {
+    match ScriptType::from_script(prevout_script) {
+        ScriptType::NonStandard | ScriptType::WitnessUnknown => {
+            Err(PolicyError::NonStandardInput(vin))
+        }
+        ScriptType::ScriptHash => match input_script(tx_in, prevout_script) {
+            Some((redeem_script, _)) if sigops(redeem_script) <= MAX_P2SH_SIGOPS => Ok(()),
+            _ => Err(PolicyError::NonStandardInput(vin)),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// The witness rules, as `IsWitnessStandard` in Bitcoin Core
+fn check_witness(
+    vin: usize,
+    tx_in: &TxIn,
+    witness: &Witness,
+    prevout_script: &[u8],
+) -> Result<(), PolicyError> {
+    if witness.is_empty() {
+        return Ok(());
+    }
+    let nonstandard = Err(PolicyError::NonStandardWitness(vin));
+    if ScriptType::from_script(prevout_script) == ScriptType::Anchor {
+        return nonstandard; // anchors are spent without witness
+    }
+    let (script, p2sh) = match input_script(tx_in, prevout_script) {
+        Some(script) => script,
+        None => return nonstandard,
+    };
+    let count = witness_elements(witness.as_ref()).count();
+    let from_end = |i: usize| {
+        witness_elements(witness.as_ref())
+            .nth(count - 1 - i)
+            .unwrap_or(&[])
+    };
+    let max_len_excluding_last = |k: usize| {
+        witness_elements(witness.as_ref())
+            .take(count.saturating_sub(k))
+            .map(<[u8]>::len)
+            .max()
+            .unwrap_or(0)
+    };
+
+    match witness_program(script) {
+        None => nonstandard,
+        Some((0, program)) if program.len() == 32 => {
+            if from_end(0).len() > MAX_STANDARD_P2WSH_SCRIPT_SIZE
+                || count - 1 > MAX_STANDARD_P2WSH_STACK_ITEMS
+                || max_len_excluding_last(1) > MAX_STANDARD_P2WSH_STACK_ITEM_SIZE
+            {
+                return nonstandard;
+            }
+            Ok(())
+        }
+        Some((1, program)) if program.len() == 32 && !p2sh => {
+            if count >= 2 && from_end(0).first() == Some(&ANNEX_TAG) {
+                return nonstandard; // the annex is reserved for future extensions
+            }
+            if count >= 2 {
+                // script path spend, the control block is last and the script before it
+                let control_block = from_end(0);
+                if control_block.is_empty() {
+                    return nonstandard;
+                }
+                if control_block[0] & TAPROOT_LEAF_MASK == TAPROOT_LEAF_TAPSCRIPT
+                    && max_len_excluding_last(2) > MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE
+                {
+                    return nonstandard;
+                }
+            }
+            Ok(())
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// Calls `check` for every input which is not a coinbase, with its witness if the transaction has
+/// witnesses, and its prevout script
+fn for_each_input<F>(
+    tx: &Transaction,
+    prevout_scripts: &[&[u8]],
+    mut check: F,
+) -> Result<(), PolicyError>
+where
+    F: FnMut(usize, &TxIn, Option<&Witness>, &[u8]) -> Result<(), PolicyError>,
+{
+    let slice = tx.as_ref();
+    let (inputs, witnesses) = if tx.has_witness() {
+        let inputs = TxIns::parse(&slice[6..]).expect("parsed transaction");
+        let outputs = TxOuts::parse(inputs.remaining()).expect("parsed transaction");
+        (inputs, Some(outputs.remaining()))
+    } else {
+        (TxIns::parse(&slice[4..]).expect("parsed transaction"), None)
+    };
+    let inputs = inputs.parsed();
+    let mut remaining_inputs = &inputs.as_ref()[len_size(inputs.n() as u64)..];
+    let mut remaining_witnesses = witnesses;
+    for vin in 0..inputs.n() {
+        let tx_in = TxIn::parse(remaining_inputs).expect("parsed transaction");
+        remaining_inputs = tx_in.remaining();
+        let witness = match remaining_witnesses {
+            Some(remaining) => {
+                let witness = Witness::parse(remaining).expect("parsed transaction");
+                remaining_witnesses = Some(witness.remaining());
+                Some(witness.parsed_owned())
+            }
+            None => None,
+        };
+        if tx_in.parsed().prevout().is_null() {
+            continue; // coinbase
+        }
+        let prevout_script = prevout_scripts
+            .get(vin)
+            .ok_or(PolicyError::NonStandardInput(vin))?;
+        check(vin, tx_in.parsed(), witness.as_ref(), prevout_script)?;
+    }
+    Ok(())
+}
+
+/// Checks the input rules on every input, then the witness rules, in the same order as Bitcoin
+/// Core so that the same reason is reported
+pub(crate) fn check_standard_inputs(
+    tx: &Transaction,
+    prevout_scripts: &[&[u8]],
+) -> Result<(), PolicyError> {
+    for_each_input(tx, prevout_scripts, |vin, tx_in, _, prevout_script| {
+        check_input(vin, tx_in, prevout_script)
+    })?;
+    if !tx.has_witness() {
+        return Ok(());
+    }
+    for_each_input(
+        tx,
+        prevout_scripts,
+        |vin, tx_in, witness, prevout_script| match witness {
+            Some(witness) => check_witness(vin, tx_in, witness, prevout_script),
+            None => Ok(()),
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use core::ops::ControlFlow;
+
+    use hex_lit::hex;
+
+    use super::{dust_threshold, is_push_only, sigops, PolicyError, ScriptType, DUST_RELAY_TX_FEE};
+    use crate::{bsl::Transaction, Parse, Visit, Visitor};
+
+    const P2PKH: [u8; 25] = hex!("76a91489abcdefabbaabbaabbaabbaabbaabbaabbaabba88ac");
+    const P2SH: [u8; 23] = hex!("a91489abcdefabbaabbaabbaabbaabbaabbaabbaabba87");
+    const P2WPKH: [u8; 22] = hex!("001489abcdefabbaabbaabbaabbaabbaabbaabbaabba");
+    const P2WSH: [u8; 34] =
+        hex!("002089abcdefabbaabbaabbaabbaabbaabbaabbaabbaabbaabbaabbaabbaabbaabba");
+    const P2TR: [u8; 34] =
+        hex!("512089abcdefabbaabbaabbaabbaabbaabbaabbaabbaabbaabbaabbaabbaabbaabba");
+    const ANCHOR: [u8; 4] = hex!("51024e73");
+    const PUBKEY: [u8; 33] =
+        hex!("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+
+    /// Serialize a transaction, with witnesses if any input has one
+    fn tx(version: i32, inputs: &[(&[u8], &[&[u8]])], outputs: &[(u64, &[u8])]) -> Vec<u8> {
+        let segwit = inputs.iter().any(|(_, w)| !w.is_empty());
+        let mut tx = version.to_le_bytes().to_vec();
+        if segwit {
+            tx.extend_from_slice(&[0, 1]);
+        }
+        tx.push(inputs.len() as u8);
+        for (i, (script_sig, _)) in inputs.iter().enumerate() {
+            tx.extend_from_slice(&[i as u8 + 1; 36]);
+            tx.push(script_sig.len() as u8);
+            tx.extend_from_slice(script_sig);
+            tx.extend_from_slice(&[0xff; 4]);
+        }
+        tx.push(outputs.len() as u8);
+        for (value, script) in outputs {
+            tx.extend_from_slice(&value.to_le_bytes());
+            tx.push(script.len() as u8);
+            tx.extend_from_slice(script);
+        }
+        if segwit {
+            for (_, witness) in inputs {
+                tx.push(witness.len() as u8);
+                for element in witness.iter() {
+                    tx.push(element.len() as u8);
+                    tx.extend_from_slice(element);
+                }
+            }
+        }
+        tx.extend_from_slice(&[0u8; 4]);
+        tx
+    }
+
+    fn standard(bytes: &[u8]) -> Result<(), PolicyError> {
+        Transaction::parse(bytes).unwrap().parsed().check_standard()
+    }
+
+    fn standard_inputs(bytes: &[u8], prevouts: &[&[u8]]) -> Result<(), PolicyError> {
+        Transaction::parse(bytes)
+            .unwrap()
+            .parsed()
+            .check_standard_inputs(prevouts)
+    }
+
+    #[test]
+    fn script_type() {
+        let mut multisig = vec![0x51];
+        for _ in 0..2 {
+            multisig.push(33);
+            multisig.extend_from_slice(&PUBKEY);
+        }
+        multisig.extend_from_slice(&[0x52, 0xae]);
+        let mut p2pk = vec![33];
+        p2pk.extend_from_slice(&PUBKEY);
+        p2pk.push(0xac);
+
+        let check = |script: &[u8], expected| assert_eq!(ScriptType::from_script(script), expected);
+        check(&P2PKH, ScriptType::PubKeyHash);
+        check(&P2SH, ScriptType::ScriptHash);
+        check(&P2WPKH, ScriptType::WitnessV0KeyHash);
+        check(&P2WSH, ScriptType::WitnessV0ScriptHash);
+        check(&P2TR, ScriptType::WitnessV1Taproot);
+        check(&ANCHOR, ScriptType::Anchor);
+        check(&hex!("5202abcd"), ScriptType::WitnessUnknown);
+        check(&hex!("0003abcdef"), ScriptType::NonStandard);
+        check(&hex!("6a0568656c6c6f"), ScriptType::NullData);
+        check(&hex!("6a"), ScriptType::NullData);
+        check(&hex!("6aac"), ScriptType::NonStandard);
+        check(&p2pk, ScriptType::PubKey);
+        check(&multisig, ScriptType::Multisig { m: 1, n: 2 });
+        check(&hex!("51ae"), ScriptType::NonStandard);
+        check(&[], ScriptType::NonStandard);
+
+        assert!(ScriptType::Multisig { m: 1, n: 2 }.is_standard());
+        assert!(!ScriptType::Multisig { m: 2, n: 4 }.is_standard());
+        assert!(!ScriptType::NonStandard.is_standard());
+    }
+
+    #[test]
+    fn dust() {
+        assert_eq!(dust_threshold(&P2PKH, DUST_RELAY_TX_FEE), 546);
+        assert_eq!(dust_threshold(&P2SH, DUST_RELAY_TX_FEE), 540);
+        assert_eq!(dust_threshold(&P2WPKH, DUST_RELAY_TX_FEE), 294);
+        assert_eq!(dust_threshold(&P2TR, DUST_RELAY_TX_FEE), 330);
+        assert_eq!(dust_threshold(&ANCHOR, DUST_RELAY_TX_FEE), 240);
+        assert_eq!(dust_threshold(&hex!("6a"), DUST_RELAY_TX_FEE), 0);
+    }
+
+    #[test]
+    fn script_ops() {
+        assert!(is_push_only(&[]));
+        assert!(is_push_only(&hex!("0001014c01004d01004e000000005160")));
+        assert!(!is_push_only(&hex!("61")));
+        assert!(!is_push_only(&hex!("02ff")));
+        assert!(!is_push_only(&hex!("4d01")));
+
+        assert_eq!(sigops(&hex!("acad")), 2);
+        assert_eq!(sigops(&hex!("53ae")), 3);
+        assert_eq!(sigops(&hex!("ae")), 20);
+        assert_eq!(sigops(&hex!("0201acac")), 1);
+    }
+
+    #[test]
+    fn check_standard() {
+        let input: (&[u8], &[&[u8]]) = (&[], &[]);
+        let ok = tx(2, &[input], &[(10_000, &P2WPKH), (10_000, &P2TR)]);
+        assert_eq!(standard(&ok), Ok(()));
+
+        let tx_version = |v| tx(v, &[input], &[(10_000, &P2WPKH), (10_000, &P2TR)]);
+        assert_eq!(standard(&tx_version(0)), Err(PolicyError::Version(0)));
+        assert_eq!(standard(&tx_version(3)), Ok(()));
+        assert_eq!(standard(&tx_version(4)), Err(PolicyError::Version(4)));
+
+        let small = tx(2, &[input], &[(0, &hex!("6a"))]);
+        assert_eq!(standard(&small), Err(PolicyError::TooSmall(61)));
+
+        let script_sig = tx(2, &[(&hex!("5161"), &[])], &[(10_000, &P2TR)]);
+        assert_eq!(
+            standard(&script_sig),
+            Err(PolicyError::ScriptSigNotPushOnly(0))
+        );
+
+        let nonstandard = tx(2, &[input], &[(10_000, &P2TR), (10_000, &hex!("61"))]);
+        assert_eq!(standard(&nonstandard), Err(PolicyError::ScriptPubkey(1)));
+
+        let big_op_return = [0x6a, 0x4c, 82].iter().chain(&[0u8; 82]).copied();
+        let big_op_return: Vec<u8> = big_op_return.collect();
+        let op_return = tx(2, &[input], &[(10_000, &P2TR), (0, &big_op_return[..83])]);
+        assert_eq!(standard(&op_return), Err(PolicyError::ScriptPubkey(1)));
+        let op_return = tx(2, &[input], &[(10_000, &P2TR), (0, &hex!("6a01ff"))]);
+        assert_eq!(standard(&op_return), Ok(()));
+        let two = tx(2, &[input], &[(0, &hex!("6a01ff")), (0, &hex!("6a01ff"))]);
+        assert_eq!(standard(&two), Err(PolicyError::MultiOpReturn));
+
+        let one_dust = tx(2, &[input], &[(10_000, &P2TR), (0, &ANCHOR)]);
+        assert_eq!(standard(&one_dust), Ok(()));
+        let two_dust = tx(2, &[input], &[(293, &P2WPKH), (0, &ANCHOR)]);
+        assert_eq!(standard(&two_dust), Err(PolicyError::Dust));
+
+        let one_dust = Transaction::parse(&one_dust).unwrap();
+        assert_eq!(one_dust.parsed().check_ephemeral(0), Ok(()));
+        assert_eq!(
+            one_dust.parsed().check_ephemeral(1),
+            Err(PolicyError::EphemeralDustFee)
+        );
+        let no_dust = Transaction::parse(&ok).unwrap();
+        assert_eq!(no_dust.parsed().check_ephemeral(1), Ok(()));
+    }
+
+    #[test]
+    fn check_truc() {
+        let outputs: Vec<(u64, &[u8])> = (0..240).map(|_| (1000, &P2TR[..])).collect();
+        let big = tx(3, &[(&[], &[])], &outputs);
+        let vsize = big.len() as u64;
+        assert!(vsize > super::TRUC_MAX_VSIZE);
+        assert_eq!(standard(&big), Err(PolicyError::TrucTooBig(vsize)));
+    }
+
+    #[test]
+    fn check_truc_parents() {
+        use super::{UnconfirmedParent, TRUC_CHILD_MAX_VSIZE};
+        let truc = |version, outputs: usize| {
+            let outputs: Vec<(u64, &[u8])> = (0..outputs).map(|_| (1000, &P2TR[..])).collect();
+            tx(version, &[(&[], &[])], &outputs)
+        };
+        let check = |bytes: &[u8], parents: &[UnconfirmedParent]| {
+            Transaction::parse(bytes)
+                .unwrap()
+                .parsed()
+                .check_truc(parents)
+        };
+        let parent = |version| UnconfirmedParent {
+            version,
+            ancestors: 0,
+            descendants: 0,
+        };
+
+        let child = truc(3, 2);
+        assert_eq!(check(&child, &[]), Ok(()));
+        assert_eq!(check(&child, &[parent(3)]), Ok(()));
+        assert_eq!(
+            check(&child, &[parent(2)]),
+            Err(PolicyError::TrucSpendsNonTruc(0))
+        );
+        assert_eq!(
+            check(&truc(2, 2), &[parent(2), parent(3)]),
+            Err(PolicyError::NonTrucSpendsTruc(1))
+        );
+        assert_eq!(check(&truc(2, 2), &[parent(2), parent(2)]), Ok(()));
+
+        assert_eq!(
+            check(&child, &[parent(3), parent(3)]),
+            Err(PolicyError::TrucTooManyAncestors)
+        );
+        let grandchild = UnconfirmedParent {
+            ancestors: 1,
+            ..parent(3)
+        };
+        assert_eq!(
+            check(&child, &[grandchild]),
+            Err(PolicyError::TrucTooManyAncestors)
+        );
+        let sibling = UnconfirmedParent {
+            descendants: 1,
+            ..parent(3)
+        };
+        assert_eq!(
+            check(&child, &[sibling]),
+            Err(PolicyError::TrucTooManyDescendants(0))
+        );
+        assert_eq!(
+            PolicyError::TrucTooManyDescendants(0).reason(),
+            "TRUC-violation"
+        );
+
+        let big_child = truc(3, 40);
+        let vsize = big_child.len() as u64;
+        assert!(vsize > TRUC_CHILD_MAX_VSIZE);
+        assert_eq!(check(&big_child, &[]), Ok(()));
+        assert_eq!(
+            check(&big_child, &[parent(3)]),
+            Err(PolicyError::TrucChildTooBig(vsize))
+        );
+
+        let big = truc(3, 240);
+        let vsize = big.len() as u64;
+        assert_eq!(check(&big, &[]), Err(PolicyError::TrucTooBig(vsize)));
+    }
+
+    #[test]
+    fn check_standard_inputs() {
+        let outputs: &[(u64, &[u8])] = &[(10_000, &P2TR)];
+
+        let legacy = tx(2, &[(&[], &[])], outputs);
+        assert_eq!(standard_inputs(&legacy, &[&P2PKH]), Ok(()));
+        assert_eq!(
+            standard_inputs(&legacy, &[&hex!("5202abcd")]),
+            Err(PolicyError::NonStandardInput(0))
+        );
+        assert_eq!(
+            standard_inputs(&legacy, &[]),
+            Err(PolicyError::NonStandardInput(0))
+        );
+
+        // P2SH with a redeem script of 16 checksig
+        let mut script_sig = vec![16];
+        script_sig.extend_from_slice(&[0xac; 16]);
+        let p2sh = tx(2, &[(&script_sig, &[])], outputs);
+        assert_eq!(
+            standard_inputs(&p2sh, &[&P2SH]),
+            Err(PolicyError::NonStandardInput(0))
+        );
+        script_sig[0] = 15;
+        let p2sh = tx(2, &[(&script_sig[..16], &[])], outputs);
+        assert_eq!(standard_inputs(&p2sh, &[&P2SH]), Ok(()));
+
+        let witness: &[&[u8]] = &[&[1; 72], &PUBKEY];
+        let p2wpkh = tx(2, &[(&[], witness)], outputs);
+        assert_eq!(standard_inputs(&p2wpkh, &[&P2WPKH]), Ok(()));
+        assert_eq!(
+            standard_inputs(&p2wpkh, &[&P2PKH]),
+            Err(PolicyError::NonStandardWitness(0))
+        );
+        assert_eq!(
+            standard_inputs(&p2wpkh, &[&ANCHOR]),
+            Err(PolicyError::NonStandardWitness(0))
+        );
+
+        let p2wsh = tx(2, &[(&[], &[&[1; 80], &[0xac; 200]])], outputs);
+        assert_eq!(standard_inputs(&p2wsh, &[&P2WSH]), Ok(()));
+        let p2wsh = tx(2, &[(&[], &[&[1; 81], &[0xac; 200]])], outputs);
+        assert_eq!(
+            standard_inputs(&p2wsh, &[&P2WSH]),
+            Err(PolicyError::NonStandardWitness(0))
+        );
+
+        let key_path = tx(2, &[(&[], &[&[1; 64]])], outputs);
+        assert_eq!(standard_inputs(&key_path, &[&P2TR]), Ok(()));
+        let annex = tx(2, &[(&[], &[&[1; 64], &[0x50, 1]])], outputs);
+        assert_eq!(
+            standard_inputs(&annex, &[&P2TR]),
+            Err(PolicyError::NonStandardWitness(0))
+        );
+        let control_block = [0xc0; 33];
+        let script_path = tx(2, &[(&[], &[&[1; 80], &[0xac], &control_block])], outputs);
+        assert_eq!(standard_inputs(&script_path, &[&P2TR]), Ok(()));
+        let script_path = tx(2, &[(&[], &[&[1; 81], &[0xac], &control_block])], outputs);
+        assert_eq!(
+            standard_inputs(&script_path, &[&P2TR]),
+            Err(PolicyError::NonStandardWitness(0))
+        );
+
+        // the second input witness is checked against the second prevout
+        let two = tx(
+            2,
+            &[(&[], &[&[1; 64]]), (&[], &[&[1; 81], &[0xac]])],
+            outputs,
+        );
+        assert_eq!(
+            standard_inputs(&two, &[&P2TR, &P2WSH]),
+            Err(PolicyError::NonStandardWitness(1))
+        );
+        assert_eq!(standard_inputs(&two, &[&P2TR, &P2TR]), Ok(()));
+
+        // the input rules are checked on every input before the witness rules
+        let mixed = tx(2, &[(&[], witness), (&[], &[])], outputs);
+        assert_eq!(
+            standard_inputs(&mixed, &[&ANCHOR, &hex!("5202abcd")]),
+            Err(PolicyError::NonStandardInput(1))
+        );
+        assert_eq!(
+            standard_inputs(&mixed, &[&ANCHOR, &P2PKH]),
+            Err(PolicyError::NonStandardWitness(0))
+        );
+    }
+
+    #[test]
+    fn mainnet_standard() {
+        struct NonStandard(usize, Vec<usize>);
+        impl Visitor for NonStandard {
+            fn visit_transaction(&mut self, tx: &Transaction) -> ControlFlow<()> {
+                if tx.check_standard().is_err() {
+                    self.1.push(self.0);
+                }
+                self.0 += 1;
+                ControlFlow::Continue(())
+            }
+        }
+        let mut visitor = NonStandard(0, vec![]);
+        let block = bitcoin_test_data::blocks::mainnet_702861();
+        crate::bsl::Block::visit(block, &mut visitor).unwrap();
+        // the coinbase is never standard
+        assert_eq!(visitor.1, vec![0]);
+    }
+}
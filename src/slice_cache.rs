@@ -1,16 +1,76 @@
 use alloc::{boxed::Box, collections::VecDeque, sync::Arc};
-use core::hash::Hash;
+use core::{
+    hash::Hash,
+    sync::atomic::{AtomicBool, Ordering},
+};
 use hashbrown::HashMap;
 use private::Range;
 
+use crate::{FromSlice, Parse};
+
+#[cfg(any(feature = "prometheus", feature = "metrics"))]
+mod metrics;
+#[cfg(feature = "std")]
+mod sharded;
+#[cfg(feature = "std")]
+mod snapshot;
+
+#[cfg(feature = "std")]
+pub use sharded::{ShardedSliceCache, SliceGuard};
+
 #[derive(Debug)]
 pub enum Error {
     ValueLargerThanBuffer,
     ValueAlreadyPresent,
 }
 
-/// A FIFO cache for serializable objects with predictable size and almost no allocations at regime
-/// and almost no wasted space.
+/// Which entries are evicted when the buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+    /// The oldest inserted entries are evicted, even if they are frequently read
+    #[default]
+    Fifo,
+
+    /// Second chance: an entry read since it has been inserted or moved is not evicted but moved
+    /// at the free pointer, as if it was inserted again. It's evicted at the next round if not
+    /// read in the meantime.
+    ///
+    /// Hot entries survive at the cost of copying them inside the buffer, without allocations.
+    Clock,
+}
+
+/// The space used by a [`SliceCache`], as returned by [`SliceCache::usage`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    /// Number of entries in the cache
+    pub entries: usize,
+    /// Bytes of the buffer used by the entries
+    pub used_bytes: usize,
+    /// Size of the buffer
+    pub capacity: usize,
+}
+
+/// Called with the key and the value of every entry evicted to make room for an insertion
+type OnEvict<K> = Box<dyn FnMut(&K, &[u8]) + Send + Sync>;
+
+/// A position in the buffer with the flag used by [`EvictionPolicy::Clock`]
+#[derive(Debug)]
+struct Entry {
+    range: Range,
+    referenced: AtomicBool,
+}
+
+impl Entry {
+    fn new(range: Range) -> Self {
+        Entry {
+            range,
+            referenced: AtomicBool::new(false),
+        }
+    }
+}
+
+/// A FIFO, or CLOCK, cache for serializable objects with predictable size and almost no
+/// allocations at regime and almost no wasted space.
 ///
 /// The serialized cache requires an allocator.
 ///
@@ -26,7 +86,9 @@ pub enum Error {
 /// object are inserted at the beginning, obviously overwriting oldest entries.
 ///
 /// The average number of elements in the cache is `size(buffer)/average_size(object)`
-///   
+///
+/// With [`EvictionPolicy::Clock`] entries read since their insertion are kept, see
+/// [`SliceCache::with_policy`].
 pub struct SliceCache<K: Hash + PartialEq + Eq + core::fmt::Debug> {
     /// Contains serialized objects one after the other, its size is defined at cache creation,
     /// once full, it starts again from the start
@@ -36,17 +98,26 @@ pub struct SliceCache<K: Hash + PartialEq + Eq + core::fmt::Debug> {
     free_pointer: usize,
 
     /// Pointers to buffer of the serialized objects
-    indexes: HashMap<Arc<K>, Range>,
+    indexes: HashMap<Arc<K>, Entry>,
 
-    /// Order of the key inserted
+    /// Order of the key inserted, which is also the order of the objects in the buffer starting
+    /// from the free pointer. Keys removed with [`SliceCache::remove`] are left here until they
+    /// reach the back.
     insertions: VecDeque<Arc<K>>,
 
+    /// Sum of the len of the entries in `indexes`
+    used_bytes: usize,
+
+    on_evict: Option<OnEvict<K>>,
+
+    policy: EvictionPolicy,
+
     /// The cache is full, at least once it removed an older element to insert a new one.
     /// Obviously elements can still be inserted but they may remove older elements.
     full: bool,
 
-    #[cfg(feature = "prometheus")]
-    metric: prometheus::IntCounterVec,
+    #[cfg(any(feature = "prometheus", feature = "metrics"))]
+    metrics: metrics::Metrics,
 }
 
 mod private {
@@ -93,23 +164,38 @@ mod private {
 impl<K: Hash + PartialEq + Eq + core::fmt::Debug> SliceCache<K> {
     /// Create the serialized cache with byte len equal to given `size`
     pub fn new(size: usize) -> Self {
+        Self::with_policy(size, EvictionPolicy::Fifo)
+    }
+
+    /// Create the serialized cache with byte len equal to given `size`, evicting entries according
+    /// to `policy`
+    pub fn with_policy(size: usize, policy: EvictionPolicy) -> Self {
         Self {
             buffer: vec![0u8; size].into_boxed_slice(),
             free_pointer: 0,
             indexes: HashMap::new(),
             insertions: VecDeque::new(),
+            used_bytes: 0,
+            on_evict: None,
+            policy,
             full: false,
 
-            // TODO: metric name should be parametrized
-            #[cfg(feature = "prometheus")]
-            metric: prometheus::IntCounterVec::new(
-                prometheus::Opts::new("slice_cache", "Counters for cache Hit/Miss"),
-                &["event"],
-            )
-            .expect("statically defined"),
+            #[cfg(any(feature = "prometheus", feature = "metrics"))]
+            metrics: metrics::Metrics::new(None),
         }
     }
 
+    #[cfg(any(feature = "prometheus", feature = "metrics"))]
+    /// Add the label `cache` with the given value to the metrics, so that many caches can be told
+    /// apart, with `prometheus` they can be registered in the same registry, all with a label.
+    /// Events counted until now are discarded.
+    pub fn with_metric_label(mut self, cache: &str) -> Self {
+        self.metrics = metrics::Metrics::new(Some(cache));
+        self.metrics
+            .add(self.indexes.len() as i64, self.used_bytes as i64);
+        self
+    }
+
     /// Insert a value V in the cache, with key K
     /// returns the number of old entries removed
     pub fn insert<V: AsRef<[u8]>>(&mut self, key: K, value: &V) -> Result<usize, Error> {
@@ -117,20 +203,80 @@ impl<K: Hash + PartialEq + Eq + core::fmt::Debug> SliceCache<K> {
         let mut removed = 0;
 
         if self.indexes.get(&key).is_some() {
+            #[cfg(any(feature = "prometheus", feature = "metrics"))]
+            self.metrics.event(metrics::Event::ValueAlreadyPresent);
+
             return Err(Error::ValueAlreadyPresent);
         }
         if value.len() > self.buffer.len() {
+            #[cfg(any(feature = "prometheus", feature = "metrics"))]
+            self.metrics.event(metrics::Event::ValueLargerThanBuffer);
+
             return Err(Error::ValueLargerThanBuffer);
         }
-        if value.len() + self.free_pointer > self.buffer.len() {
-            // the element would not fit in the buffer, start again from the beginning,
-            // but first remove any element in the buffer tail, otherwise inserted_range will not
-            // overlap with the latest elements
-            let range = Range::from_begin_end(self.free_pointer, self.buffer.len())
-                .expect("the buffer is longer than free_pointer as ensured by the if clause");
-            removed += self.remove_range(&range);
-            self.free_pointer = 0;
-            self.full = true;
+        loop {
+            // the area that will be written, or the buffer tail if the element would not fit and
+            // we need to start again from the beginning
+            let end = (self.free_pointer + value.len()).min(self.buffer.len());
+            let area = Range::from_begin_end(self.free_pointer, end);
+            if let Some(oldest) = self.insertions.back() {
+                if !self.is_live(oldest) {
+                    self.insertions.pop_back();
+                    continue;
+                }
+            }
+            let oldest = match (area, self.insertions.back()) {
+                (Some(area), Some(oldest)) => {
+                    let entry = self
+                        .indexes
+                        .get(oldest)
+                        .expect("if in insertion, must be in indexes");
+                    if entry.range.overlaps(&area) {
+                        Some(entry)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+            match oldest {
+                Some(entry) if entry.referenced.swap(false, Ordering::Relaxed) => {
+                    // second chance, the oldest entry begins after the free pointer since
+                    // entries are in insertion order
+                    let range = entry.range.clone();
+                    let len = range.end() - range.begin();
+                    self.buffer
+                        .copy_within(range.begin()..range.end(), self.free_pointer);
+                    let key = self.insertions.pop_back().expect("must be found");
+                    self.indexes.get_mut(&key).expect("must be found").range =
+                        Range::from_begin_len(self.free_pointer, len);
+                    self.insertions.push_front(key);
+                    self.free_pointer += len;
+                }
+                Some(_) => {
+                    let key = self.insertions.pop_back().expect("must be found");
+                    let entry = self.indexes.remove(&key).expect("must be found");
+                    let range = entry.range;
+                    self.used_bytes -= range.end() - range.begin();
+
+                    #[cfg(any(feature = "prometheus", feature = "metrics"))]
+                    {
+                        self.metrics.event(metrics::Event::Eviction);
+                        self.metrics
+                            .add(-1, -((range.end() - range.begin()) as i64));
+                    }
+
+                    if let Some(on_evict) = self.on_evict.as_mut() {
+                        on_evict(&key, &self.buffer[range.begin()..range.end()]);
+                    }
+                    removed += 1;
+                }
+                None if end - self.free_pointer < value.len() => {
+                    self.free_pointer = 0;
+                    self.full = true;
+                }
+                None => break,
+            }
         }
         let begin = self.free_pointer;
         let end = begin + value.len();
@@ -139,34 +285,112 @@ impl<K: Hash + PartialEq + Eq + core::fmt::Debug> SliceCache<K> {
 
         let inserted_range = Range::from_begin_len(begin, value.len());
         let key = Arc::new(key);
-        self.indexes.insert(key.clone(), inserted_range.clone());
+        self.indexes.insert(key.clone(), Entry::new(inserted_range));
         self.insertions.push_front(key);
+        self.used_bytes += value.len();
 
-        if self.insertions.len() > 1 {
-            removed += self.remove_range(&inserted_range)
-        }
+        #[cfg(any(feature = "prometheus", feature = "metrics"))]
+        self.metrics.add(1, value.len() as i64);
 
         Ok(removed)
     }
 
+    /// Whether the key in `insertions` refers to an entry in the cache and not to a removed one,
+    /// possibly inserted again
+    fn is_live(&self, key: &Arc<K>) -> bool {
+        match self.indexes.get_key_value(key) {
+            Some((live, _)) => Arc::ptr_eq(live, key),
+            None => false,
+        }
+    }
+
+    /// Remove the value at key `K`, returns whether it was in the cache.
+    ///
+    /// The space used by the value is reused when the free pointer reaches it.
+    pub fn remove(&mut self, key: &K) -> bool {
+        match self.indexes.remove(key) {
+            Some(entry) => {
+                let len = entry.range.end() - entry.range.begin();
+                self.used_bytes -= len;
+
+                #[cfg(any(feature = "prometheus", feature = "metrics"))]
+                self.metrics.add(-1, -(len as i64));
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove every value from the cache
+    pub fn clear(&mut self) {
+        #[cfg(any(feature = "prometheus", feature = "metrics"))]
+        self.metrics
+            .add(-(self.indexes.len() as i64), -(self.used_bytes as i64));
+
+        self.indexes.clear();
+        self.insertions.clear();
+        self.free_pointer = 0;
+        self.used_bytes = 0;
+        self.full = false;
+    }
+
+    /// Iterate over the keys and values in the cache, from the oldest inserted.
+    ///
+    /// Reads through the iterator are not counted in the metrics and by [`EvictionPolicy::Clock`]
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &[u8])> + '_ {
+        self.insertions.iter().rev().filter_map(move |key| {
+            let (live, entry) = self.indexes.get_key_value(key)?;
+            if Arc::ptr_eq(live, key) {
+                Some((&**key, &self.buffer[entry.range.begin()..entry.range.end()]))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Return the number of entries and the bytes they use
+    pub fn usage(&self) -> Usage {
+        Usage {
+            entries: self.indexes.len(),
+            used_bytes: self.used_bytes,
+            capacity: self.buffer.len(),
+        }
+    }
+
+    /// Call `on_evict` with the key and the value of every entry evicted to make room for an
+    /// insertion, for example to spill it on disk.
+    ///
+    /// Entries removed with [`SliceCache::remove`] or [`SliceCache::clear`] are not passed.
+    pub fn set_on_evict<F: FnMut(&K, &[u8]) + Send + Sync + 'static>(&mut self, on_evict: F) {
+        self.on_evict = Some(Box::new(on_evict));
+    }
+
     /// Get the value as slice at key `K` if exist in the cache, `None` otherwise
     pub fn get(&self, key: &K) -> Option<&[u8]> {
-        let index = match self.indexes.get(key) {
+        let range = self.get_range(key)?;
+        Some(&self.buffer[range.begin()..range.end()])
+    }
+
+    /// Get the position in the buffer of the value at key `K`, counting the hit or the miss
+    fn get_range(&self, key: &K) -> Option<Range> {
+        match self.indexes.get(key) {
             Some(val) => {
-                #[cfg(feature = "prometheus")]
-                self.metric.with_label_values(&["hit"]).inc();
+                #[cfg(any(feature = "prometheus", feature = "metrics"))]
+                self.metrics.event(metrics::Event::Hit);
 
-                val
+                if self.policy == EvictionPolicy::Clock {
+                    val.referenced.store(true, Ordering::Relaxed);
+                }
+                Some(val.range.clone())
             }
             None => {
-                #[cfg(feature = "prometheus")]
-                self.metric.with_label_values(&["miss"]).inc();
+                #[cfg(any(feature = "prometheus", feature = "metrics"))]
+                self.metrics.event(metrics::Event::Miss);
 
-                return None;
+                None
             }
-        };
-
-        Some(&self.buffer[index.begin()..index.end()])
+        }
     }
 
     /// Return wether the cache contains the given key
@@ -174,15 +398,28 @@ impl<K: Hash + PartialEq + Eq + core::fmt::Debug> SliceCache<K> {
         self.get(key).is_some()
     }
 
-    #[cfg(feature = "redb")]
-    /// Get the value at key `K` if exist in the cache, `None` otherwise
-    pub fn get_value<'a, V: redb::RedbValue>(&'a self, key: &K) -> Option<V::SelfType<'a>> {
+    /// Get the value at key `K` if exist in the cache, `None` otherwise.
+    ///
+    /// The value is decoded with [`FromSlice::from_trusted_slice`], so it must have been inserted
+    /// with the same type.
+    pub fn get_value<'a, V: FromSlice<'a>>(&'a self, key: &K) -> Option<V> {
         let slice = self.get(key)?;
-        let value = V::from_bytes(slice);
+        let value = V::from_trusted_slice(slice);
 
         Some(value)
     }
 
+    /// Get the value at key `K` parsed as `T` if exist in the cache, `None` otherwise.
+    ///
+    /// Unlike [`SliceCache::get_value`] the value is validated, so it's an error if it has been
+    /// inserted with a different type.
+    pub fn get_parsed<'a, T: Parse<'a>>(&'a self, key: &K) -> Result<Option<T>, crate::Error> {
+        match self.get(key) {
+            Some(slice) => Ok(Some(T::parse(slice)?.parsed_owned())),
+            None => Ok(None),
+        }
+    }
+
     /// Return the number of elements contained in the cache
     pub fn len(&self) -> usize {
         self.indexes.len()
@@ -199,29 +436,11 @@ impl<K: Hash + PartialEq + Eq + core::fmt::Debug> SliceCache<K> {
         self.full
     }
 
-    fn remove_range(&mut self, range_to_remove: &Range) -> usize {
-        let mut removed = 0;
-
-        while let Some(back) = self.insertions.back() {
-            let range = self
-                .indexes
-                .get(back)
-                .expect("if in insertion, must be in indexes");
-            if range_to_remove.overlaps(&range) {
-                self.indexes.remove(back).expect("must be found");
-                self.insertions.pop_back().expect("must be found");
-                removed += 1;
-            } else {
-                break;
-            }
-        }
-        removed
-    }
-
     #[cfg(feature = "prometheus")]
-    /// Register the inner metric for hit/cache in the prometheus registry
+    /// Register the inner metrics in the prometheus registry: the counters of hit, miss, eviction
+    /// and rejected insertions, and the gauges of entries, used bytes and average entry size
     pub fn register_metric(&self, r: &prometheus::Registry) -> Result<(), prometheus::Error> {
-        r.register(Box::new(self.metric.clone()))
+        self.metrics.register(r)
     }
 }
 
@@ -276,6 +495,99 @@ mod tests {
         println!("{:?}", cache.insertions);
     }
 
+    #[test]
+    fn clock() {
+        let mut fifo = SliceCache::new(10);
+        let mut clock = SliceCache::with_policy(10, EvictionPolicy::Clock);
+        for cache in [&mut fifo, &mut clock] {
+            cache.insert(1, &[1, 1, 1]).unwrap();
+            cache.insert(2, &[2, 2, 2]).unwrap();
+            cache.insert(3, &[3, 3, 3]).unwrap();
+            assert!(cache.contains(&3));
+            assert_eq!(cache.insert(4, &[4, 4]).unwrap(), 1);
+            assert_eq!(cache.insert(5, &[5, 5, 5]).unwrap(), 1);
+            assert_eq!(cache.get(&2), None);
+        }
+
+        // the read entry 3 is evicted by FIFO
+        assert_eq!(fifo.insert(6, &[6, 6]).unwrap(), 1);
+        assert_eq!(fifo.get(&3), None);
+
+        // while CLOCK moves it at the free pointer, before the inserted element
+        assert_eq!(clock.insert(6, &[6, 6]).unwrap(), 0);
+        assert_eq!(clock.len(), 4);
+        assert_eq!(&clock.buffer[..], &[4, 4, 5, 5, 5, 3, 3, 3, 6, 6]);
+
+        // entries not read since they have been moved or inserted are evicted
+        clock.get(&6);
+        assert_eq!(clock.insert(7, &[7, 7, 7]).unwrap(), 2);
+        assert_eq!(clock.get(&4), None);
+        assert_eq!(clock.get(&5), None);
+        assert_eq!(clock.insert(8, &[8, 8, 8]).unwrap(), 1);
+        assert_eq!(clock.get(&3), None);
+        assert_eq!(clock.get(&6), Some(&[6, 6][..]));
+        assert_eq!(clock.get(&7), Some(&[7, 7, 7][..]));
+        assert_eq!(clock.get(&8), Some(&[8, 8, 8][..]));
+    }
+
+    #[test]
+    fn remove_iter_evict() {
+        use std::sync::{Arc, Mutex};
+
+        let evicted = Arc::new(Mutex::new(vec![]));
+        let mut cache = SliceCache::new(10);
+        let evicted_clone = evicted.clone();
+        cache.set_on_evict(move |k: &u32, v: &[u8]| {
+            evicted_clone.lock().unwrap().push((*k, v.to_vec()));
+        });
+
+        cache.insert(1, &[1, 1]).unwrap();
+        cache.insert(2, &[2, 2, 2]).unwrap();
+        cache.insert(3, &[3, 3]).unwrap();
+        let usage = cache.usage();
+        assert_eq!(
+            (usage.entries, usage.used_bytes, usage.capacity),
+            (3, 7, 10)
+        );
+
+        assert!(cache.remove(&2));
+        assert!(!cache.remove(&2));
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.usage().used_bytes, 4);
+        let entries: Vec<_> = cache.iter().collect();
+        assert_eq!(entries, vec![(&1, &[1, 1][..]), (&3, &[3, 3][..])]);
+
+        // a removed key can be inserted again, the stale position is skipped during eviction
+        cache.insert(2, &[2, 2]).unwrap();
+        assert_eq!(cache.insert(4, &[4, 4, 4]).unwrap(), 1);
+        let entries: Vec<_> = cache.iter().map(|(k, _)| *k).collect();
+        assert_eq!(entries, vec![3, 2, 4]);
+        assert_eq!(cache.get(&2), Some(&[2, 2][..]));
+        assert_eq!(*evicted.lock().unwrap(), vec![(1, vec![1, 1])]);
+
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.iter().count(), 0);
+        assert_eq!(cache.usage().used_bytes, 0);
+        cache.insert(5, &[5; 10]).unwrap();
+        assert_eq!(cache.get(&5), Some(&[5; 10][..]));
+        assert_eq!(evicted.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn get_parsed() {
+        use crate::{bsl::TxOut, test_common::GENESIS_TX};
+
+        let tx_out_bytes = &GENESIS_TX[GENESIS_TX.len() - 80..GENESIS_TX.len() - 4];
+        let mut cache = SliceCache::new(100);
+        cache.insert(0, &tx_out_bytes).unwrap();
+        cache.insert(1, &[0u8; 8]).unwrap();
+        let tx_out = cache.get_parsed::<TxOut>(&0).unwrap().unwrap();
+        assert_eq!(tx_out.value(), 5_000_000_000);
+        assert_eq!(cache.get_parsed::<TxOut>(&2), Ok(None));
+        assert_eq!(cache.get_parsed::<TxOut>(&1), Err(crate::Error::Needed(1)));
+    }
+
     #[cfg(feature = "prometheus")]
     #[test]
     fn prometheus() {
@@ -298,7 +610,114 @@ mod tests {
         let metric_families = r.gather();
         encoder.encode(&metric_families, &mut buffer).unwrap();
         let result = format!("{}", String::from_utf8(buffer.clone()).unwrap());
-        assert_eq!(result, "# HELP slice_cache Counters for cache Hit/Miss\n# TYPE slice_cache counter\nslice_cache{event=\"hit\"} 1\nslice_cache{event=\"miss\"} 1\n");
+        assert_eq!(result, "# HELP slice_cache Counters for cache hit, miss, eviction and rejected insertions\n# TYPE slice_cache counter\nslice_cache{event=\"hit\"} 1\nslice_cache{event=\"miss\"} 1\n# HELP slice_cache_avg_entry_size Average size in bytes of the entries\n# TYPE slice_cache_avg_entry_size gauge\nslice_cache_avg_entry_size 2\n# HELP slice_cache_entries Number of entries in the cache\n# TYPE slice_cache_entries gauge\nslice_cache_entries 1\n# HELP slice_cache_used_bytes Bytes of the buffer used by the entries\n# TYPE slice_cache_used_bytes gauge\nslice_cache_used_bytes 2\n");
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn prometheus_labels() {
+        let r = prometheus::Registry::new();
+        let mut first = SliceCache::new(10).with_metric_label("first");
+        let mut second = SliceCache::new(10).with_metric_label("second");
+        first.register_metric(&r).unwrap();
+        second.register_metric(&r).unwrap();
+
+        first.insert(0, &[0; 4]).unwrap();
+        first.insert(1, &[1; 4]).unwrap();
+        assert!(first.insert(1, &[1; 4]).is_err());
+        assert!(first.insert(2, &[2; 11]).is_err());
+        assert_eq!(first.insert(2, &[2; 3]).unwrap(), 1);
+        second.insert(0, &[0; 6]).unwrap();
+
+        let value = |name: &str, cache: &str, event: Option<&str>| {
+            let family = r
+                .gather()
+                .into_iter()
+                .find(|f| f.get_name() == name)
+                .unwrap();
+            let metric = family
+                .get_metric()
+                .iter()
+                .find(|m| {
+                    m.get_label()
+                        .iter()
+                        .any(|l| l.get_name() == "cache" && l.get_value() == cache)
+                        && m.get_label()
+                            .iter()
+                            .all(|l| l.get_name() != "event" || Some(l.get_value()) == event)
+                })
+                .unwrap()
+                .clone();
+            match event {
+                Some(_) => metric.get_counter().get_value(),
+                None => metric.get_gauge().get_value(),
+            }
+        };
+        assert_eq!(value("slice_cache", "first", Some("eviction")), 1.0);
+        assert_eq!(
+            value("slice_cache", "first", Some("value_already_present")),
+            1.0
+        );
+        assert_eq!(
+            value("slice_cache", "first", Some("value_larger_than_buffer")),
+            1.0
+        );
+        assert_eq!(value("slice_cache_entries", "first", None), 2.0);
+        assert_eq!(value("slice_cache_used_bytes", "first", None), 7.0);
+        assert_eq!(value("slice_cache_avg_entry_size", "first", None), 3.5);
+        assert_eq!(value("slice_cache_entries", "second", None), 1.0);
+        assert_eq!(value("slice_cache_used_bytes", "second", None), 6.0);
+
+        first.remove(&1);
+        assert_eq!(value("slice_cache_entries", "first", None), 1.0);
+        first.clear();
+        assert_eq!(value("slice_cache_used_bytes", "first", None), 0.0);
+        assert_eq!(value("slice_cache_avg_entry_size", "first", None), 0.0);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics_facade() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        ::metrics::with_local_recorder(&recorder, || {
+            let mut cache = SliceCache::new(10).with_metric_label("first");
+            cache.insert(0, &[0; 4]).unwrap();
+            cache.insert(1, &[1; 4]).unwrap();
+            assert!(cache.insert(1, &[1; 4]).is_err());
+            assert_eq!(cache.insert(2, &[2; 3]).unwrap(), 1);
+            assert!(cache.get(&2).is_some());
+            assert!(cache.get(&0).is_none());
+        });
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let value = |name: &str, event: Option<&str>| {
+            snapshot
+                .iter()
+                .find(|(key, _, _, _)| {
+                    let key = key.key();
+                    key.name() == name
+                        && key
+                            .labels()
+                            .any(|l| l.key() == "cache" && l.value() == "first")
+                        && key.labels().find(|l| l.key() == "event").map(|l| l.value()) == event
+                })
+                .map(|(_, _, _, value)| match value {
+                    DebugValue::Counter(c) => *c as f64,
+                    DebugValue::Gauge(g) => g.into_inner(),
+                    DebugValue::Histogram(_) => unreachable!(),
+                })
+                .unwrap()
+        };
+        assert_eq!(value("slice_cache", Some("hit")), 1.0);
+        assert_eq!(value("slice_cache", Some("miss")), 1.0);
+        assert_eq!(value("slice_cache", Some("eviction")), 1.0);
+        assert_eq!(value("slice_cache", Some("value_already_present")), 1.0);
+        assert_eq!(value("slice_cache_entries", None), 2.0);
+        assert_eq!(value("slice_cache_used_bytes", None), 7.0);
+        assert_eq!(value("slice_cache_avg_entry_size", None), 3.5);
     }
 
     #[cfg(feature = "bitcoin")]
@@ -355,7 +774,7 @@ mod tests {
         }
     }
 
-    #[cfg(all(feature = "bitcoin", feature = "redb"))]
+    #[cfg(feature = "bitcoin")]
     #[test]
     fn with_transaction_value() {
         use crate::bsl::Transaction;
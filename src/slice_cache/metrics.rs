@@ -0,0 +1,218 @@
+#[cfg(feature = "prometheus")]
+use alloc::boxed::Box;
+#[cfg(feature = "metrics")]
+use alloc::{sync::Arc, vec::Vec};
+#[cfg(feature = "metrics")]
+use core::sync::atomic::{AtomicI64, Ordering};
+#[cfg(feature = "prometheus")]
+use prometheus::{Gauge, IntCounterVec, IntGauge, Opts, Registry};
+
+/// The events counted in the `slice_cache` counter, with the label `event`
+#[derive(Clone, Copy)]
+pub(crate) enum Event {
+    Hit,
+    Miss,
+    Eviction,
+    ValueAlreadyPresent,
+    ValueLargerThanBuffer,
+}
+
+impl Event {
+    #[cfg(feature = "metrics")]
+    const ALL: [Event; 5] = [
+        Event::Hit,
+        Event::Miss,
+        Event::Eviction,
+        Event::ValueAlreadyPresent,
+        Event::ValueLargerThanBuffer,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Event::Hit => "hit",
+            Event::Miss => "miss",
+            Event::Eviction => "eviction",
+            Event::ValueAlreadyPresent => "value_already_present",
+            Event::ValueLargerThanBuffer => "value_larger_than_buffer",
+        }
+    }
+}
+
+/// The metrics of a [`super::SliceCache`], cloning shares the underlying values
+#[derive(Clone)]
+pub(crate) struct Metrics {
+    #[cfg(feature = "prometheus")]
+    prometheus: Prometheus,
+
+    #[cfg(feature = "metrics")]
+    facade: Facade,
+}
+
+impl Metrics {
+    /// Create the metrics, with the label `cache` if given, so that many caches can be
+    /// distinguished
+    pub(crate) fn new(cache: Option<&str>) -> Self {
+        Metrics {
+            #[cfg(feature = "prometheus")]
+            prometheus: Prometheus::new(cache),
+
+            #[cfg(feature = "metrics")]
+            facade: Facade::new(cache),
+        }
+    }
+
+    /// Count an event such as a hit or an eviction
+    pub(crate) fn event(&self, event: Event) {
+        #[cfg(feature = "prometheus")]
+        self.prometheus.event(event);
+
+        #[cfg(feature = "metrics")]
+        self.facade.event(event);
+    }
+
+    /// Add the given number of entries and bytes, which are negative on removal.
+    ///
+    /// Gauges are updated with deltas since they may be shared by many shards.
+    pub(crate) fn add(&self, entries: i64, bytes: i64) {
+        #[cfg(feature = "prometheus")]
+        self.prometheus.add(entries, bytes);
+
+        #[cfg(feature = "metrics")]
+        self.facade.add(entries, bytes);
+    }
+
+    #[cfg(feature = "prometheus")]
+    pub(crate) fn register(&self, r: &Registry) -> Result<(), prometheus::Error> {
+        self.prometheus.register(r)
+    }
+}
+
+/// Average size of the entries, 0 if there are none
+fn avg_entry_size(entries: i64, bytes: i64) -> f64 {
+    if entries > 0 {
+        bytes as f64 / entries as f64
+    } else {
+        0.0
+    }
+}
+
+/// The metrics registered in a prometheus registry
+#[cfg(feature = "prometheus")]
+#[derive(Clone)]
+struct Prometheus {
+    events: IntCounterVec,
+    entries: IntGauge,
+    used_bytes: IntGauge,
+    avg_entry_size: Gauge,
+}
+
+#[cfg(feature = "prometheus")]
+impl Prometheus {
+    /// Create the metrics, with the constant label `cache` if given, so that many caches can be
+    /// registered in the same registry
+    fn new(cache: Option<&str>) -> Self {
+        let opts = |name: &str, help: &str| {
+            let opts = Opts::new(name, help);
+            match cache {
+                Some(cache) => opts.const_label("cache", cache),
+                None => opts,
+            }
+        };
+        Prometheus {
+            events: IntCounterVec::new(
+                opts(
+                    "slice_cache",
+                    "Counters for cache hit, miss, eviction and rejected insertions",
+                ),
+                &["event"],
+            )
+            .expect("statically defined"),
+            entries: IntGauge::with_opts(opts(
+                "slice_cache_entries",
+                "Number of entries in the cache",
+            ))
+            .expect("statically defined"),
+            used_bytes: IntGauge::with_opts(opts(
+                "slice_cache_used_bytes",
+                "Bytes of the buffer used by the entries",
+            ))
+            .expect("statically defined"),
+            avg_entry_size: Gauge::with_opts(opts(
+                "slice_cache_avg_entry_size",
+                "Average size in bytes of the entries",
+            ))
+            .expect("statically defined"),
+        }
+    }
+
+    fn event(&self, event: Event) {
+        self.events.with_label_values(&[event.label()]).inc();
+    }
+
+    fn add(&self, entries: i64, bytes: i64) {
+        self.entries.add(entries);
+        self.used_bytes.add(bytes);
+        self.avg_entry_size
+            .set(avg_entry_size(self.entries.get(), self.used_bytes.get()));
+    }
+
+    fn register(&self, r: &Registry) -> Result<(), prometheus::Error> {
+        r.register(Box::new(self.events.clone()))?;
+        r.register(Box::new(self.entries.clone()))?;
+        r.register(Box::new(self.used_bytes.clone()))?;
+        r.register(Box::new(self.avg_entry_size.clone()))
+    }
+}
+
+/// The metrics emitted through the `metrics` crate facade.
+///
+/// The handles are registered once at creation, so the recorder must be installed before creating
+/// the cache. The totals are kept here because they may be shared by many shards, the gauges are
+/// set to them.
+#[cfg(feature = "metrics")]
+#[derive(Clone)]
+struct Facade {
+    events: [metrics::Counter; 5],
+    entries_gauge: metrics::Gauge,
+    used_bytes_gauge: metrics::Gauge,
+    avg_entry_size_gauge: metrics::Gauge,
+    entries: Arc<AtomicI64>,
+    used_bytes: Arc<AtomicI64>,
+}
+
+#[cfg(feature = "metrics")]
+impl Facade {
+    /// Create the metrics, with the label `cache` if given
+    fn new(cache: Option<&str>) -> Self {
+        let cache: Vec<_> = cache
+            .map(|cache| metrics::Label::new("cache", Arc::<str>::from(cache)))
+            .into_iter()
+            .collect();
+        let gauge = |name: &'static str| metrics::gauge!(name, cache.clone());
+        Facade {
+            events: Event::ALL.map(|event| {
+                let mut labels = cache.clone();
+                labels.push(metrics::Label::new("event", event.label()));
+                metrics::counter!("slice_cache", labels)
+            }),
+            entries_gauge: gauge("slice_cache_entries"),
+            used_bytes_gauge: gauge("slice_cache_used_bytes"),
+            avg_entry_size_gauge: gauge("slice_cache_avg_entry_size"),
+            entries: Arc::new(AtomicI64::new(0)),
+            used_bytes: Arc::new(AtomicI64::new(0)),
+        }
+    }
+
+    fn event(&self, event: Event) {
+        self.events[event as usize].increment(1);
+    }
+
+    fn add(&self, entries: i64, bytes: i64) {
+        let entries = self.entries.fetch_add(entries, Ordering::Relaxed) + entries;
+        let bytes = self.used_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.entries_gauge.set(entries as f64);
+        self.used_bytes_gauge.set(bytes as f64);
+        self.avg_entry_size_gauge
+            .set(avg_entry_size(entries, bytes));
+    }
+}
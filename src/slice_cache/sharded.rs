@@ -0,0 +1,241 @@
+use core::{
+    hash::{BuildHasher, Hash, Hasher},
+    ops::Deref,
+};
+use std::sync::{RwLock, RwLockReadGuard};
+
+use alloc::{boxed::Box, vec::Vec};
+use hashbrown::hash_map::DefaultHashBuilder;
+
+use super::{Error, EvictionPolicy, Range, SliceCache};
+
+/// A [`SliceCache`] usable concurrently from many threads.
+///
+/// The cache is split in independent shards, each one with its own buffer and lock, the shard
+/// containing a key is chosen by hashing the key. Reads take the shard lock in shared mode, so they
+/// proceed in parallel and only wait for insertions in the same shard.
+///
+/// With the `prometheus` or the `metrics` feature, metrics are aggregated among the shards.
+pub struct ShardedSliceCache<K: Hash + PartialEq + Eq + core::fmt::Debug> {
+    shards: Box<[RwLock<SliceCache<K>>]>,
+    hasher: DefaultHashBuilder,
+}
+
+/// A value read from a [`ShardedSliceCache`], the shard can't be modified while this is alive
+pub struct SliceGuard<'a, K: Hash + PartialEq + Eq + core::fmt::Debug> {
+    shard: RwLockReadGuard<'a, SliceCache<K>>,
+    range: Range,
+}
+
+impl<K: Hash + PartialEq + Eq + core::fmt::Debug> Deref for SliceGuard<'_, K> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.shard.buffer[self.range.begin()..self.range.end()]
+    }
+}
+
+impl<K: Hash + PartialEq + Eq + core::fmt::Debug> AsRef<[u8]> for SliceGuard<'_, K> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<K: Hash + PartialEq + Eq + core::fmt::Debug> ShardedSliceCache<K> {
+    /// Create the cache with `shards` shards of byte len `size / shards`
+    ///
+    /// # Panics
+    ///
+    /// If `shards` is 0
+    pub fn new(size: usize, shards: usize) -> Self {
+        Self::with_policy(size, shards, EvictionPolicy::Fifo)
+    }
+
+    /// Create the cache with `shards` shards of byte len `size / shards`, evicting entries
+    /// according to `policy`
+    ///
+    /// # Panics
+    ///
+    /// If `shards` is 0
+    pub fn with_policy(size: usize, shards: usize, policy: EvictionPolicy) -> Self {
+        assert!(shards > 0, "at least one shard is needed");
+        let shards: Vec<_> = (0..shards)
+            .map(|_| SliceCache::with_policy(size / shards, policy))
+            .collect();
+
+        let cache = Self {
+            shards: shards.into_iter().map(RwLock::new).collect(),
+            hasher: DefaultHashBuilder::default(),
+        };
+
+        #[cfg(any(feature = "prometheus", feature = "metrics"))]
+        cache.share_metrics(super::metrics::Metrics::new(None));
+
+        cache
+    }
+
+    #[cfg(any(feature = "prometheus", feature = "metrics"))]
+    fn share_metrics(&self, metrics: super::metrics::Metrics) {
+        for shard in self.shards.iter() {
+            let mut shard = shard.write().expect("slice cache lock poisoned");
+            metrics.add(shard.len() as i64, shard.used_bytes as i64);
+            shard.metrics = metrics.clone();
+        }
+    }
+
+    #[cfg(any(feature = "prometheus", feature = "metrics"))]
+    /// Add the label `cache` with the given value to the metrics, so that many caches can be told
+    /// apart, with `prometheus` they can be registered in the same registry, all with a label.
+    /// Events counted until now are discarded.
+    pub fn with_metric_label(self, cache: &str) -> Self {
+        self.share_metrics(super::metrics::Metrics::new(Some(cache)));
+        self
+    }
+
+    // TODO use hash_one once MSRV allows
+    #[allow(clippy::manual_hash_one)]
+    fn shard(&self, key: &K) -> &RwLock<SliceCache<K>> {
+        let mut hasher = self.hasher.build_hasher();
+        key.hash(&mut hasher);
+        let index = hasher.finish() % self.shards.len() as u64;
+        &self.shards[index as usize]
+    }
+
+    /// Insert a value V in the cache, with key K
+    /// returns the number of old entries removed from the shard
+    pub fn insert<V: AsRef<[u8]>>(&self, key: K, value: &V) -> Result<usize, Error> {
+        self.shard(&key)
+            .write()
+            .expect("slice cache lock poisoned")
+            .insert(key, value)
+    }
+
+    /// Get the value as slice at key `K` if exist in the cache, `None` otherwise
+    pub fn get(&self, key: &K) -> Option<SliceGuard<'_, K>> {
+        let shard = self.shard(key).read().expect("slice cache lock poisoned");
+        let range = shard.get_range(key)?;
+        Some(SliceGuard { shard, range })
+    }
+
+    /// Remove the value at key `K`, returns whether it was in the cache
+    pub fn remove(&self, key: &K) -> bool {
+        self.shard(key)
+            .write()
+            .expect("slice cache lock poisoned")
+            .remove(key)
+    }
+
+    /// Remove every value from the cache
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.write().expect("slice cache lock poisoned").clear();
+        }
+    }
+
+    /// Return wether the cache contains the given key
+    pub fn contains(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Return the number of elements contained in the cache
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().expect("slice cache lock poisoned").len())
+            .sum()
+    }
+
+    /// Return whether the cache contains no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[cfg(feature = "prometheus")]
+    /// Register the metrics aggregated among the shards in the prometheus registry, see
+    /// [`SliceCache::register_metric`]
+    pub fn register_metric(&self, r: &prometheus::Registry) -> Result<(), prometheus::Error> {
+        self.shards[0]
+            .read()
+            .expect("slice cache lock poisoned")
+            .register_metric(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShardedSliceCache;
+    use crate::EvictionPolicy;
+
+    #[test]
+    fn concurrent() {
+        let cache = ShardedSliceCache::with_policy(4_000, 4, EvictionPolicy::Clock);
+        std::thread::scope(|s| {
+            for t in 0..4u32 {
+                let cache = &cache;
+                s.spawn(move || {
+                    for i in 0..100u32 {
+                        let key = t * 100 + i;
+                        cache.insert(key, &key.to_le_bytes()).unwrap();
+                        let value = cache.get(&key).unwrap();
+                        assert_eq!(&value[..], &key.to_le_bytes()[..]);
+                    }
+                });
+            }
+        });
+        assert_eq!(cache.len(), 400);
+        for key in 0..400u32 {
+            assert_eq!(cache.get(&key).unwrap().as_ref(), &key.to_le_bytes()[..]);
+        }
+        assert!(cache.get(&400).is_none());
+        assert!(cache.insert(0, &[0u8]).is_err());
+
+        // every shard has 10 bytes, the inserted values are evicted once full
+        let cache = ShardedSliceCache::new(40, 4);
+        for key in 0..100u32 {
+            cache.insert(key, &key.to_le_bytes()).unwrap();
+        }
+        assert!(cache.len() <= 8);
+        assert!(!cache.is_empty());
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn prometheus() {
+        let r = prometheus::Registry::new();
+        let cache = ShardedSliceCache::new(1_000, 4);
+        cache.register_metric(&r).unwrap();
+        for key in 0..10u32 {
+            cache.insert(key, &key.to_le_bytes()).unwrap();
+        }
+        for key in 0..20u32 {
+            let _ = cache.get(&key);
+        }
+
+        let families = r.gather();
+        let counters: Vec<_> = families[0]
+            .get_metric()
+            .iter()
+            .map(|m| m.get_counter().get_value())
+            .collect();
+        assert_eq!(counters, vec![10.0, 10.0]);
+        let entries = families
+            .iter()
+            .find(|f| f.get_name() == "slice_cache_entries")
+            .unwrap();
+        assert_eq!(entries.get_metric()[0].get_gauge().get_value(), 10.0);
+
+        // labelled caches can be registered in the same registry
+        let r = prometheus::Registry::new();
+        for label in ["first", "second"] {
+            let labelled = ShardedSliceCache::new(1_000, 4).with_metric_label(label);
+            labelled.insert(0u32, &[0u8; 4]).unwrap();
+            labelled.register_metric(&r).unwrap();
+        }
+        let families = r.gather();
+        let entries = families
+            .iter()
+            .find(|f| f.get_name() == "slice_cache_entries")
+            .unwrap();
+        assert_eq!(entries.get_metric().len(), 2);
+    }
+}
@@ -0,0 +1,262 @@
+use core::hash::Hash;
+use std::io::{self, Read, Write};
+
+use alloc::{sync::Arc, vec::Vec};
+
+use super::{Entry, EvictionPolicy, Range, SliceCache};
+use crate::{AsSlice, FromSlice};
+
+const MAGIC: &[u8; 8] = b"bslcache";
+const VERSION: u8 = 1;
+
+/// Avoids allocating huge keys when reading corrupted snapshots
+const MAX_KEY_LEN: usize = 1 << 16;
+
+/// Wraps a reader or a writer computing the FNV-1a hash of the bytes going through
+struct Checksum<T> {
+    inner: T,
+    hash: u64,
+}
+
+impl<T> Checksum<T> {
+    fn new(inner: T) -> Self {
+        Checksum {
+            inner,
+            hash: 0xcbf29ce484222325,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.hash ^= *b as u64;
+            self.hash = self.hash.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+impl<W: Write> Write for Checksum<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Checksum<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<usize> {
+    let n = u64::from_le_bytes(read_array(reader)?);
+    usize::try_from(n).map_err(|_| invalid("length overflows usize"))
+}
+
+impl<K> SliceCache<K>
+where
+    K: Hash + PartialEq + Eq + core::fmt::Debug + AsSlice + for<'a> FromSlice<'a>,
+{
+    /// Write the buffer and the index of the cache in `writer`, followed by a checksum, so that
+    /// it can be restored with [`SliceCache::restore`], for example after a restart.
+    ///
+    /// The flags of [`EvictionPolicy::Clock`] are not saved.
+    pub fn write_snapshot<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = Checksum::new(writer);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, self.full as u8])?;
+        writer.write_all(&(self.buffer.len() as u64).to_le_bytes())?;
+        writer.write_all(&(self.free_pointer as u64).to_le_bytes())?;
+        writer.write_all(&(self.indexes.len() as u64).to_le_bytes())?;
+        for key in self.insertions.iter().rev().filter(|key| self.is_live(key)) {
+            let range = &self.indexes.get(key).expect("must be found").range;
+            let key = key.as_slice();
+            writer.write_all(&(key.len() as u32).to_le_bytes())?;
+            writer.write_all(key)?;
+            writer.write_all(&(range.begin() as u64).to_le_bytes())?;
+            writer.write_all(&(range.end() as u64).to_le_bytes())?;
+        }
+        writer.write_all(&self.buffer)?;
+        let hash = writer.hash;
+        writer.inner.write_all(&hash.to_le_bytes())?;
+        writer.flush()
+    }
+
+    /// Read a cache written with [`SliceCache::write_snapshot`], with a buffer of byte len `size`.
+    ///
+    /// Returns an [`io::ErrorKind::InvalidData`] error if the snapshot is corrupted or its buffer
+    /// has a different size.
+    pub fn read_snapshot<R: Read>(
+        reader: R,
+        size: usize,
+        policy: EvictionPolicy,
+    ) -> io::Result<Self> {
+        let mut reader = Checksum::new(reader);
+        if &read_array::<_, 8>(&mut reader)? != MAGIC {
+            return Err(invalid("not a slice cache snapshot"));
+        }
+        let [version, full] = read_array(&mut reader)?;
+        if version != VERSION {
+            return Err(invalid("unknown snapshot version"));
+        }
+        if read_u64(&mut reader)? != size {
+            return Err(invalid("snapshot has a different size"));
+        }
+        let mut cache = SliceCache::with_policy(size, policy);
+        cache.full = full != 0;
+        cache.free_pointer = read_u64(&mut reader)?;
+        if cache.free_pointer > size {
+            return Err(invalid("free pointer out of the buffer"));
+        }
+        let entries = read_u64(&mut reader)?;
+        let mut key_bytes = Vec::new();
+        for _ in 0..entries {
+            let key_len = u32::from_le_bytes(read_array(&mut reader)?) as usize;
+            if key_len > MAX_KEY_LEN {
+                return Err(invalid("key too long"));
+            }
+            key_bytes.resize(key_len, 0);
+            reader.read_exact(&mut key_bytes)?;
+            let key = K::from_slice(&key_bytes).map_err(|_| invalid("invalid key"))?;
+            let begin = read_u64(&mut reader)?;
+            let end = read_u64(&mut reader)?;
+            if begin > end || end > size {
+                return Err(invalid("entry out of the buffer"));
+            }
+            let key = Arc::new(key);
+            let range = Range::from_begin_len(begin, end - begin);
+            if cache
+                .indexes
+                .insert(key.clone(), Entry::new(range))
+                .is_some()
+            {
+                return Err(invalid("duplicated key"));
+            }
+            cache.insertions.push_front(key);
+            cache.used_bytes += end - begin;
+        }
+        reader.read_exact(&mut cache.buffer)?;
+        let hash = reader.hash;
+        if u64::from_le_bytes(read_array(&mut reader.inner)?) != hash {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        #[cfg(any(feature = "prometheus", feature = "metrics"))]
+        cache
+            .metrics
+            .add(cache.indexes.len() as i64, cache.used_bytes as i64);
+
+        Ok(cache)
+    }
+
+    /// Read a cache written with [`SliceCache::write_snapshot`] as [`SliceCache::read_snapshot`],
+    /// falling back to an empty cache if the snapshot can't be read or it's corrupted
+    pub fn restore<R: Read>(reader: R, size: usize, policy: EvictionPolicy) -> Self {
+        Self::read_snapshot(reader, size, policy)
+            .unwrap_or_else(|_| SliceCache::with_policy(size, policy))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bsl::Txid, EvictionPolicy, SliceCache};
+
+    #[test]
+    fn snapshot_restore() {
+        let mut cache = SliceCache::with_policy(100, EvictionPolicy::Clock);
+        for i in 0..20u8 {
+            cache.insert(Txid([i; 32]), &[i; 7]).unwrap();
+        }
+        assert!(cache.full());
+        let mut snapshot = Vec::new();
+        cache.write_snapshot(&mut snapshot).unwrap();
+
+        let mut restored =
+            SliceCache::read_snapshot(&snapshot[..], 100, EvictionPolicy::Clock).unwrap();
+        assert_eq!(restored.len(), cache.len());
+        assert!(restored.full());
+        for i in 0..20u8 {
+            assert_eq!(restored.get(&Txid([i; 32])), cache.get(&Txid([i; 32])));
+        }
+
+        // the restored cache keeps evicting in the same order
+        for i in 20..25u8 {
+            assert_eq!(
+                restored.insert(Txid([i; 32]), &[i; 9]).unwrap(),
+                cache.insert(Txid([i; 32]), &[i; 9]).unwrap()
+            );
+        }
+        assert_eq!(restored.buffer, cache.buffer);
+
+        // corrupted, truncated or differently sized snapshots give an empty cache
+        let mut corrupted = snapshot.clone();
+        corrupted[200] ^= 1;
+        let err = SliceCache::<Txid>::read_snapshot(&corrupted[..], 100, EvictionPolicy::Fifo)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let empty = SliceCache::<Txid>::restore(&corrupted[..], 100, EvictionPolicy::Fifo);
+        assert_eq!(empty.len(), 0);
+        assert!(!empty.full());
+        let truncated = &snapshot[..snapshot.len() - 1];
+        assert_eq!(
+            SliceCache::<Txid>::restore(truncated, 100, EvictionPolicy::Fifo).len(),
+            0
+        );
+        assert_eq!(
+            SliceCache::<Txid>::restore(&snapshot[..], 200, EvictionPolicy::Fifo).len(),
+            0
+        );
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn snapshot_restore_metrics() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let mut cache = SliceCache::new(100);
+        for i in 0..20u8 {
+            cache.insert(Txid([i; 32]), &[i; 7]).unwrap();
+        }
+        let mut snapshot = Vec::new();
+        cache.write_snapshot(&mut snapshot).unwrap();
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            let mut restored =
+                SliceCache::read_snapshot(&snapshot[..], 100, EvictionPolicy::Fifo).unwrap();
+            assert_eq!(restored.insert(Txid([20; 32]), &[20; 7]).unwrap(), 1);
+        });
+
+        let values = snapshotter.snapshot().into_vec();
+        let gauge = |name: &str| {
+            values
+                .iter()
+                .find(|(key, _, _, _)| key.key().name() == name)
+                .map(|(_, _, _, value)| match value {
+                    DebugValue::Gauge(g) => g.into_inner(),
+                    _ => panic!("{} is not a gauge", name),
+                })
+                .unwrap()
+        };
+        assert_eq!(gauge("slice_cache_entries"), cache.len() as f64);
+        assert_eq!(gauge("slice_cache_used_bytes"), (cache.len() * 7) as f64);
+    }
+}
@@ -0,0 +1,275 @@
+//! Interpretation of the transaction locktime and of the inputs sequence.
+//!
+//! The absolute lock of [`Transaction::locktime`] is a block height or a timestamp, while the
+//! [`TxIn::sequence`] encodes the BIP 68 relative lock and the BIP 125 replaceability signal.
+
+use core::ops::ControlFlow;
+
+use crate::{
+    bsl::{Transaction, TxIn},
+    Descend, Subtree, Visit, Visitor,
+};
+
+/// Locktime values below this threshold are block heights, the others are unix timestamps
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// The sequence of an input which disables the absolute locktime, if all inputs have it
+pub const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+
+/// Inputs with a sequence up to this value signal replaceability, as defined by BIP 125
+pub const MAX_BIP125_RBF_SEQUENCE: u32 = 0xffff_fffd;
+
+/// If set in the sequence, the input has no relative lock, as defined by BIP 68
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+
+/// If set in the sequence, the relative lock is in units of 512 seconds, otherwise in blocks
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+
+/// The bits of the sequence containing the relative lock value
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_ffff;
+
+/// The relative time locks are in units of `1 << SEQUENCE_LOCKTIME_GRANULARITY` seconds
+pub const SEQUENCE_LOCKTIME_GRANULARITY: u32 = 9;
+
+/// The absolute lock of a transaction, see [`Transaction::absolute_lock_time`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockTime {
+    /// The transaction can't be included in a block with height lower or equal than this value
+    Height(u32),
+
+    /// The transaction can't be included in a block with median time past lower or equal than
+    /// this unix timestamp
+    Time(u32),
+}
+
+impl LockTime {
+    /// Interprets the locktime field of a transaction
+    pub fn from_consensus(locktime: u32) -> Self {
+        if locktime < LOCKTIME_THRESHOLD {
+            LockTime::Height(locktime)
+        } else {
+            LockTime::Time(locktime)
+        }
+    }
+
+    /// Returns the locktime field of a transaction
+    pub fn to_consensus(self) -> u32 {
+        match self {
+            LockTime::Height(value) | LockTime::Time(value) => value,
+        }
+    }
+
+    /// Returns true if the lock is expired for a transaction included in a block at `height` with
+    /// median time past `mtp`
+    pub fn is_satisfied_by(self, height: u32, mtp: u32) -> bool {
+        match self {
+            LockTime::Height(value) => value < height,
+            LockTime::Time(value) => value < mtp,
+        }
+    }
+}
+
+/// The relative lock of a transaction input, as defined by BIP 68, see
+/// [`TxIn::relative_lock_time`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelativeLockTime {
+    /// The input can't be included until this number of blocks is mined after the one containing
+    /// the spent output
+    Blocks(u16),
+
+    /// The input can't be included until this number of 512 seconds intervals passed since the
+    /// median time past of the block before the one containing the spent output
+    Time(u16),
+}
+
+impl RelativeLockTime {
+    /// Interprets the sequence of an input, `None` if the relative lock is disabled
+    pub fn from_sequence(sequence: u32) -> Option<Self> {
+        if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return None;
+        }
+        let value = (sequence & SEQUENCE_LOCKTIME_MASK) as u16;
+        Some(if sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            RelativeLockTime::Time(value)
+        } else {
+            RelativeLockTime::Blocks(value)
+        })
+    }
+
+    /// Returns the lock duration in seconds, `None` if the lock is in blocks
+    pub fn seconds(self) -> Option<u32> {
+        match self {
+            RelativeLockTime::Time(value) => Some((value as u32) << SEQUENCE_LOCKTIME_GRANULARITY),
+            RelativeLockTime::Blocks(_) => None,
+        }
+    }
+}
+
+impl<'a> TxIn<'a> {
+    /// Returns the BIP 68 relative lock of this input, `None` if disabled by the flag in the
+    /// sequence.
+    ///
+    /// The relative lock is enforced only in transactions with version 2 or greater.
+    pub fn relative_lock_time(&self) -> Option<RelativeLockTime> {
+        RelativeLockTime::from_sequence(self.sequence())
+    }
+
+    /// Returns true if the sequence has the BIP 68 disable flag set
+    pub fn is_relative_lock_time_disabled(&self) -> bool {
+        self.sequence() & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0
+    }
+
+    /// Returns true if this input signals the transaction is replaceable, as defined by BIP 125
+    pub fn signals_rbf(&self) -> bool {
+        self.sequence() <= MAX_BIP125_RBF_SEQUENCE
+    }
+
+    /// Returns true if the sequence is [`SEQUENCE_FINAL`]
+    pub fn is_final(&self) -> bool {
+        self.sequence() == SEQUENCE_FINAL
+    }
+}
+
+/// Looks for an input satisfying the predicate, stopping at the first one
+struct AnyInput<F>(F);
+
+impl<F: FnMut(&TxIn) -> bool> Visitor for AnyInput<F> {
+    fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<(), Descend> {
+        match subtree {
+            Subtree::TxIns => ControlFlow::Continue(Descend::Visit),
+            _ => ControlFlow::Continue(Descend::Skip),
+        }
+    }
+    fn visit_tx_in(&mut self, _vin: usize, tx_in: &TxIn) -> ControlFlow<()> {
+        if (self.0)(tx_in) {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+}
+
+fn any_input(tx: &Transaction, predicate: impl FnMut(&TxIn) -> bool) -> bool {
+    Transaction::visit(tx.as_ref(), &mut AnyInput(predicate)).is_err()
+}
+
+impl<'a> Transaction<'a> {
+    /// Returns the locktime interpreted as a block height or a timestamp
+    pub fn absolute_lock_time(&self) -> LockTime {
+        LockTime::from_consensus(self.locktime())
+    }
+
+    /// Returns true if any input signals the transaction is replaceable, as defined by BIP 125
+    pub fn signals_rbf(&self) -> bool {
+        any_input(self, |tx_in| tx_in.signals_rbf())
+    }
+
+    /// Returns true if the transaction can be included in a block at `height` with median time
+    /// past `mtp`, with regard to its absolute lock, like `IsFinalTx` in Bitcoin Core.
+    ///
+    /// The locktime is ignored if it's zero or if every input has a [`SEQUENCE_FINAL`] sequence.
+    pub fn is_final(&self, height: u32, mtp: u32) -> bool {
+        let locktime = self.locktime();
+        locktime == 0
+            || LockTime::from_consensus(locktime).is_satisfied_by(height, mtp)
+            || !any_input(self, |tx_in| !tx_in.is_final())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hex_lit::hex;
+
+    use super::{LockTime, RelativeLockTime};
+    use crate::{
+        bsl::{Transaction, TxIn},
+        test_common::GENESIS_TX,
+        Parse,
+    };
+
+    /// A transaction with one input for every given sequence and the given locktime
+    fn tx(sequences: &[u32], locktime: u32) -> Vec<u8> {
+        let mut tx = hex!("02000000").to_vec();
+        tx.push(sequences.len() as u8);
+        for (i, sequence) in sequences.iter().enumerate() {
+            tx.extend_from_slice(&[0xaa; 32]);
+            tx.extend_from_slice(&(i as u32).to_le_bytes());
+            tx.push(0);
+            tx.extend_from_slice(&sequence.to_le_bytes());
+        }
+        tx.extend_from_slice(&hex!("01000000000000000000"));
+        tx.extend_from_slice(&locktime.to_le_bytes());
+        tx
+    }
+
+    #[test]
+    fn lock_time() {
+        assert_eq!(LockTime::from_consensus(0), LockTime::Height(0));
+        assert_eq!(
+            LockTime::from_consensus(499_999_999),
+            LockTime::Height(499_999_999)
+        );
+        assert_eq!(
+            LockTime::from_consensus(500_000_000),
+            LockTime::Time(500_000_000)
+        );
+        assert_eq!(LockTime::Time(1_700_000_000).to_consensus(), 1_700_000_000);
+
+        assert!(LockTime::Height(100).is_satisfied_by(101, 0));
+        assert!(!LockTime::Height(100).is_satisfied_by(100, u32::MAX));
+        assert!(LockTime::Time(1_700_000_000).is_satisfied_by(0, 1_700_000_001));
+        assert!(!LockTime::Time(1_700_000_000).is_satisfied_by(u32::MAX, 1_700_000_000));
+    }
+
+    #[test]
+    fn relative_lock_time() {
+        assert_eq!(RelativeLockTime::from_sequence(0xffff_ffff), None);
+        assert_eq!(RelativeLockTime::from_sequence(1 << 31), None);
+        assert_eq!(
+            RelativeLockTime::from_sequence(144),
+            Some(RelativeLockTime::Blocks(144))
+        );
+        // bits outside the type flag and the mask are ignored
+        assert_eq!(
+            RelativeLockTime::from_sequence(0x0040_0000 | 0x0001_0000 | 10),
+            Some(RelativeLockTime::Time(10))
+        );
+        assert_eq!(RelativeLockTime::Time(10).seconds(), Some(5120));
+        assert_eq!(RelativeLockTime::Blocks(10).seconds(), None);
+
+        let tx_in = hex!(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa0000000000fdffffff"
+        );
+        let tx_in = TxIn::parse(&tx_in[..]).unwrap().parsed_owned();
+        assert!(tx_in.signals_rbf());
+        assert!(!tx_in.is_final());
+        assert!(tx_in.is_relative_lock_time_disabled());
+        assert_eq!(tx_in.relative_lock_time(), None);
+    }
+
+    #[test]
+    fn transaction() {
+        let genesis = Transaction::parse(&GENESIS_TX[..]).unwrap().parsed_owned();
+        assert_eq!(genesis.absolute_lock_time(), LockTime::Height(0));
+        assert!(!genesis.signals_rbf());
+        assert!(genesis.is_final(0, 0));
+
+        let bytes = tx(&[0xffff_ffff, 0xffff_fffe], 800_000);
+        let tx_locked = Transaction::parse(&bytes[..]).unwrap().parsed_owned();
+        assert_eq!(tx_locked.absolute_lock_time(), LockTime::Height(800_000));
+        assert!(!tx_locked.signals_rbf());
+        assert!(!tx_locked.is_final(800_000, u32::MAX));
+        assert!(tx_locked.is_final(800_001, 0));
+
+        let bytes = tx(&[0xffff_ffff, 0xffff_ffff], 800_000);
+        let tx_final = Transaction::parse(&bytes[..]).unwrap().parsed_owned();
+        assert!(tx_final.is_final(0, 0));
+
+        let bytes = tx(&[0xffff_ffff, 0xffff_fffd], 1_700_000_000);
+        let tx_rbf = Transaction::parse(&bytes[..]).unwrap().parsed_owned();
+        assert_eq!(tx_rbf.absolute_lock_time(), LockTime::Time(1_700_000_000));
+        assert!(tx_rbf.signals_rbf());
+        assert!(!tx_rbf.is_final(u32::MAX, 1_700_000_000));
+        assert!(tx_rbf.is_final(0, 1_700_000_001));
+    }
+}
@@ -0,0 +1,420 @@
+//! Maintenance of the set of unspent transaction outputs in a redb database.
+//!
+//! [`connect_block`] applies a block to the [`UTXOS`] table, removing the outputs spent by the
+//! block inputs and inserting the outputs it creates. The spent outputs are saved in the
+//! [`UNDO`] table, so that [`disconnect_block`] can revert the block during a reorg.
+//!
+//! Provably unspendable outputs, starting with `OP_RETURN` or with a script longer than
+//! [`MAX_SCRIPT_SIZE`], are never inserted. Neither is the output of the genesis coinbase, which
+//! can't be spent since Bitcoin Core doesn't add it to its UTXO set.
+
+use core::{fmt, ops::ControlFlow};
+use std::vec::Vec;
+
+use redb::{Table, TableDefinition, WriteTransaction};
+
+use crate::{
+    bsl::{Block, OutPoint, Transaction, TxIn, TxOut},
+    Descend, FromSlice, Parse, Stop, Subtree, Visit, Visitor,
+};
+
+/// The maximum size of a script, outputs with longer scripts are unspendable
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// The unspent outputs, keyed by the outpoint creating them
+pub const UTXOS: TableDefinition<OutPoint, TxOut> = TableDefinition::new("utxos");
+
+/// The outputs spent by the block at the given height, in block order. Every entry is the
+/// serialized outpoint followed by the serialized output.
+pub const UNDO: TableDefinition<u32, &[u8]> = TableDefinition::new("undo");
+
+type UtxoTable<'db, 'txn> = Table<'db, 'txn, OutPoint<'static>, TxOut<'static>>;
+
+/// The reasons a block can't be connected or disconnected
+#[derive(Debug)]
+pub enum UtxoError {
+    /// The database returned an error
+    Db(redb::Error),
+
+    /// The input `vin` of the transaction at index `tx` in the block spends an output which is not
+    /// in the UTXO set
+    MissingPrevout {
+        /// The index of the transaction in the block
+        tx: usize,
+        /// The index of the input in the transaction
+        vin: usize,
+    },
+
+    /// There is no undo data for the block at the given height
+    MissingUndo(u32),
+
+    /// There is already undo data at the given height, the block there must be disconnected
+    /// before connecting another one
+    UndoPresent(u32),
+}
+
+impl fmt::Display for UtxoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UtxoError::Db(e) => write!(f, "database error: {}", e),
+            UtxoError::MissingPrevout { tx, vin } => {
+                write!(f, "tx {} input {} spends a missing output", tx, vin)
+            }
+            UtxoError::MissingUndo(height) => write!(f, "missing undo data at height {}", height),
+            UtxoError::UndoPresent(height) => {
+                write!(f, "undo data already present at height {}", height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UtxoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UtxoError::Db(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E: Into<redb::Error>> From<E> for UtxoError {
+    fn from(e: E) -> Self {
+        UtxoError::Db(e.into())
+    }
+}
+
+/// Returns true if the output can never be spent, so it's not worth keeping in the UTXO set
+pub fn is_unspendable(script_pubkey: &[u8]) -> bool {
+    script_pubkey.first() == Some(&0x6a) || script_pubkey.len() > MAX_SCRIPT_SIZE
+}
+
+/// Returns the serialized outpoint of the output `vout` of `txid`
+pub(crate) fn outpoint(txid: &[u8; 32], vout: usize) -> [u8; 36] {
+    let mut bytes = [0u8; 36];
+    bytes[..32].copy_from_slice(txid);
+    bytes[32..].copy_from_slice(&(vout as u32).to_le_bytes());
+    bytes
+}
+
+/// Visits the transactions of a block, to visit again each one knowing its txid
+struct Transactions<F> {
+    index: usize,
+    on_tx: F,
+}
+
+impl<E, F> Visitor<E> for Transactions<F>
+where
+    F: FnMut(usize, &Transaction) -> Result<(), E>,
+{
+    fn visit_subtree(&mut self, _subtree: Subtree) -> ControlFlow<E, Descend> {
+        ControlFlow::Continue(Descend::Skip)
+    }
+    fn visit_transaction(&mut self, tx: &Transaction) -> ControlFlow<E> {
+        let index = self.index;
+        self.index += 1;
+        match (self.on_tx)(index, tx) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(e) => ControlFlow::Break(e),
+        }
+    }
+}
+
+/// Calls `on_tx` with the index and the transaction for every transaction in `block`, stopping at
+/// the first error
+pub(crate) fn for_each_tx<E, F>(block: &Block, on_tx: F) -> Result<(), E>
+where
+    F: FnMut(usize, &Transaction) -> Result<(), E>,
+{
+    let mut visitor = Transactions { index: 0, on_tx };
+    Block::visit_break(block.as_ref(), &mut visitor).map_err(|stop| match stop {
+        Stop::Break { value, .. } => value,
+        Stop::Error(_) => unreachable!("block already parsed"),
+    })?;
+    Ok(())
+}
+
+/// Spends the inputs of a transaction, saving the spent outputs in the undo data, and inserts
+/// its outputs
+struct Connect<'t, 'db, 'txn> {
+    utxos: &'t mut UtxoTable<'db, 'txn>,
+    undo: &'t mut Vec<u8>,
+    txid: [u8; 32],
+    tx: usize,
+    /// The outputs are not inserted, as for the genesis coinbase
+    skip_outputs: bool,
+}
+
+impl Visitor<UtxoError> for Connect<'_, '_, '_> {
+    fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<UtxoError, Descend> {
+        match subtree {
+            Subtree::TxIns if self.tx == 0 => ControlFlow::Continue(Descend::Skip),
+            Subtree::TxOuts if self.skip_outputs => ControlFlow::Continue(Descend::Skip),
+            Subtree::Witnesses => ControlFlow::Continue(Descend::Skip),
+            _ => ControlFlow::Continue(Descend::Visit),
+        }
+    }
+    fn visit_tx_in(&mut self, vin: usize, tx_in: &TxIn) -> ControlFlow<UtxoError> {
+        let spent = match self.utxos.remove(tx_in.prevout()) {
+            Ok(Some(spent)) => spent,
+            Ok(None) => return ControlFlow::Break(UtxoError::MissingPrevout { tx: self.tx, vin }),
+            Err(e) => return ControlFlow::Break(e.into()),
+        };
+        self.undo.extend_from_slice(tx_in.prevout().as_ref());
+        self.undo.extend_from_slice(spent.value().as_ref());
+        ControlFlow::Continue(())
+    }
+    fn visit_tx_out(&mut self, vout: usize, tx_out: &TxOut) -> ControlFlow<UtxoError> {
+        if is_unspendable(tx_out.script_pubkey()) {
+            return ControlFlow::Continue(());
+        }
+        let outpoint = outpoint(&self.txid, vout);
+        match self
+            .utxos
+            .insert(&OutPoint::from_trusted_slice(&outpoint[..]), tx_out)
+        {
+            Ok(_) => ControlFlow::Continue(()),
+            Err(e) => ControlFlow::Break(e.into()),
+        }
+    }
+}
+
+/// Applies `block` at `height` to the [`UTXOS`] table, saving the spent outputs in the [`UNDO`]
+/// table.
+///
+/// Returns [`UtxoError::UndoPresent`] without changes if a block is already connected at
+/// `height`, since overwriting its undo data would make it impossible to disconnect. On other
+/// errors the write transaction contains a partially applied block and should be aborted.
+///
+/// The duplicate coinbases before BIP 30 overwrite the previous outputs, as in Bitcoin Core.
+pub fn connect_block(txn: &WriteTransaction, block: &Block, height: u32) -> Result<(), UtxoError> {
+    use crate::bitcoin_hashes::Hash;
+    use redb::ReadableTable;
+
+    let mut undo_table = txn.open_table(UNDO)?;
+    if undo_table.get(height)?.is_some() {
+        return Err(UtxoError::UndoPresent(height));
+    }
+    let mut utxos = txn.open_table(UTXOS)?;
+    let mut undo = Vec::new();
+    for_each_tx(block, |index, tx| {
+        let mut connect = Connect {
+            utxos: &mut utxos,
+            undo: &mut undo,
+            txid: tx.txid().to_byte_array(),
+            tx: index,
+            skip_outputs: height == 0 && index == 0,
+        };
+        match tx.visit_subtrees(&mut connect) {
+            ControlFlow::Continue(()) => Ok(()),
+            ControlFlow::Break(e) => Err(e),
+        }
+    })?;
+    undo_table.insert(height, &undo[..])?;
+    Ok(())
+}
+
+/// Reverts `block` at `height`, previously applied with [`connect_block`]: restores the outputs
+/// spent by the block from the [`UNDO`] table, then removes the outputs created by the block.
+///
+/// On error the write transaction contains a partially reverted block and should be aborted.
+pub fn disconnect_block(
+    txn: &WriteTransaction,
+    block: &Block,
+    height: u32,
+) -> Result<(), UtxoError> {
+    use crate::bitcoin_hashes::Hash;
+
+    let mut utxos = txn.open_table(UTXOS)?;
+    let mut undo_table = txn.open_table(UNDO)?;
+    {
+        let undo = undo_table
+            .remove(height)?
+            .ok_or(UtxoError::MissingUndo(height))?;
+        let mut remaining = undo.value();
+        while !remaining.is_empty() {
+            let outpoint = OutPoint::parse(remaining).expect("undo data written by connect_block");
+            let tx_out =
+                TxOut::parse(outpoint.remaining()).expect("undo data written by connect_block");
+            utxos.insert(outpoint.parsed(), tx_out.parsed())?;
+            remaining = tx_out.remaining();
+        }
+    }
+
+    // outputs created and spent in the block are restored above and removed here
+    for_each_tx(block, |_, tx| {
+        let mut disconnect = Disconnect {
+            utxos: &mut utxos,
+            txid: tx.txid().to_byte_array(),
+        };
+        match tx.visit_subtrees(&mut disconnect) {
+            ControlFlow::Continue(()) => Ok(()),
+            ControlFlow::Break(e) => Err(e),
+        }
+    })
+}
+
+/// Removes the outputs of a transaction
+struct Disconnect<'t, 'db, 'txn> {
+    utxos: &'t mut UtxoTable<'db, 'txn>,
+    txid: [u8; 32],
+}
+
+impl Visitor<UtxoError> for Disconnect<'_, '_, '_> {
+    fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<UtxoError, Descend> {
+        match subtree {
+            Subtree::TxOuts => ControlFlow::Continue(Descend::Visit),
+            _ => ControlFlow::Continue(Descend::Skip),
+        }
+    }
+    fn visit_tx_out(&mut self, vout: usize, _tx_out: &TxOut) -> ControlFlow<UtxoError> {
+        let outpoint = outpoint(&self.txid, vout);
+        match self
+            .utxos
+            .remove(&OutPoint::from_trusted_slice(&outpoint[..]))
+        {
+            Ok(_) => ControlFlow::Continue(()),
+            Err(e) => ControlFlow::Break(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use redb::ReadableTable;
+
+    use super::{connect_block, disconnect_block, UtxoError, UNDO, UTXOS};
+    use crate::{
+        bitcoin_hashes::Hash,
+        bsl::{Block, Transaction},
+        builder::TransactionWriter,
+        test_common::GENESIS_BLOCK,
+        Parse,
+    };
+
+    /// The utxos in the table, serialized outpoint and output, sorted
+    fn utxos(db: &redb::Database) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(UTXOS).unwrap();
+        table
+            .iter()
+            .unwrap()
+            .map(|e| {
+                let (k, v) = e.unwrap();
+                (k.value().as_ref().to_vec(), v.value().as_ref().to_vec())
+            })
+            .collect()
+    }
+
+    fn tx(prevout: ([u8; 32], u32), outputs: &[(u64, &[u8])]) -> Vec<u8> {
+        let mut writer = TransactionWriter::new(Vec::new(), 2, 1, false).unwrap();
+        writer.tx_in(&prevout.0, prevout.1, &[0x51], 0).unwrap();
+        writer.tx_outs(outputs.len()).unwrap();
+        for (value, script) in outputs {
+            writer.tx_out(*value, script).unwrap();
+        }
+        writer.finish(0).unwrap()
+    }
+
+    fn txid(tx: &[u8]) -> [u8; 32] {
+        let tx = Transaction::parse(tx).unwrap().parsed_owned();
+        tx.txid().to_byte_array()
+    }
+
+    /// A block with the genesis header and the given transactions
+    fn block(txs: &[&[u8]]) -> Vec<u8> {
+        let mut block_bytes = GENESIS_BLOCK[..80].to_vec();
+        block_bytes.push(txs.len() as u8);
+        for tx in txs {
+            block_bytes.extend_from_slice(tx);
+        }
+        block_bytes
+    }
+
+    /// A coinbase with the given script sig, to make it unique, and outputs
+    fn coinbase(script_sig: &[u8], outputs: &[(u64, &[u8])]) -> Vec<u8> {
+        let mut coinbase = TransactionWriter::new(Vec::new(), 1, 1, false).unwrap();
+        coinbase.tx_in(&[0u8; 32], u32::MAX, script_sig, 0).unwrap();
+        coinbase.tx_outs(outputs.len()).unwrap();
+        for (value, script) in outputs {
+            coinbase.tx_out(*value, script).unwrap();
+        }
+        coinbase.finish(0).unwrap()
+    }
+
+    #[test]
+    fn connect_disconnect() {
+        let genesis = Block::parse(&GENESIS_BLOCK[..]).unwrap().parsed_owned();
+
+        // the first block funds the second one: a coinbase, a tx spending the first block
+        // coinbase and one spending an output of the former
+        let funding = coinbase(&[0x51], &[(100, &[0x55])]);
+        let block1_bytes = block(&[&funding]);
+        let block1 = Block::parse(&block1_bytes[..]).unwrap().parsed_owned();
+        let coinbase = coinbase(&[0x51, 0x51], &[(50, &[0x51]), (0, &[0x6a, 0x00])]);
+        let tx1 = tx((txid(&funding), 0), &[(10, &[0x52]), (20, &[0x53])]);
+        let tx2 = tx((txid(&tx1), 0), &[(5, &[0x54])]);
+        let block_bytes = block(&[&coinbase, &tx1, &tx2]);
+        let block = Block::parse(&block_bytes[..]).unwrap().parsed_owned();
+
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let db = redb::Database::create(path).unwrap();
+        let write_txn = db.begin_write().unwrap();
+        connect_block(&write_txn, &genesis, 0).unwrap();
+        write_txn.commit().unwrap();
+        // the genesis coinbase output is unspendable
+        assert!(utxos(&db).is_empty());
+
+        let write_txn = db.begin_write().unwrap();
+        connect_block(&write_txn, &block1, 1).unwrap();
+        write_txn.commit().unwrap();
+        let after_block1 = utxos(&db);
+        assert_eq!(after_block1.len(), 1);
+
+        let write_txn = db.begin_write().unwrap();
+        connect_block(&write_txn, &block, 2).unwrap();
+        write_txn.commit().unwrap();
+        let after_block = utxos(&db);
+        // coinbase output, second output of tx1 and tx2 output, the OP_RETURN is skipped
+        assert_eq!(after_block.len(), 3);
+        let scripts: Vec<_> = after_block.iter().map(|(_, v)| v[9]).collect();
+        assert!(scripts.contains(&0x51));
+        assert!(scripts.contains(&0x53));
+        assert!(scripts.contains(&0x54));
+        {
+            let read_txn = db.begin_read().unwrap();
+            let undo = read_txn.open_table(UNDO).unwrap();
+            // two spent outputs: the first block coinbase and the first output of tx1
+            let undo = undo.get(2).unwrap().unwrap();
+            assert_eq!(undo.value().len(), 36 + 10 + 36 + 10);
+        }
+
+        // a competing block at the same height requires disconnecting the current one
+        let write_txn = db.begin_write().unwrap();
+        assert!(matches!(
+            connect_block(&write_txn, &block, 2),
+            Err(UtxoError::UndoPresent(2))
+        ));
+        write_txn.abort().unwrap();
+        assert_eq!(utxos(&db), after_block);
+
+        let write_txn = db.begin_write().unwrap();
+        disconnect_block(&write_txn, &block, 2).unwrap();
+        write_txn.commit().unwrap();
+        assert_eq!(utxos(&db), after_block1);
+
+        let write_txn = db.begin_write().unwrap();
+        assert!(matches!(
+            disconnect_block(&write_txn, &block, 2),
+            Err(UtxoError::MissingUndo(2))
+        ));
+        write_txn.abort().unwrap();
+
+        let write_txn = db.begin_write().unwrap();
+        connect_block(&write_txn, &block, 2).unwrap();
+        assert!(matches!(
+            connect_block(&write_txn, &block, 3),
+            Err(UtxoError::MissingPrevout { tx: 1, vin: 0 })
+        ));
+        write_txn.abort().unwrap();
+    }
+}
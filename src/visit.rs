@@ -1,4 +1,6 @@
-use crate::SResult;
+use core::ops::ControlFlow;
+
+use crate::{BResult, Error, ParseResult, SResult};
 
 use super::bsl;
 
@@ -22,8 +24,21 @@ pub trait Parse<'a>: Sized + AsRef<[u8]> {
 /// while consuming the slice it calls methods on the provided visitor.
 #[allow(clippy::len_without_is_empty)]
 pub trait Visit<'a>: Sized + AsRef<[u8]> {
+    /// Visit the object from the slice while calling methods on the given visitor, which may stop
+    /// the visit by returning `ControlFlow::Break(value)` from a callback.
+    ///
+    /// In that case [`Stop::Break`] is returned with the `value` and the position where parsing
+    /// stopped.
+    fn visit_break<'b, B, V: Visitor<B>>(slice: &'a [u8], visit: &'b mut V)
+        -> BResult<'a, Self, B>;
+
     /// Visit the object from the slice while calling methods on the given visitor
-    fn visit<'b, V: Visitor>(slice: &'a [u8], visit: &'b mut V) -> SResult<'a, Self>;
+    ///
+    /// If the visitor breaks, [`Error::VisitBreak`] is returned, use [`Visit::visit_break`] to
+    /// get a value out of the visitor.
+    fn visit<'b, V: Visitor>(slice: &'a [u8], visit: &'b mut V) -> SResult<'a, Self> {
+        Self::visit_break(slice, visit).map_err(Stop::into_error)
+    }
 
     /// Self visit calling methods on the given visitor.
     ///
@@ -56,39 +71,56 @@ impl<'a, T: Visit<'a>> Parse<'a> for T {
 /// Traits with single function would have been more precise, however, it would have required the
 /// user to implement those trait with an empty block which was considered too verbose. Morever it
 /// looks a single trait with many functions is more perfomant.
+///
+/// To run many visitors in a single pass, combine them in a tuple or an array, for example of
+/// `&mut dyn Visitor`, see [`crate::combinators`].
+///
+/// Callbacks can stop the visit by returning `ControlFlow::Break`, the break value `B` is returned
+/// in [`Stop::Break`] by [`Visit::visit_break`], while [`Visit::visit`] requires `B = ()` and
+/// returns [`Error::VisitBreak`].
 #[allow(unused)]
-pub trait Visitor {
+pub trait Visitor<B = ()> {
     /// Visit the block header, called from [`bsl::Block::visit()`] and [`bsl::BlockHeader::visit()`]
-    fn visit_block_header(&mut self, header: &bsl::BlockHeader) -> core::ops::ControlFlow<()> {
-        core::ops::ControlFlow::Continue(())
+    fn visit_block_header(&mut self, header: &bsl::BlockHeader) -> ControlFlow<B> {
+        ControlFlow::Continue(())
     }
     /// Visit the number of transactions in a block, called from [`bsl::Block::visit()`]
     fn visit_block_begin(&mut self, total_transactions: usize) {}
 
+    /// Called from [`bsl::Transaction::visit()`] before visiting the given `subtree` of the current
+    /// transaction.
+    ///
+    /// Returning `Continue(Descend::Skip)` skips the callbacks of the subtree, which is parsed by
+    /// length only, while the visit goes on with the rest of the transaction.
+    /// Returning `Break` aborts the visit like the other callbacks.
+    fn visit_subtree(&mut self, subtree: Subtree) -> ControlFlow<B, Descend> {
+        ControlFlow::Continue(Descend::Visit)
+    }
+
     /// Visit a transaction, called from  [`bsl::Block::visit()`] and  [`bsl::Transaction::visit()`]
     ///
     /// Note you can't access inputs and outputs from the transaction, you need [`Visitor::visit_tx_ins()`]
     /// or [`Visitor::visit_tx_outs()`]
-    fn visit_transaction(&mut self, tx: &bsl::Transaction) -> core::ops::ControlFlow<()> {
-        core::ops::ControlFlow::Continue(())
+    fn visit_transaction(&mut self, tx: &bsl::Transaction) -> ControlFlow<B> {
+        ControlFlow::Continue(())
     }
 
     /// We are going to visit `total_inputs` transaction inputs
     fn visit_tx_ins(&mut self, total_inputs: usize) {}
     /// Visit transaction input at position `vin`
-    fn visit_tx_in(&mut self, vin: usize, tx_in: &bsl::TxIn) -> core::ops::ControlFlow<()> {
-        core::ops::ControlFlow::Continue(())
+    fn visit_tx_in(&mut self, vin: usize, tx_in: &bsl::TxIn) -> ControlFlow<B> {
+        ControlFlow::Continue(())
     }
     /// We are going to visit `total_outputs` transaction outputs
     fn visit_tx_outs(&mut self, total_outputs: usize) {}
     /// Visit transaction output at position `vout`
-    fn visit_tx_out(&mut self, vout: usize, tx_out: &bsl::TxOut) -> core::ops::ControlFlow<()> {
-        core::ops::ControlFlow::Continue(())
+    fn visit_tx_out(&mut self, vout: usize, tx_out: &bsl::TxOut) -> ControlFlow<B> {
+        ControlFlow::Continue(())
     }
 
     /// We are going to visit the witnes of the `vin` input
-    fn visit_witness(&mut self, vin: usize) -> core::ops::ControlFlow<()> {
-        core::ops::ControlFlow::Continue(())
+    fn visit_witness(&mut self, vin: usize) -> ControlFlow<B> {
+        ControlFlow::Continue(())
     }
     /// The following witness has `witness_total` element
     fn visit_witness_total_element(&mut self, witness_total: usize) {}
@@ -98,9 +130,86 @@ pub trait Visitor {
     fn visit_witness_end(&mut self) {}
 }
 
+/// A part of a transaction that could be skipped while visiting, see [`Visitor::visit_subtree`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subtree {
+    /// The transaction inputs
+    TxIns,
+    /// The transaction outputs
+    TxOuts,
+    /// The witnesses of the transaction inputs, only present in segwit transactions
+    Witnesses,
+}
+
+/// Returned by [`Visitor::visit_subtree`] to decide if the callbacks of a subtree are called
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Descend {
+    /// Call the visitor on every element of the subtree
+    Visit,
+    /// Parse the subtree by length only without calling the visitor
+    Skip,
+}
+
 /// A visitor with all empty function.
 ///
 /// When `visit()` is present in structs, the `parse()` method is constructed by calling `visit`
 /// with this empty visitor.
 pub struct EmptyVisitor {}
-impl Visitor for EmptyVisitor {}
+impl<B> Visitor<B> for EmptyVisitor {}
+
+/// Returned by [`Visit::visit_break`] when the visit doesn't complete.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop<'a, B> {
+    /// Parsing failed
+    Error(Error),
+
+    /// The visitor returned `ControlFlow::Break(value)`.
+    ///
+    /// `remaining` contains the bytes following the element passed to the callback that broke, so
+    /// that parsing could be resumed from there. For example, breaking in
+    /// [`Visitor::visit_transaction`] while visiting a block leaves the following transactions in
+    /// `remaining`.
+    Break {
+        /// The value returned by the visitor
+        value: B,
+        /// The bytes after the position where parsing stopped
+        remaining: &'a [u8],
+    },
+}
+
+impl<'a, B> Stop<'a, B> {
+    /// Convert into [`Error`], a break is converted into [`Error::VisitBreak`]
+    pub fn into_error(self) -> Error {
+        match self {
+            Stop::Error(e) => e,
+            Stop::Break { .. } => Error::VisitBreak,
+        }
+    }
+
+    /// Returns the break value if the visitor broke, `None` if parsing failed
+    pub fn into_break(self) -> Option<B> {
+        match self {
+            Stop::Error(_) => None,
+            Stop::Break { value, .. } => Some(value),
+        }
+    }
+}
+
+impl<'a, B> From<Error> for Stop<'a, B> {
+    fn from(e: Error) -> Self {
+        Stop::Error(e)
+    }
+}
+
+/// Convert the result of a visitor callback, `remaining` are the bytes after the visited element.
+#[inline(always)]
+pub(crate) fn check<'a, B, T: AsRef<[u8]>>(
+    flow: ControlFlow<B>,
+    remaining: &'a [u8],
+    parsed: T,
+) -> BResult<'a, T, B> {
+    match flow {
+        ControlFlow::Continue(_) => Ok(ParseResult::new(remaining, parsed)),
+        ControlFlow::Break(value) => Err(Stop::Break { value, remaining }),
+    }
+}